@@ -1,15 +1,23 @@
-use crate::{asan_intrinsic::*, asan_runtime::__asan_mem_check};
-use heapless::FnvIndexMap;
-use libc::{c_char, c_void, size_t};
+use crate::{
+    asan_intrinsic::*,
+    asan_leak::{register_chunk, unregister_chunk},
+    asan_runtime::{__asan_mem_check, report_alloc_dealloc_mismatch},
+};
+use libc::{c_char, c_int, c_void, size_t};
+pub use sanitizer_common::reentrance::{is_reentered, with_reentered, MALLOC_REENTERED};
+use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::ptr;
-use std::sync::Mutex;
-use std::ffi::CStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Once;
 
 pub const ALLOC_STACK: u8 = 0x1;
 pub const ALLOC_HEAP: u8 = 0x2;
 
 pub const STACK_LEFT_REDZONE_MARKER: i8 = -0x10;
 pub const STACK_RIGHT_REDZONE_MARKER: i8 = -0x11;
+pub const STACK_AFTER_RETURN_MARKER: i8 = -0x12;
+pub const STACK_AFTER_SCOPE_MARKER: i8 = -0x13;
 pub const HEAP_LEFT_REDZONE_MARKER: i8 = -0x20;
 pub const HEAP_RIGHT_REDZONE_MARKER: i8 = -0x21;
 pub const FREED_MARKER: i8 = -0x30;
@@ -17,105 +25,522 @@ pub const CLEAN_BYTE_MARKER: i8 = 0x00;
 
 pub const SHADOW_SCALE: usize = 3;
 pub const REDZONE_SIZE: usize = 32;
-pub const SHADOW_SIZE: usize = 1 << 32; // mapping 4GB. We can extend to maximum system memory
-                                        // (on-demand paging). Increase this value if collision is
-                                        // found
-const ALLOC_MAP_SIZE: usize = 1 << 15;
+// shadow memory of the whole user address space (47 bits) is reserved at a fixed offset, so
+// that the shadow byte of `addr` is `(addr >> SHADOW_SCALE) + SHADOW_OFFSET`. Instrumented code
+// computes the same address inline, thus both values must be kept in sync with the instrument
+// crate.
+pub const SHADOW_OFFSET: usize = 0x7fff8000;
+pub const SHADOW_SIZE: usize = 1 << (47 - SHADOW_SCALE);
+const CHUNK_MAGIC: u64 = 0x4153_414e_4348_4b21;
+// freed chunks are kept poisoned until this many bytes of newer chunks are freed by the same
+// thread, so that a dangling pointer does not reach a reallocated chunk right away
+const HEAP_QUARANTINE_SIZE: usize = 1 << 22;
 
-const ALLOC_MAP_INSERT_ERR_STR: &str = "alloc map insertion error (hint: increase heapless map)";
+/// Allocation family of a heap chunk, used to detect mismatched deallocation
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AllocFn {
+    Malloc,
+    New,
+    NewArray,
+}
 
-thread_local! {
-    pub static MALLOC_REENTERED: Mutex<bool> = const { Mutex::new(false) }
+impl AllocFn {
+    pub fn alloc_str(&self) -> &'static str {
+        match self {
+            AllocFn::Malloc => "malloc",
+            AllocFn::New => "operator new",
+            AllocFn::NewArray => "operator new []",
+        }
+    }
+
+    pub fn dealloc_str(&self) -> &'static str {
+        match self {
+            AllocFn::Malloc => "free",
+            AllocFn::New => "operator delete",
+            AllocFn::NewArray => "operator delete []",
+        }
+    }
 }
 
-lazy_static::lazy_static! {
-    pub static ref SHADOW_MEMORY: Mutex<Vec<i8>> = Mutex::new(vec![0i8; SHADOW_SIZE]);
-    pub static ref ALLOC_MAP: Mutex<FnvIndexMap<usize, usize, ALLOC_MAP_SIZE>> = Mutex::new(FnvIndexMap::new());
+/// Bookkeeping of a live heap chunk. It is stored at the end of the left redzone, right
+/// before the usable region, so looking it up costs neither a lock nor an allocation.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct HeapChunk {
+    magic: u64,
+    pub size: usize,
+    /// Bytes in front of the usable region, including alignment padding
+    pub left_redzone: usize,
+    pub alloc_fn: AllocFn,
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn malloc(size: size_t) -> *mut c_void {
-    let cmalloc = get_cmalloc();
+const _: () = assert!(std::mem::size_of::<HeapChunk>() <= REDZONE_SIZE);
+
+impl HeapChunk {
+    fn new(size: usize, left_redzone: usize, alloc_fn: AllocFn) -> Self {
+        Self {
+            magic: CHUNK_MAGIC,
+            size,
+            left_redzone,
+            alloc_fn,
+        }
+    }
+
+    fn header_of(usable_ptr: *mut c_void) -> *mut HeapChunk {
+        (usable_ptr as usize - std::mem::size_of::<HeapChunk>()) as *mut HeapChunk
+    }
+}
 
-    MALLOC_REENTERED.with(|re_enter| {
-        let is_renter = re_enter.lock().unwrap();
-        if *is_renter {
-            cmalloc(size)
+const MALLOC_ALIGNMENT: usize = 16;
+
+/// Allocates `size` bytes surrounded by redzones and records the chunk
+unsafe fn alloc_chunk(size: usize, align: usize, alloc_fn: AllocFn) -> *mut c_void {
+    with_reentered(|| {
+        let left_redzone = REDZONE_SIZE.max(align);
+        let Some(total_size) = size.checked_add(left_redzone + REDZONE_SIZE) else {
+            return ptr::null_mut();
+        };
+        let raw_ptr = if align <= MALLOC_ALIGNMENT {
+            get_cmalloc()(total_size)
         } else {
-            let total_size = size + REDZONE_SIZE * 2;
-            let raw_ptr = cmalloc(total_size) as *mut u8;
-            __asan_init_redzone(raw_ptr, size,  ALLOC_HEAP)
+            let mut raw_ptr = ptr::null_mut();
+            if get_cposix_memalign()(&mut raw_ptr, align, total_size) != 0 {
+                return ptr::null_mut();
+            }
+            raw_ptr
+        } as *mut u8;
+        if raw_ptr.is_null() {
+            return ptr::null_mut();
+        }
+        // alignment padding in front of the left redzone is poisoned as left redzone as well
+        poison_shadow_range(
+            raw_ptr as usize,
+            left_redzone - REDZONE_SIZE,
+            HEAP_LEFT_REDZONE_MARKER,
+        );
+        let usable_ptr =
+            __asan_init_redzone(raw_ptr.add(left_redzone - REDZONE_SIZE), size, ALLOC_HEAP);
+        // redzones are zeroed by `__asan_init_redzone`, so the header is written afterwards
+        HeapChunk::header_of(usable_ptr).write(HeapChunk::new(size, left_redzone, alloc_fn));
+        register_chunk(usable_ptr as usize, size);
+        usable_ptr
+    })
+}
+
+/// Returns the chunk record of `ptr`, if `ptr` is a live chunk allocated by the runtime
+unsafe fn get_chunk(ptr: *mut c_void) -> Option<HeapChunk> {
+    // only a live chunk is preceded by a heap left redzone. Checking the shadow memory first
+    // avoids reading memory in front of pointers allocated by libc.
+    if (ptr as usize) < REDZONE_SIZE
+        || read_shadow_mem(convert_to_shadow_idx(ptr as usize - 1)) != HEAP_LEFT_REDZONE_MARKER
+    {
+        return None;
+    }
+    let chunk = HeapChunk::header_of(ptr).read();
+    (chunk.magic == CHUNK_MAGIC).then_some(chunk)
+}
+
+/// Returns the chunk record of `ptr` and invalidates it. Only one of the threads releasing
+/// the same chunk at once gets the record, so the chunk is never returned to libc twice.
+unsafe fn take_chunk(ptr: *mut c_void) -> Option<HeapChunk> {
+    let chunk = get_chunk(ptr)?;
+    let magic = &*(ptr::addr_of_mut!((*HeapChunk::header_of(ptr)).magic) as *const AtomicU64);
+    magic
+        .compare_exchange(CHUNK_MAGIC, 0, Ordering::AcqRel, Ordering::Acquire)
+        .ok()?;
+    Some(chunk)
+}
+
+/// Chunks freed by the current thread as (raw pointer, size), oldest first
+#[derive(Default)]
+struct HeapQuarantine {
+    chunks: VecDeque<(usize, usize)>,
+    size: usize,
+}
+
+impl Drop for HeapQuarantine {
+    fn drop(&mut self) {
+        with_reentered(|| {
+            for (raw_ptr, _) in self.chunks.drain(..) {
+                unsafe { get_cfree()(raw_ptr as *mut c_void) };
+            }
+        });
+    }
+}
+
+thread_local! {
+    static HEAP_QUARANTINE: RefCell<HeapQuarantine> = RefCell::new(HeapQuarantine::default());
+}
+
+/// Returns the memory of a freed chunk to libc once it leaves the quarantine
+unsafe fn quarantine_chunk(raw_ptr: *mut c_void, size: usize) {
+    let quarantined = HEAP_QUARANTINE.try_with(|quarantine| {
+        let mut quarantine = quarantine.borrow_mut();
+        quarantine.chunks.push_back((raw_ptr as usize, size));
+        quarantine.size += size;
+        while quarantine.size > HEAP_QUARANTINE_SIZE {
+            let (raw_ptr, size) = quarantine.chunks.pop_front().unwrap();
+            quarantine.size -= size;
+            get_cfree()(raw_ptr as *mut c_void);
         }
+    });
+    // the thread is exiting
+    if quarantined.is_err() {
+        get_cfree()(raw_ptr);
+    }
+}
+
+/// Poisons a freed chunk and quarantines its memory
+unsafe fn release_chunk(ptr: *mut c_void, chunk: HeapChunk) {
+    with_reentered(|| {
+        unregister_chunk(ptr as usize);
+        let raw_ptr = ptr.sub(chunk.left_redzone);
+        poison_shadow_range(
+            raw_ptr as usize,
+            chunk.left_redzone - REDZONE_SIZE,
+            FREED_MARKER,
+        );
+        poison_shadow_freed(ptr, chunk.size);
+        // use this code block for debugging purpose
+        // some of library may touch areas of `LEFT_ZONE` and `RIGHT_ZONE`
+        // as a result, free-ing thoes unexpected area may cause undefined behavior (e.g., segfault)
+        // {
+        // unsafe {
+        //     let mut start = ptr.sub(REDZONE_SIZE) as *const u8;
+        //     for offset in 0..(REDZONE_SIZE) {
+        //         let c = start.add(offset);
+        //         if *c != 0u8 {
+        //             dbg!("corrupted: {}", *c);
+        //         }
+        //     }
+        //     start = ptr.add(size) as *const u8;
+        //     for offset in 0..(REDZONE_SIZE) {
+        //         let c = start.add(offset);
+        //         if *c != 0u8 {
+        //             dbg!("corrupted: {}", *c);
+        //         }
+        //     }
+        // }
+        // }
+        quarantine_chunk(raw_ptr, chunk.left_redzone + chunk.size + REDZONE_SIZE);
     })
 }
 
+/// # Safety
+/// The returned chunk is released by `free` or `realloc`
 #[no_mangle]
-pub unsafe extern "C" fn strcpy(dest: *mut c_char, src: *const c_char) -> *mut c_char {
-    let cstrcpy = get_strcpy();
-    let src_len = CStr::from_ptr(src).to_bytes().len();
-    let temp_filename_ptr = c"libc::strcpy".as_ptr() as *const c_char;
-    for i in 0..=src_len {
-        __asan_mem_check(temp_filename_ptr, dest as usize + i, 1);
+pub unsafe extern "C" fn malloc(size: size_t) -> *mut c_void {
+    if is_reentered() {
+        return get_cmalloc()(size);
+    }
+    alloc_chunk(size, MALLOC_ALIGNMENT, AllocFn::Malloc)
+}
+
+/// # Safety
+/// The returned chunk is released by `free` or `realloc`
+#[no_mangle]
+pub unsafe extern "C" fn calloc(nmemb: size_t, size: size_t) -> *mut c_void {
+    if is_reentered() {
+        return __libc_calloc(nmemb, size);
+    }
+    let Some(total_size) = nmemb.checked_mul(size) else {
+        return ptr::null_mut();
+    };
+    let usable_ptr = alloc_chunk(total_size, MALLOC_ALIGNMENT, AllocFn::Malloc);
+    if !usable_ptr.is_null() {
+        get_memset()(usable_ptr, 0, total_size);
+    }
+    usable_ptr
+}
+
+/// # Safety
+/// `memptr` points to a writable pointer
+#[no_mangle]
+pub unsafe extern "C" fn posix_memalign(
+    memptr: *mut *mut c_void,
+    align: size_t,
+    size: size_t,
+) -> c_int {
+    if is_reentered() {
+        return get_cposix_memalign()(memptr, align, size);
+    }
+    if !align.is_power_of_two() || !align.is_multiple_of(std::mem::size_of::<*mut c_void>()) {
+        return libc::EINVAL;
+    }
+    let usable_ptr = alloc_chunk(size, align, AllocFn::Malloc);
+    if usable_ptr.is_null() {
+        return libc::ENOMEM;
+    }
+    *memptr = usable_ptr;
+    0
+}
+
+/// # Safety
+/// The returned chunk is released by `free` or `realloc`
+#[no_mangle]
+pub unsafe extern "C" fn aligned_alloc(align: size_t, size: size_t) -> *mut c_void {
+    if !align.is_power_of_two() {
+        *libc::__errno_location() = libc::EINVAL;
+        return ptr::null_mut();
+    }
+    let mut usable_ptr = ptr::null_mut();
+    let align = align.max(std::mem::size_of::<*mut c_void>());
+    match posix_memalign(&mut usable_ptr, align, size) {
+        0 => usable_ptr,
+        err => {
+            *libc::__errno_location() = err;
+            ptr::null_mut()
+        }
     }
-    cstrcpy(dest, src)
 }
 
+/// # Safety
+/// `ptr` is either null or a live chunk returned by the `malloc` family
 #[no_mangle]
 pub unsafe extern "C" fn realloc(ptr: *mut c_void, size: size_t) -> *mut c_void {
     if ptr.is_null() {
         return malloc(size);
     }
-    let mut alloc_map = ALLOC_MAP.lock().unwrap();
-    if let Some(org_size) = alloc_map.remove(&(ptr as usize)) {
-        // drop locked variables because the following code will require `free` call
-        drop(alloc_map);
-        poison_shadow_freed(ptr, size);
-        let usable_ptr = malloc(size);
-        ptr::copy(ptr, usable_ptr, org_size);
-        free(ptr.sub(REDZONE_SIZE));
-        usable_ptr
-    } else {
-        let crealloc = get_crealloc();
-        crealloc(ptr, size)
+    let Some(chunk) = get_chunk(ptr) else {
+        return get_crealloc()(ptr, size);
+    };
+    if chunk.alloc_fn != AllocFn::Malloc {
+        report_alloc_dealloc_mismatch(ptr as usize, chunk.alloc_fn.alloc_str(), "realloc");
+    }
+    let usable_ptr = malloc(size);
+    // on failure, the original chunk is left untouched
+    if usable_ptr.is_null() {
+        return ptr::null_mut();
     }
+    ptr::copy_nonoverlapping(
+        ptr as *const u8,
+        usable_ptr as *mut u8,
+        chunk.size.min(size),
+    );
+    if let Some(chunk) = take_chunk(ptr) {
+        release_chunk(ptr, chunk);
+    }
+    usable_ptr
 }
 
+/// # Safety
+/// `ptr` is either null or a live chunk returned by the `malloc` family
 #[no_mangle]
 pub unsafe extern "C" fn free(ptr: *mut c_void) {
     if ptr.is_null() {
         return;
     }
-    let cfree = get_cfree();
-    let mut alloc_map = ALLOC_MAP.lock().unwrap();
-    if let Some(size) = alloc_map.remove(&(ptr as usize)) {
-        poison_shadow_freed(ptr, size);
-        // use this code block for debugging purpose
-        // some of library may touch areas of `LEFT_ZONE` and `RIGHT_ZONE`
-        // as a result, free-ing thoes unexpected area may cause undefined behavior (e.g., segfault)
-        // {
-            // unsafe {
-            //     let mut start = ptr.sub(REDZONE_SIZE) as *const u8;
-            //     for offset in 0..(REDZONE_SIZE) {
-            //         let c = start.add(offset);
-            //         if *c != 0u8 {
-            //             dbg!("corrupted: {}", *c);
-            //         }
-            //     }
-            //     start = ptr.add(size) as *const u8;
-            //     for offset in 0..(REDZONE_SIZE) {
-            //         let c = start.add(offset);
-            //         if *c != 0u8 {
-            //             dbg!("corrupted: {}", *c);
-            //         }
-            //     }
-            // }
-        // }
-        cfree(ptr.sub(REDZONE_SIZE));
-    } else {
-        cfree(ptr);
+    match take_chunk(ptr) {
+        Some(chunk) => {
+            if chunk.alloc_fn != AllocFn::Malloc {
+                report_alloc_dealloc_mismatch(ptr as usize, chunk.alloc_fn.alloc_str(), "free");
+            }
+            release_chunk(ptr, chunk);
+        }
+        None => get_cfree()(ptr),
     }
 }
 
+/// operator new(unsigned long)
+///
+/// # Safety
+/// The returned chunk is released by `operator delete`
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn _Znwm(size: size_t) -> *mut c_void {
+    if is_reentered() {
+        return get_cmalloc()(size);
+    }
+    alloc_chunk(size, MALLOC_ALIGNMENT, AllocFn::New)
+}
+
+/// operator new[](unsigned long)
+///
+/// # Safety
+/// The returned chunk is released by `operator delete []`
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn _Znam(size: size_t) -> *mut c_void {
+    if is_reentered() {
+        return get_cmalloc()(size);
+    }
+    alloc_chunk(size, MALLOC_ALIGNMENT, AllocFn::NewArray)
+}
+
+/// operator delete(void*)
+///
+/// # Safety
+/// `ptr` is either null or a live chunk returned by `operator new`
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn _ZdlPv(ptr: *mut c_void) {
+    if ptr.is_null() {
+        return;
+    }
+    match take_chunk(ptr) {
+        Some(chunk) => {
+            if chunk.alloc_fn != AllocFn::New {
+                report_alloc_dealloc_mismatch(
+                    ptr as usize,
+                    chunk.alloc_fn.alloc_str(),
+                    AllocFn::New.dealloc_str(),
+                );
+            }
+            release_chunk(ptr, chunk);
+        }
+        None => get_cfree()(ptr),
+    }
+}
+
+/// operator delete[](void*)
+///
+/// # Safety
+/// `ptr` is either null or a live chunk returned by `operator new []`
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn _ZdaPv(ptr: *mut c_void) {
+    if ptr.is_null() {
+        return;
+    }
+    match take_chunk(ptr) {
+        Some(chunk) => {
+            if chunk.alloc_fn != AllocFn::NewArray {
+                report_alloc_dealloc_mismatch(
+                    ptr as usize,
+                    chunk.alloc_fn.alloc_str(),
+                    AllocFn::NewArray.dealloc_str(),
+                );
+            }
+            release_chunk(ptr, chunk);
+        }
+        None => get_cfree()(ptr),
+    }
+}
+
+/// operator delete(void*, unsigned long)
+///
+/// # Safety
+/// `ptr` is either null or a live chunk returned by `operator new`
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn _ZdlPvm(ptr: *mut c_void, _size: size_t) {
+    _ZdlPv(ptr)
+}
+
+/// operator delete[](void*, unsigned long)
+///
+/// # Safety
+/// `ptr` is either null or a live chunk returned by `operator new []`
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn _ZdaPvm(ptr: *mut c_void, _size: size_t) {
+    _ZdaPv(ptr)
+}
+
+/// # Safety
+/// `s` is a null-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn strdup(s: *const c_char) -> *mut c_char {
+    let len = get_strlen()(s);
+    if !is_reentered() {
+        __asan_mem_check(c"libc::strdup".as_ptr(), s as usize, len + 1);
+    }
+    let dup = malloc(len + 1) as *mut c_char;
+    if !dup.is_null() {
+        ptr::copy_nonoverlapping(s, dup, len + 1);
+    }
+    dup
+}
+
+/// # Safety
+/// `s` is a null-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn strlen(s: *const c_char) -> size_t {
+    let len = get_strlen()(s);
+    if !is_reentered() {
+        __asan_mem_check(c"libc::strlen".as_ptr(), s as usize, len + 1);
+    }
+    len
+}
+
+/// # Safety
+/// `src` is a null-terminated string and `dest` is a writable buffer
+#[no_mangle]
+pub unsafe extern "C" fn strcpy(dest: *mut c_char, src: *const c_char) -> *mut c_char {
+    if !is_reentered() {
+        let len = get_strlen()(src);
+        __asan_mem_check(c"libc::strcpy".as_ptr(), src as usize, len + 1);
+        __asan_mem_check(c"libc::strcpy".as_ptr(), dest as usize, len + 1);
+    }
+    get_strcpy()(dest, src)
+}
+
+/// # Safety
+/// `src` is a null-terminated string or holds `n` bytes, and `dest` is a writable buffer
+#[no_mangle]
+pub unsafe extern "C" fn strncpy(dest: *mut c_char, src: *const c_char, n: size_t) -> *mut c_char {
+    // `src` is read up to its terminator or `n` bytes, `dest` is always written `n` bytes
+    if !is_reentered() {
+        let src_len = (0..n).find(|&i| *src.add(i) == 0).map_or(n, |len| len + 1);
+        __asan_mem_check(c"libc::strncpy".as_ptr(), src as usize, src_len);
+        __asan_mem_check(c"libc::strncpy".as_ptr(), dest as usize, n);
+    }
+    get_strncpy()(dest, src, n)
+}
+
+/// # Safety
+/// `dest` and `src` are null-terminated strings, and `dest` is a writable buffer
+#[no_mangle]
+pub unsafe extern "C" fn strcat(dest: *mut c_char, src: *const c_char) -> *mut c_char {
+    if !is_reentered() {
+        let dest_len = get_strlen()(dest);
+        let src_len = get_strlen()(src);
+        __asan_mem_check(c"libc::strcat".as_ptr(), src as usize, src_len + 1);
+        __asan_mem_check(
+            c"libc::strcat".as_ptr(),
+            dest as usize,
+            dest_len + src_len + 1,
+        );
+    }
+    get_strcat()(dest, src)
+}
+
+/// Checks both ranges of a call from code not instrumented (e.g., a library or a call through
+/// a function pointer). Instrumented code checks the `llvm.mem*` intrinsics in place.
+///
+/// # Safety
+/// `src` holds `n` readable bytes and `dest` holds `n` writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn memcpy(dest: *mut c_void, src: *const c_void, n: size_t) -> *mut c_void {
+    if !is_reentered() {
+        __asan_mem_check(c"libc::memcpy".as_ptr(), src as usize, n);
+        __asan_mem_check(c"libc::memcpy".as_ptr(), dest as usize, n);
+    }
+    get_memcpy()(dest, src, n)
+}
+
+/// # Safety
+/// `src` holds `n` readable bytes and `dest` holds `n` writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn memmove(dest: *mut c_void, src: *const c_void, n: size_t) -> *mut c_void {
+    if !is_reentered() {
+        __asan_mem_check(c"libc::memmove".as_ptr(), src as usize, n);
+        __asan_mem_check(c"libc::memmove".as_ptr(), dest as usize, n);
+    }
+    get_memmove()(dest, src, n)
+}
+
+/// # Safety
+/// `dest` holds `n` writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn memset(dest: *mut c_void, c: c_int, n: size_t) -> *mut c_void {
+    if !is_reentered() {
+        __asan_mem_check(c"libc::memset".as_ptr(), dest as usize, n);
+    }
+    get_memset()(dest, c, n)
+}
+
 /// Poisons left and right redzones and make clean for usable region
 fn poison_shadow_allocated(raw_ptr: usize, usable_size: usize, alloc_kind: u8) {
     // |----------------------|----------------------|----------------------|
@@ -136,16 +561,9 @@ fn poison_shadow_allocated(raw_ptr: usize, usable_size: usize, alloc_kind: u8) {
     let left_end = usable_ptr;
     let shadow_left_start = convert_to_shadow_idx(left_start);
     let shadow_left_end = convert_to_shadow_idx(left_end);
-    let mut shadow_mem = SHADOW_MEMORY.lock().unwrap();
-
-    set_bounary_poison_byte(
-        &mut shadow_mem,
-        left_start,
-        shadow_left_start,
-        left_rz_marker,
-    );
+    set_bounary_poison_byte(left_start, shadow_left_start, left_rz_marker);
     for i in (shadow_left_start + 1)..shadow_left_end {
-        write_shadow_mem(&mut shadow_mem, i, left_rz_marker);
+        write_shadow_mem(i, left_rz_marker);
     }
 
     // 2. Unpoison usable region
@@ -155,14 +573,9 @@ fn poison_shadow_allocated(raw_ptr: usize, usable_size: usize, alloc_kind: u8) {
     let shadow_usable_end = convert_to_shadow_idx(usable_end);
 
     for i in shadow_usable_start..shadow_usable_end {
-        write_shadow_mem(&mut shadow_mem, i, CLEAN_BYTE_MARKER);
+        write_shadow_mem(i, CLEAN_BYTE_MARKER);
     }
-    set_bounary_poison_byte(
-        &mut shadow_mem,
-        usable_end,
-        shadow_usable_end,
-        right_rz_marker,
-    );
+    set_bounary_poison_byte(usable_end, shadow_usable_end, right_rz_marker);
 
     // 3. Poison right redzone
     let right_start = usable_end;
@@ -171,27 +584,17 @@ fn poison_shadow_allocated(raw_ptr: usize, usable_size: usize, alloc_kind: u8) {
     let shadow_right_end = convert_to_shadow_idx(right_end);
 
     for i in (shadow_right_start + 1)..shadow_right_end {
-        write_shadow_mem(&mut shadow_mem, i, right_rz_marker);
+        write_shadow_mem(i, right_rz_marker);
     }
-    set_bounary_poison_byte(
-        &mut shadow_mem,
-        right_end,
-        shadow_right_end,
-        right_rz_marker,
-    );
+    set_bounary_poison_byte(right_end, shadow_right_end, right_rz_marker);
 }
 
-fn set_bounary_poison_byte(
-    shadow_mem: &mut [i8],
-    start: usize,
-    shadow_start: usize,
-    rz_marker: i8,
-) {
+fn set_bounary_poison_byte(start: usize, shadow_start: usize, rz_marker: i8) {
     let remaining = start & 0x07;
     if remaining != 0 {
-        write_shadow_mem(shadow_mem, shadow_start, remaining as i8);
+        write_shadow_mem(shadow_start, remaining as i8);
     } else {
-        write_shadow_mem(shadow_mem, shadow_start, rz_marker);
+        write_shadow_mem(shadow_start, rz_marker);
     }
 }
 
@@ -201,14 +604,19 @@ fn poison_shadow_freed(usable_ptr: *mut c_void, size: usize) {
     let shadow_start = convert_to_shadow_idx(start as usize);
     let shadow_end =
         unsafe { convert_to_shadow_idx(start.add(REDZONE_SIZE + size + REDZONE_SIZE) as usize) };
-    let mut shadow = SHADOW_MEMORY.lock().unwrap();
-    set_bounary_poison_byte(&mut shadow, start as usize, shadow_start, FREED_MARKER);
+    set_bounary_poison_byte(start as usize, shadow_start, FREED_MARKER);
     for i in (shadow_start + 1)..shadow_end {
-        write_shadow_mem(&mut shadow, i, FREED_MARKER);
+        write_shadow_mem(i, FREED_MARKER);
     }
-    set_bounary_poison_byte(&mut shadow, start as usize, shadow_end, FREED_MARKER);
+    set_bounary_poison_byte(start as usize, shadow_end, FREED_MARKER);
 }
 
+/// Poisons the redzones around `usable_size` bytes starting after the left redzone of
+/// `raw_ptr`, and returns the usable region
+///
+/// # Safety
+/// `raw_ptr` is either null or points to `REDZONE_SIZE + usable_size + REDZONE_SIZE`
+/// writable bytes
 #[no_mangle]
 pub unsafe extern "C" fn __asan_init_redzone(
     raw_ptr: *mut u8,
@@ -227,26 +635,77 @@ pub unsafe extern "C" fn __asan_init_redzone(
     // when `free` system call is invoked.
     // exact reasoning has not been found yet
     // also, initialization with zero value only works emperically
-    libc::memset(raw_ptr as *mut c_void, 0, REDZONE_SIZE);
-    libc::memset(raw_ptr.add(REDZONE_SIZE + usable_size) as *mut c_void, 0, REDZONE_SIZE);
+    // redzones are not poisoned yet, so the interceptor of `memset` is bypassed
+    get_memset()(raw_ptr as *mut c_void, 0, REDZONE_SIZE);
+    get_memset()(
+        raw_ptr.add(REDZONE_SIZE + usable_size) as *mut c_void,
+        0,
+        REDZONE_SIZE,
+    );
 
     // initialize shadow memory
     poison_shadow_allocated(raw_ptr as usize, usable_size, alloc_kind);
-    // record allocated size
-    ALLOC_MAP
-        .lock()
-        .unwrap()
-        .insert(usable_ptr as usize, usable_size)
-        .expect(ALLOC_MAP_INSERT_ERR_STR);
 
     // return usable region pointer
     usable_ptr as *mut c_void
 }
 
+/// Poisons `[start, start + len)` with `marker`. Both ends must be granule-aligned.
+pub fn poison_shadow_range(start: usize, len: usize, marker: i8) {
+    for i in convert_to_shadow_idx(start)..convert_to_shadow_idx(start + len) {
+        write_shadow_mem(i, marker);
+    }
+}
+
 pub fn convert_to_shadow_idx(addr: usize) -> usize {
     addr >> SHADOW_SCALE
 }
 
-fn write_shadow_mem(shadow_mem: &mut [i8], idx: usize, val: i8) {
-    shadow_mem[idx % SHADOW_SIZE] = val;
+static SHADOW_INIT: Once = Once::new();
+
+/// Reserves the shadow memory. Pages are populated (zero, i.e., clean) on the first touch.
+fn init_shadow_memory() {
+    SHADOW_INIT.call_once(|| {
+        let shadow = unsafe {
+            libc::mmap(
+                SHADOW_OFFSET as *mut c_void,
+                SHADOW_SIZE,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE
+                    | libc::MAP_ANONYMOUS
+                    | libc::MAP_NORESERVE
+                    | libc::MAP_FIXED_NOREPLACE,
+                -1,
+                0,
+            )
+        };
+        if shadow != SHADOW_OFFSET as *mut c_void {
+            // `eprintln` is avoided since it may allocate
+            let msg = c"[ASAN] failed to reserve shadow memory\n";
+            unsafe {
+                libc::write(2, msg.as_ptr() as *const c_void, msg.to_bytes().len());
+                libc::abort();
+            }
+        }
+    });
+}
+
+// map shadow memory before any instrumented code runs
+#[used]
+#[link_section = ".init_array"]
+static SHADOW_INIT_CTOR: extern "C" fn() = {
+    extern "C" fn ctor() {
+        init_shadow_memory();
+    }
+    ctor
+};
+
+pub fn read_shadow_mem(idx: usize) -> i8 {
+    init_shadow_memory();
+    unsafe { *((idx + SHADOW_OFFSET) as *const i8) }
+}
+
+pub fn write_shadow_mem(idx: usize, val: i8) {
+    init_shadow_memory();
+    unsafe { *((idx + SHADOW_OFFSET) as *mut i8) = val };
 }
@@ -1,99 +1,106 @@
-use libc::{c_char, c_void, dlsym, size_t, RTLD_NEXT};
-use std::collections::HashMap;
-use std::sync::Mutex;
+use libc::{c_char, c_int, c_void, dlsym, pthread_attr_t, pthread_t, size_t, RTLD_NEXT};
+use std::ffi::CStr;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
 
 type MallocFn = unsafe extern "C" fn(size_t) -> *mut c_void;
 type ReallocFn = unsafe extern "C" fn(ptr: *mut c_void, size: size_t) -> *mut c_void;
 type FreeFn = unsafe extern "C" fn(ptr: *mut c_void);
+type PosixMemalignFn =
+    unsafe extern "C" fn(memptr: *mut *mut c_void, align: size_t, size: size_t) -> c_int;
 type StrcpyFn = unsafe extern "C" fn(dest: *mut c_char, src: *const c_char) -> *mut c_char;
+type StrncpyFn =
+    unsafe extern "C" fn(dest: *mut c_char, src: *const c_char, n: size_t) -> *mut c_char;
+type StrcatFn = unsafe extern "C" fn(dest: *mut c_char, src: *const c_char) -> *mut c_char;
+type StrlenFn = unsafe extern "C" fn(s: *const c_char) -> size_t;
+type MemcpyFn =
+    unsafe extern "C" fn(dest: *mut c_void, src: *const c_void, n: size_t) -> *mut c_void;
+type MemsetFn = unsafe extern "C" fn(dest: *mut c_void, c: c_int, n: size_t) -> *mut c_void;
+pub type ThreadStartFn = extern "C" fn(arg: *mut c_void) -> *mut c_void;
+type PthreadCreateFn = unsafe extern "C" fn(
+    thread: *mut pthread_t,
+    attr: *const pthread_attr_t,
+    start_routine: ThreadStartFn,
+    arg: *mut c_void,
+) -> c_int;
 
-lazy_static::lazy_static! {
-    static ref ALLOC_MAP: Mutex<HashMap<usize, usize>> = Mutex::new(HashMap::new());
-    static ref MALLOC: Mutex<Option<MallocFn>> = Mutex::new(None);
-    static ref REALLOC: Mutex<Option<ReallocFn>> = Mutex::new(None);
-    static ref FREE: Mutex<Option<FreeFn>> = Mutex::new(None);
-    static ref STRCPY: Mutex<Option<StrcpyFn>> = Mutex::new(None);
+extern "C" {
+    // glibc exports its allocator under these aliases as well. `calloc` is served from here
+    // while the runtime itself is allocating, because `dlsym` may call `calloc` before any
+    // real function has been resolved.
+    pub fn __libc_calloc(nmemb: size_t, size: size_t) -> *mut c_void;
 }
 
-pub fn get_malloc_cstr() -> [i8; 7] {
-    let fn_str = b"malloc";
-    let mut buf = [0 as c_char; 7];
-    for (i, &b) in fn_str.iter().enumerate() {
-        buf[i] = b as c_char;
+// resolved functions are cached without a lock, as they are looked up on every allocation
+static MALLOC: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static REALLOC: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static FREE: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static POSIX_MEMALIGN: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static STRCPY: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static STRNCPY: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static STRCAT: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static STRLEN: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static MEMCPY: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static MEMMOVE: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static MEMSET: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static PTHREAD_CREATE: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+/// Resolves the next definition of `name` (i.e., the libc one) and caches it. Threads racing
+/// on the first call resolve the same address, so either store wins.
+fn get_real_fn<F: Copy>(real_fn: &AtomicPtr<c_void>, name: &CStr) -> F {
+    let mut sym = real_fn.load(Ordering::Acquire);
+    if sym.is_null() {
+        sym = unsafe { dlsym(RTLD_NEXT, name.as_ptr()) };
+        assert!(!sym.is_null(), "failed to resolve {:?}", name);
+        real_fn.store(sym, Ordering::Release);
     }
-    buf
+    unsafe { std::mem::transmute_copy::<*mut c_void, F>(&sym) }
 }
 
-pub fn get_realloc_str() -> [i8; 8] {
-    let fn_str = b"realloc";
-    let mut buf = [0 as c_char; 8];
-    for (i, &b) in fn_str.iter().enumerate() {
-        buf[i] = b as c_char;
-    }
-    buf
+pub fn get_cmalloc() -> MallocFn {
+    get_real_fn(&MALLOC, c"malloc")
 }
 
-pub fn get_free_str() -> [i8; 5] {
-    let fn_str = b"free";
-    let mut buf = [0 as c_char; 5];
-    for (i, &b) in fn_str.iter().enumerate() {
-        buf[i] = b as c_char;
-    }
-    buf
+pub fn get_crealloc() -> ReallocFn {
+    get_real_fn(&REALLOC, c"realloc")
 }
 
-pub fn get_strcpy_str() -> [i8; 7] {
-    let fn_str = b"strcpy";
-    let mut buf = [0 as c_char; 7];
-    for (i, &b) in fn_str.iter().enumerate() {
-        buf[i] = b as c_char;
-    }
-    buf
+pub fn get_cfree() -> FreeFn {
+    get_real_fn(&FREE, c"free")
 }
 
-pub fn get_cmalloc() -> MallocFn {
-    let mut cmalloc = MALLOC.lock().unwrap();
-    if cmalloc.is_none() {
-        let buf = get_malloc_cstr();
-        let malloc_sym = unsafe { dlsym(RTLD_NEXT, buf.as_ptr()) };
-        let malloc_fn =
-            unsafe { std::mem::transmute::<*const (), MallocFn>(malloc_sym as *const ()) };
-        *cmalloc = Some(malloc_fn);
-    }
-    cmalloc.unwrap()
+pub fn get_cposix_memalign() -> PosixMemalignFn {
+    get_real_fn(&POSIX_MEMALIGN, c"posix_memalign")
 }
 
-pub fn get_crealloc() -> ReallocFn {
-    let mut crealloc = REALLOC.lock().unwrap();
-    if crealloc.is_none() {
-        let buf = get_realloc_str();
-        let realloc_sym = unsafe { dlsym(RTLD_NEXT, buf.as_ptr()) };
-        let realloc_fn =
-            unsafe { std::mem::transmute::<*const (), ReallocFn>(realloc_sym as *const ()) };
-        *crealloc = Some(realloc_fn);
-    }
-    crealloc.unwrap()
+pub fn get_strcpy() -> StrcpyFn {
+    get_real_fn(&STRCPY, c"strcpy")
 }
 
-pub fn get_cfree() -> FreeFn {
-    let mut cfree = FREE.lock().unwrap();
-    if cfree.is_none() {
-        let buf = get_free_str();
-        let free_sym = unsafe { dlsym(RTLD_NEXT, buf.as_ptr()) };
-        let free_fn = unsafe { std::mem::transmute::<*const (), FreeFn>(free_sym as *const ()) };
-        *cfree = Some(free_fn);
-    }
-    cfree.unwrap()
+pub fn get_strncpy() -> StrncpyFn {
+    get_real_fn(&STRNCPY, c"strncpy")
 }
 
-pub fn get_strcpy() -> StrcpyFn {
-    let mut cstrcpy = STRCPY.lock().unwrap();
-    if cstrcpy.is_none() {
-        let buf = get_strcpy_str();
-        let strcpy_sym = unsafe { dlsym(RTLD_NEXT, buf.as_ptr()) };
-        let strcpy_fn =
-            unsafe { std::mem::transmute::<*const (), StrcpyFn>(strcpy_sym as *const ()) };
-        *cstrcpy = Some(strcpy_fn);
-    }
-    cstrcpy.unwrap()
+pub fn get_strcat() -> StrcatFn {
+    get_real_fn(&STRCAT, c"strcat")
+}
+
+pub fn get_strlen() -> StrlenFn {
+    get_real_fn(&STRLEN, c"strlen")
+}
+
+pub fn get_memcpy() -> MemcpyFn {
+    get_real_fn(&MEMCPY, c"memcpy")
+}
+
+pub fn get_memmove() -> MemcpyFn {
+    get_real_fn(&MEMMOVE, c"memmove")
+}
+
+pub fn get_memset() -> MemsetFn {
+    get_real_fn(&MEMSET, c"memset")
+}
+
+pub fn get_pthread_create() -> PthreadCreateFn {
+    get_real_fn(&PTHREAD_CREATE, c"pthread_create")
 }
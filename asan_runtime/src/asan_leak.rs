@@ -0,0 +1,406 @@
+use crate::{
+    asan_hook::with_reentered,
+    asan_intrinsic::{get_pthread_create, ThreadStartFn},
+    asan_report::{get_object_base, get_stack_trace},
+    asan_runtime::{__asan_mem_check, cstr_to_string, get_option, is_test_enabled, EXIT_CODE},
+};
+use libc::{c_int, c_void, dl_phdr_info, pthread_attr_t, pthread_t, size_t, Dl_info};
+use std::collections::HashMap;
+use std::mem::{size_of, MaybeUninit};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// live chunks are spread over shards so that allocating threads rarely contend on a lock
+const LIVE_CHUNK_SHARDS: usize = 64;
+const MAX_ALLOC_FRAMES: usize = 16;
+
+/// Return addresses of the frames allocating a chunk, innermost first
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct AllocStack {
+    frames: [usize; MAX_ALLOC_FRAMES],
+    depth: usize,
+}
+
+impl AllocStack {
+    fn capture() -> Self {
+        let mut frames = [0; MAX_ALLOC_FRAMES];
+        let runtime_base = get_object_base(__asan_mem_check as *const () as usize);
+        // allocation functions of the runtime are not part of the allocation stack
+        let user_frames = get_stack_trace()
+            .into_iter()
+            .skip_while(|&pc| get_object_base(pc) == runtime_base);
+        let mut depth = 0;
+        for (frame, pc) in frames.iter_mut().zip(user_frames) {
+            *frame = pc;
+            depth += 1;
+        }
+        AllocStack { frames, depth }
+    }
+
+    fn frames(&self) -> &[usize] {
+        &self.frames[..self.depth]
+    }
+
+    /// Whether the chunk is allocated by the dynamic loader (e.g., TLS blocks of threads),
+    /// which keeps the pointers in memory not scanned for leaks
+    fn is_from_loader(&self) -> bool {
+        let Some(&pc) = self.frames().first() else {
+            return false;
+        };
+        let mut info = MaybeUninit::<Dl_info>::zeroed();
+        let object = unsafe {
+            if libc::dladdr(pc as *const c_void, info.as_mut_ptr()) == 0 {
+                return false;
+            }
+            cstr_to_string(info.assume_init().dli_fname)
+        };
+        object
+            .rsplit('/')
+            .next()
+            .is_some_and(|name| name.starts_with("ld-"))
+    }
+}
+
+struct LiveChunk {
+    size: usize,
+    stack: AllocStack,
+}
+
+lazy_static::lazy_static! {
+    static ref LEAK_DETECTION_ENABLED: bool =
+        get_option("detect_leaks").is_some_and(|val| val == "1");
+    static ref LIVE_CHUNKS: Vec<Mutex<HashMap<usize, LiveChunk>>> =
+        (0..LIVE_CHUNK_SHARDS).map(|_| Mutex::new(HashMap::new())).collect();
+    /// Stacks of the running threads created by `pthread_create`
+    static ref THREAD_STACKS: Mutex<HashMap<pthread_t, (usize, usize)>> =
+        Mutex::new(HashMap::new());
+}
+
+static MAIN_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+fn is_leak_detection_enabled() -> bool {
+    with_reentered(|| *LEAK_DETECTION_ENABLED)
+}
+
+fn get_shard(usable_ptr: usize) -> &'static Mutex<HashMap<usize, LiveChunk>> {
+    // chunks are aligned at least to 16 bytes
+    &LIVE_CHUNKS[(usable_ptr >> 4) % LIVE_CHUNK_SHARDS]
+}
+
+/// Records a new heap chunk along with its allocation stack
+pub fn register_chunk(usable_ptr: usize, size: usize) {
+    if !is_leak_detection_enabled() {
+        return;
+    }
+    with_reentered(|| {
+        let stack = AllocStack::capture();
+        get_shard(usable_ptr)
+            .lock()
+            .unwrap()
+            .insert(usable_ptr, LiveChunk { size, stack });
+    });
+}
+
+pub fn unregister_chunk(usable_ptr: usize) {
+    if !is_leak_detection_enabled() {
+        return;
+    }
+    with_reentered(|| {
+        get_shard(usable_ptr).lock().unwrap().remove(&usable_ptr);
+    });
+}
+
+/// Returns the stack of the calling thread
+unsafe fn get_stack_range() -> Option<(usize, usize)> {
+    let mut attr = MaybeUninit::<pthread_attr_t>::zeroed();
+    if libc::pthread_getattr_np(libc::pthread_self(), attr.as_mut_ptr()) != 0 {
+        return None;
+    }
+    let mut stack_addr = std::ptr::null_mut();
+    let mut stack_size = 0;
+    libc::pthread_attr_getstack(attr.as_ptr(), &mut stack_addr, &mut stack_size);
+    libc::pthread_attr_destroy(attr.as_mut_ptr());
+    Some((stack_addr as usize, stack_addr as usize + stack_size))
+}
+
+/// The mapped part of the main thread stack, which grows on demand up to its limit
+fn get_main_stack_range() -> Option<(usize, usize)> {
+    let maps = std::fs::read_to_string("/proc/self/maps").ok()?;
+    let line = maps.lines().find(|line| line.ends_with("[stack]"))?;
+    let (start, end) = line.split_whitespace().next()?.split_once('-')?;
+    Some((
+        usize::from_str_radix(start, 16).ok()?,
+        usize::from_str_radix(end, 16).ok()?,
+    ))
+}
+
+/// Unregisters the stack of a thread when it exits, including through `pthread_exit`
+struct ThreadStackGuard;
+
+impl Drop for ThreadStackGuard {
+    fn drop(&mut self) {
+        with_reentered(|| {
+            THREAD_STACKS
+                .lock()
+                .unwrap()
+                .remove(&unsafe { libc::pthread_self() });
+        });
+    }
+}
+
+thread_local! {
+    static THREAD_STACK_GUARD: ThreadStackGuard = const { ThreadStackGuard };
+}
+
+struct ThreadStart {
+    start_routine: ThreadStartFn,
+    arg: *mut c_void,
+}
+
+extern "C" fn start_thread(data: *mut c_void) -> *mut c_void {
+    let ThreadStart { start_routine, arg } =
+        with_reentered(|| *unsafe { Box::from_raw(data as *mut ThreadStart) });
+    with_reentered(|| unsafe {
+        if let Some(stack_range) = get_stack_range() {
+            THREAD_STACKS
+                .lock()
+                .unwrap()
+                .insert(libc::pthread_self(), stack_range);
+            THREAD_STACK_GUARD.with(|_| {});
+        }
+    });
+    start_routine(arg)
+}
+
+/// Registers the stack of a new thread, so that pointers held by other threads than the one
+/// checking for leaks are found as well
+///
+/// # Safety
+/// Same as `pthread_create`
+#[no_mangle]
+pub unsafe extern "C" fn pthread_create(
+    thread: *mut pthread_t,
+    attr: *const pthread_attr_t,
+    start_routine: ThreadStartFn,
+    arg: *mut c_void,
+) -> c_int {
+    if !is_leak_detection_enabled() {
+        return get_pthread_create()(thread, attr, start_routine, arg);
+    }
+    let data = with_reentered(|| Box::into_raw(Box::new(ThreadStart { start_routine, arg })));
+    let ret = get_pthread_create()(thread, attr, start_thread, data as *mut c_void);
+    if ret != 0 {
+        with_reentered(|| drop(Box::from_raw(data)));
+    }
+    ret
+}
+
+/// Live chunks sorted by their address, which are marked while scanning for pointers
+struct LeakScanner {
+    chunks: Vec<(usize, usize, AllocStack)>,
+    reachable: Vec<bool>,
+    worklist: Vec<usize>,
+}
+
+impl LeakScanner {
+    fn new() -> Self {
+        let mut chunks = Vec::new();
+        for shard in LIVE_CHUNKS.iter() {
+            let shard = shard.lock().unwrap();
+            chunks.extend(
+                shard
+                    .iter()
+                    .map(|(&usable_ptr, chunk)| (usable_ptr, chunk.size, chunk.stack)),
+            );
+        }
+        chunks.sort_unstable_by_key(|(usable_ptr, _, _)| *usable_ptr);
+        let reachable = vec![false; chunks.len()];
+        LeakScanner {
+            chunks,
+            reachable,
+            worklist: Vec::new(),
+        }
+    }
+
+    /// Returns the chunk containing `addr`. Interior pointers keep a chunk alive as well.
+    fn find_chunk(&self, addr: usize) -> Option<usize> {
+        let idx = self
+            .chunks
+            .partition_point(|(usable_ptr, _, _)| *usable_ptr <= addr)
+            .checked_sub(1)?;
+        let (usable_ptr, size, _) = self.chunks[idx];
+        (addr < usable_ptr + size.max(1)).then_some(idx)
+    }
+
+    /// Conservatively treats every aligned word in `[start, end)` as a pointer
+    unsafe fn scan_range(&mut self, start: usize, end: usize) {
+        let mut addr = start.next_multiple_of(size_of::<usize>());
+        while addr + size_of::<usize>() <= end {
+            let val = (addr as *const usize).read_volatile();
+            if let Some(idx) = self.find_chunk(val) {
+                if !self.reachable[idx] {
+                    self.reachable[idx] = true;
+                    self.worklist.push(idx);
+                }
+            }
+            addr += size_of::<usize>();
+        }
+    }
+
+    /// Writable segments of every loaded object, i.e., `.data` and `.bss`
+    unsafe fn scan_globals(&mut self) {
+        unsafe extern "C" fn callback(
+            info: *mut dl_phdr_info,
+            _size: size_t,
+            data: *mut c_void,
+        ) -> c_int {
+            let scanner = &mut *(data as *mut LeakScanner);
+            let info = &*info;
+            for idx in 0..info.dlpi_phnum as usize {
+                let phdr = &*info.dlpi_phdr.add(idx);
+                if phdr.p_type == libc::PT_LOAD && phdr.p_flags & libc::PF_W != 0 {
+                    let start = info.dlpi_addr as usize + phdr.p_vaddr as usize;
+                    scanner.scan_range(start, start + phdr.p_memsz as usize);
+                }
+            }
+            0
+        }
+        libc::dl_iterate_phdr(Some(callback), self as *mut Self as *mut c_void);
+    }
+
+    /// The stack of the calling thread from the current frame up to its top, and the whole
+    /// stacks of the other threads, as where their frames end is unknown. Pointers kept only
+    /// in the registers of a running thread are missed.
+    #[inline(never)]
+    unsafe fn scan_stacks(&mut self) {
+        if let Some((_, stack_end)) = get_stack_range() {
+            let frame_addr = &stack_end as *const usize as usize;
+            self.scan_range(frame_addr, stack_end);
+        }
+        let current = libc::pthread_self();
+        if current as usize != MAIN_THREAD.load(Ordering::Relaxed) {
+            if let Some((start, end)) = get_main_stack_range() {
+                self.scan_range(start, end);
+            }
+        }
+        // held while scanning, so that no stack is released in the meantime
+        let thread_stacks = THREAD_STACKS.lock().unwrap();
+        for (_, &(start, end)) in thread_stacks
+            .iter()
+            .filter(|(&thread, _)| thread != current)
+        {
+            self.scan_range(start, end);
+        }
+    }
+
+    /// Marks every chunk reachable from the roots and returns the unreachable ones
+    unsafe fn find_leaks(mut self) -> Vec<(usize, AllocStack)> {
+        self.scan_globals();
+        self.scan_stacks();
+        while let Some(idx) = self.worklist.pop() {
+            let (usable_ptr, size, _) = self.chunks[idx];
+            self.scan_range(usable_ptr, usable_ptr + size);
+        }
+        self.chunks
+            .into_iter()
+            .zip(self.reachable)
+            .filter(|((_, _, stack), reachable)| !reachable && !stack.is_from_loader())
+            .map(|((_, size, stack), _)| (size, stack))
+            .collect()
+    }
+}
+
+fn symbolize(pc: usize) -> String {
+    let mut info = MaybeUninit::<Dl_info>::zeroed();
+    let info = unsafe {
+        if libc::dladdr(pc as *const c_void, info.as_mut_ptr()) == 0 {
+            return format!("{:#x}", pc);
+        }
+        info.assume_init()
+    };
+    // `dladdr` fills in null or nul-terminated names
+    let (object, func) = unsafe {
+        (
+            cstr_to_string(info.dli_fname),
+            cstr_to_string(info.dli_sname),
+        )
+    };
+    let object = object.rsplit('/').next().unwrap_or_default();
+    let offset = pc - info.dli_fbase as usize;
+    match (func.is_empty(), is_test_enabled()) {
+        (false, true) => format!("{} ({})", func, object),
+        (true, true) => format!("({})", object),
+        (false, false) => format!("{:#x} in {} ({}+{:#x})", pc, func, object, offset),
+        (true, false) => format!("{:#x} ({}+{:#x})", pc, object, offset),
+    }
+}
+
+/// Reports unreachable chunks grouped by their allocation stack and returns the number of
+/// leaked bytes
+fn report_leaks(leaks: Vec<(usize, AllocStack)>) -> usize {
+    let mut groups = HashMap::<AllocStack, (usize, usize)>::new();
+    for (size, stack) in leaks {
+        let (bytes, count) = groups.entry(stack).or_default();
+        *bytes += size;
+        *count += 1;
+    }
+    let mut groups = groups.into_iter().collect::<Vec<_>>();
+    groups.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    let (total_bytes, total_count) = groups
+        .iter()
+        .fold((0, 0), |(bytes, count), (_, (b, c))| (bytes + b, count + c));
+    if total_count == 0 {
+        return 0;
+    }
+    eprintln!("[ASAN] memory leaks detected");
+    for (stack, (bytes, count)) in groups {
+        eprintln!(
+            "[ASAN] {} byte(s) leaked in {} allocation(s) from:",
+            bytes, count
+        );
+        for (idx, pc) in stack.frames().iter().enumerate() {
+            eprintln!("    #{} {}", idx, symbolize(*pc));
+        }
+    }
+    eprintln!(
+        "[ASAN] SUMMARY: {} byte(s) leaked in {} allocation(s)",
+        total_bytes, total_count
+    );
+    total_bytes
+}
+
+/// Runs a leak check on demand and returns the number of leaked bytes. Allocations are only
+/// tracked with `ASAN_OPTIONS=detect_leaks=1`, in which case the check also runs at exit.
+/// Other threads are expected to be idle while checking, as their stacks are read as they are.
+#[no_mangle]
+pub extern "C" fn __asan_do_leak_check() -> size_t {
+    if !is_leak_detection_enabled() {
+        return 0;
+    }
+    with_reentered(|| unsafe { report_leaks(LeakScanner::new().find_leaks()) })
+}
+
+extern "C" fn leak_check_at_exit() {
+    if __asan_do_leak_check() != 0 && !is_test_enabled() {
+        unsafe {
+            libc::fflush(std::ptr::null_mut());
+            libc::_exit(EXIT_CODE);
+        }
+    }
+}
+
+// registered before `main`, so that the check runs after the exit handlers of the program
+#[used]
+#[link_section = ".init_array"]
+static LEAK_CHECK_CTOR: extern "C" fn() = {
+    extern "C" fn ctor() {
+        if is_leak_detection_enabled() {
+            unsafe {
+                MAIN_THREAD.store(libc::pthread_self() as usize, Ordering::Relaxed);
+                libc::atexit(leak_check_at_exit);
+            }
+        }
+    }
+    ctor
+};
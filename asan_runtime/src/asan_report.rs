@@ -0,0 +1,162 @@
+use crate::{
+    asan_hook::{
+        convert_to_shadow_idx, read_shadow_mem, with_reentered, CLEAN_BYTE_MARKER, FREED_MARKER,
+        HEAP_LEFT_REDZONE_MARKER, HEAP_RIGHT_REDZONE_MARKER, SHADOW_SCALE,
+        STACK_AFTER_RETURN_MARKER, STACK_AFTER_SCOPE_MARKER, STACK_LEFT_REDZONE_MARKER,
+        STACK_RIGHT_REDZONE_MARKER,
+    },
+    asan_runtime::{__asan_mem_check, get_option, is_test_enabled},
+};
+use libc::{c_int, c_void, Dl_info};
+use std::collections::{BTreeMap, HashMap};
+use std::mem::MaybeUninit;
+use std::sync::{Mutex, Once};
+
+// deep enough to skip the runtime frames and keep a few frames of the program
+const MAX_STACK_FRAMES: usize = 32;
+
+extern "C" {
+    fn backtrace(buffer: *mut *mut c_void, size: c_int) -> c_int;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BugClass {
+    HeapBufferOverflow,
+    StackBufferOverflow,
+    HeapUseAfterFree,
+    StackUseAfterReturn,
+    StackUseAfterScope,
+    AllocDeallocMismatch,
+    Unknown,
+}
+
+impl BugClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BugClass::HeapBufferOverflow => "heap-buffer-overflow",
+            BugClass::StackBufferOverflow => "stack-buffer-overflow",
+            BugClass::HeapUseAfterFree => "heap-use-after-free",
+            BugClass::StackUseAfterReturn => "stack-use-after-return",
+            BugClass::StackUseAfterScope => "stack-use-after-scope",
+            BugClass::AllocDeallocMismatch => "alloc-dealloc-mismatch",
+            BugClass::Unknown => "unknown-crash",
+        }
+    }
+
+    /// Classifies an invalid access by the shadow byte of the poisoned granule
+    pub fn from_poisoned_granule(shadow_idx: usize) -> Self {
+        let mut shadow_val = read_shadow_mem(shadow_idx);
+        // partially addressable granule is followed by the right redzone
+        if shadow_val > 0 {
+            shadow_val = read_shadow_mem(shadow_idx + 1);
+        }
+        match shadow_val {
+            HEAP_LEFT_REDZONE_MARKER | HEAP_RIGHT_REDZONE_MARKER => BugClass::HeapBufferOverflow,
+            STACK_LEFT_REDZONE_MARKER | STACK_RIGHT_REDZONE_MARKER => BugClass::StackBufferOverflow,
+            FREED_MARKER => BugClass::HeapUseAfterFree,
+            STACK_AFTER_RETURN_MARKER => BugClass::StackUseAfterReturn,
+            STACK_AFTER_SCOPE_MARKER => BugClass::StackUseAfterScope,
+            _ => BugClass::Unknown,
+        }
+    }
+}
+
+/// Number of hits of each (bug class, fault site)
+type ErrorCounts = HashMap<(BugClass, usize), usize>;
+
+lazy_static::lazy_static! {
+    static ref ERRORS: Mutex<ErrorCounts> = Mutex::new(HashMap::new());
+}
+
+static SUMMARY_INIT: Once = Once::new();
+
+/// Whether the process is terminated on the first error (`ASAN_OPTIONS=halt_on_error=0`
+/// keeps running). Unit tests always keep running to see the report.
+pub fn halt_on_error() -> bool {
+    !is_test_enabled() && get_option("halt_on_error").is_none_or(|val| val != "0")
+}
+
+/// Records an error and returns whether it has not been reported yet. Errors are
+/// deduplicated by their bug class and the code location triggering them.
+pub fn record_error(bug_class: BugClass) -> bool {
+    let site = with_reentered(get_fault_site);
+    let is_first = with_reentered(|| {
+        let mut errors = ERRORS.lock().unwrap();
+        let hits = errors.entry((bug_class, site)).or_default();
+        *hits += 1;
+        *hits == 1
+    });
+    if !halt_on_error() && get_option("halt_on_error").is_some() {
+        SUMMARY_INIT.call_once(|| unsafe {
+            libc::atexit(print_summary);
+        });
+    }
+    is_first
+}
+
+extern "C" fn print_summary() {
+    with_reentered(|| {
+        let errors = ERRORS.lock().unwrap();
+        let mut per_class = BTreeMap::<BugClass, (usize, usize)>::new();
+        for ((bug_class, _), hits) in errors.iter() {
+            let (sites, total) = per_class.entry(*bug_class).or_default();
+            *sites += 1;
+            *total += hits;
+        }
+        eprintln!(
+            "[ASAN] SUMMARY: {} unique error(s), {} in total",
+            errors.len(),
+            errors.values().sum::<usize>()
+        );
+        for (bug_class, (sites, total)) in per_class {
+            eprintln!(
+                "[ASAN]   {}: {} location(s), {} hit(s)",
+                bug_class.as_str(),
+                sites,
+                total
+            );
+        }
+    });
+}
+
+/// Returns the return addresses of the current call stack, innermost first
+pub fn get_stack_trace() -> Vec<usize> {
+    let mut frames = [std::ptr::null_mut(); MAX_STACK_FRAMES];
+    let depth = unsafe { backtrace(frames.as_mut_ptr(), MAX_STACK_FRAMES as c_int) };
+    frames[..depth.max(0) as usize]
+        .iter()
+        .map(|frame| *frame as usize)
+        .collect()
+}
+
+/// Returns the return address of the innermost frame outside the runtime, i.e., the faulting
+/// access or the call of an intercepted function
+fn get_fault_site() -> usize {
+    let runtime_base = get_object_base(__asan_mem_check as *const () as usize);
+    get_stack_trace()
+        .into_iter()
+        .find(|&pc| get_object_base(pc) != runtime_base)
+        .unwrap_or(0)
+}
+
+/// Returns the base address of the shared object containing `addr`
+pub fn get_object_base(addr: usize) -> usize {
+    let mut info = MaybeUninit::<Dl_info>::zeroed();
+    unsafe {
+        if libc::dladdr(addr as *const c_void, info.as_mut_ptr()) == 0 {
+            return 0;
+        }
+        info.assume_init().dli_fbase as usize
+    }
+}
+
+/// Returns the first shadow granule poisoned within `[addr, addr + access_size)`
+pub fn find_poisoned_granule(addr: usize, access_size: usize) -> Option<usize> {
+    let last_addr = addr + access_size - 1;
+    (convert_to_shadow_idx(addr)..=convert_to_shadow_idx(last_addr)).find(|&shadow_idx| {
+        let shadow_val = read_shadow_mem(shadow_idx);
+        // the last byte accessed within this granule
+        let granule_last = last_addr.min((shadow_idx << SHADOW_SCALE) | 0x07);
+        shadow_val != CLEAN_BYTE_MARKER && ((granule_last & 0x07) + 1) as i8 > shadow_val
+    })
+}
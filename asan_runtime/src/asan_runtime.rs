@@ -1,73 +1,123 @@
-use crate::asan_hook::{
-    convert_to_shadow_idx, CLEAN_BYTE_MARKER, MALLOC_REENTERED, SHADOW_MEMORY, SHADOW_SIZE,
+use crate::{
+    asan_hook::MALLOC_REENTERED,
+    asan_report::{find_poisoned_granule, halt_on_error, record_error, BugClass},
 };
 use std::backtrace::Backtrace;
-use std::env;
 
-pub fn cstr_to_string(file_ptr: *const libc::c_char) -> String {
-    if file_ptr.is_null() {
-        return "".to_string();
-    }
-    unsafe {
-        std::ffi::CStr::from_ptr(file_ptr)
-            .to_string_lossy()
-            .into_owned()
-    }
-}
+use sanitizer_common::options;
+pub use sanitizer_common::options::cstr_to_string;
+
 const ASAN_TEST_ENABLED: &str = "ASAN_UNIT_TEST_ENABLED";
-const EXIT_CODE: i32 = 99;
+const ASAN_OPTIONS: &str = "ASAN_OPTIONS";
+pub const EXIT_CODE: i32 = 99;
 
+/// `src_loc_ptr` describes the checked access, which is `file:line:col in func` for
+/// instrumented code or the name of an intercepted function (e.g., `libc::strcpy`)
+///
+/// # Safety
+///
+/// `src_loc_ptr` must be null or point to a nul-terminated string.
 #[no_mangle]
-pub extern "C" fn __asan_mem_check(file_ptr: *const libc::c_char, addr: usize, access_size: usize) {
-    if file_ptr.is_null() || addr == 0 {
+pub unsafe extern "C" fn __asan_mem_check(
+    src_loc_ptr: *const libc::c_char,
+    addr: usize,
+    access_size: usize,
+) {
+    if src_loc_ptr.is_null() || addr == 0 || access_size == 0 {
         return;
     }
 
-    let filename = cstr_to_string(file_ptr);
-    let shadow_idx = convert_to_shadow_idx(addr);
-    let shadow_val = SHADOW_MEMORY.lock().unwrap()[shadow_idx % SHADOW_SIZE];
-    if shadow_val != CLEAN_BYTE_MARKER && ((addr & 0x07) + access_size) as i8 > shadow_val {
-        report_asan_violated(&filename, addr);
+    if let Some(shadow_idx) = find_poisoned_granule(addr, access_size) {
+        let src_loc = cstr_to_string(src_loc_ptr);
+        report_asan_violated(&src_loc, addr, BugClass::from_poisoned_granule(shadow_idx));
     }
 }
 
-fn report_asan_violated(filename: &str, addr: usize) {
+fn report_asan_violated(src_loc: &str, addr: usize, bug_class: BugClass) {
+    if !record_error(bug_class) {
+        return;
+    }
     if is_test_enabled() {
-        eprintln!("[ASAN] invalid memory access detected at {}", filename);
+        eprintln!("[ASAN] invalid memory access detected at {}", src_loc);
     } else {
         eprintln!(
             "[ASAN] invalid memory access detected at {}: 0x{:x}",
-            filename, addr
+            src_loc, addr
+        );
+    }
+    // print backtrace
+    MALLOC_REENTERED.with(|re_enter| {
+        re_enter.set(true);
+        let bt = Backtrace::force_capture();
+        if is_test_enabled() {
+            eprintln!("{}", trim_runtime_bt(bt.to_string()));
+        } else {
+            eprintln!("{bt}");
+        }
+        re_enter.set(false);
+    });
+    if halt_on_error() {
+        unsafe {
+            libc::_exit(EXIT_CODE);
+        }
+    }
+}
+
+/// Reports a chunk released by a function of another allocation family (e.g., `new` and `free`)
+pub fn report_alloc_dealloc_mismatch(addr: usize, alloc_str: &str, dealloc_str: &str) {
+    if !record_error(BugClass::AllocDeallocMismatch) {
+        return;
+    }
+    if is_test_enabled() {
+        eprintln!(
+            "[ASAN] alloc-dealloc mismatch ({} vs {}) detected",
+            alloc_str, dealloc_str
+        );
+    } else {
+        eprintln!(
+            "[ASAN] alloc-dealloc mismatch ({} vs {}) detected: 0x{:x}",
+            alloc_str, dealloc_str, addr
         );
     }
     // print backtrace
     MALLOC_REENTERED.with(|re_enter| {
-        *re_enter.lock().unwrap() = true;
+        re_enter.set(true);
         let bt = Backtrace::force_capture();
         if is_test_enabled() {
             eprintln!("{}", trim_runtime_bt(bt.to_string()));
         } else {
             eprintln!("{bt}");
         }
-        *re_enter.lock().unwrap() = false;
+        re_enter.set(false);
     });
-    if !is_test_enabled() {
+    if halt_on_error() {
         unsafe {
             libc::_exit(EXIT_CODE);
         }
     }
 }
 
-fn is_test_enabled() -> bool {
-    matches!(env::var(ASAN_TEST_ENABLED), Ok(val) if val == "1")
+pub fn is_test_enabled() -> bool {
+    options::is_enabled(ASAN_TEST_ENABLED)
+}
+
+/// Reads an option from `ASAN_OPTIONS` (e.g., `ASAN_OPTIONS=halt_on_error=0:detect_leaks=1`)
+pub fn get_option(name: &str) -> Option<String> {
+    options::get_option(ASAN_OPTIONS, name)
 }
 
+/// Drops runtime frames so that the backtrace starts at the frame calling into the runtime
+/// (i.e., the instrumented code or an interceptor)
 fn trim_runtime_bt(bt: String) -> String {
-    bt.lines()
-        .skip_while(|line| !line.contains("__asan_mem_check"))
-        // remove filepath line from `strcpy` test case
-        .filter(|line| !line.contains("asan_hook.rs"))
-        .skip(2)
+    let mut lines = bt.lines().collect::<Vec<_>>();
+    let runtime_frames = lines
+        .iter()
+        .rposition(|line| line.contains("asan_runtime::"))
+        .map_or(0, |idx| idx + 1);
+    lines
+        .drain(runtime_frames..)
+        // remove filepath lines of runtime frames (e.g., `strcpy` interceptor)
+        .filter(|line| !line.contains("asan_runtime/src/") && !line.contains("__asan_mem_check"))
         .collect::<Vec<_>>()
         .join("\n")
 }
@@ -0,0 +1,122 @@
+use crate::{
+    asan_hook::{
+        convert_to_shadow_idx, poison_shadow_range, with_reentered, write_shadow_mem,
+        CLEAN_BYTE_MARKER, REDZONE_SIZE, SHADOW_SCALE, STACK_AFTER_RETURN_MARKER,
+        STACK_AFTER_SCOPE_MARKER,
+    },
+    asan_intrinsic::{get_cfree, get_cposix_memalign},
+    asan_runtime::get_option,
+};
+use libc::{c_void, size_t};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ptr;
+
+// returned frames are kept poisoned until this many bytes of newer frames are returned
+const FAKE_STACK_QUARANTINE_SIZE: usize = 1 << 20;
+
+lazy_static::lazy_static! {
+    static ref FAKE_STACK_ENABLED: bool =
+        get_option("detect_stack_use_after_return").is_some_and(|val| val == "1");
+}
+
+/// Frames returned by the current thread, oldest first
+#[derive(Default)]
+struct FakeStackQuarantine {
+    frames: VecDeque<(usize, usize)>,
+    size: usize,
+}
+
+thread_local! {
+    static FAKE_STACK_QUARANTINE: RefCell<FakeStackQuarantine> =
+        RefCell::new(FakeStackQuarantine::default());
+}
+
+fn is_fake_stack_enabled() -> bool {
+    with_reentered(|| *FAKE_STACK_ENABLED)
+}
+
+unsafe fn release_fake_frame(frame: usize, size: usize) {
+    poison_shadow_range(frame, size, CLEAN_BYTE_MARKER);
+    get_cfree()(frame as *mut c_void);
+}
+
+/// Allocates a frame for a stack object on the heap, so that it outlives the function.
+/// Returns null unless `ASAN_OPTIONS=detect_stack_use_after_return=1` is given, in which case
+/// the object stays on the real stack.
+///
+/// # Safety
+/// The returned frame is released only by `__asan_stack_free` with the same `size`
+#[no_mangle]
+pub unsafe extern "C" fn __asan_stack_malloc(size: size_t) -> *mut c_void {
+    if !is_fake_stack_enabled() {
+        return ptr::null_mut();
+    }
+    with_reentered(|| {
+        let mut frame = ptr::null_mut();
+        // stack objects are aligned up to the redzone size
+        if get_cposix_memalign()(&mut frame, REDZONE_SIZE, size) != 0 {
+            return ptr::null_mut();
+        }
+        frame
+    })
+}
+
+/// Called when the function owning a stack object returns. A fake frame is poisoned and
+/// quarantined to catch use-after-return, while the real stack frame is unpoisoned so that
+/// stale redzones do not hit later frames.
+///
+/// # Safety
+/// `fake_frame` is null or was returned by `__asan_stack_malloc(size)`, and `real_frame` is
+/// the `size`-byte frame on the real stack
+#[no_mangle]
+pub unsafe extern "C" fn __asan_stack_free(
+    fake_frame: *mut c_void,
+    real_frame: *mut c_void,
+    size: size_t,
+) {
+    if fake_frame.is_null() {
+        poison_shadow_range(real_frame as usize, size, CLEAN_BYTE_MARKER);
+        return;
+    }
+    poison_shadow_range(fake_frame as usize, size, STACK_AFTER_RETURN_MARKER);
+    with_reentered(|| {
+        let quarantined = FAKE_STACK_QUARANTINE.try_with(|quarantine| {
+            let mut quarantine = quarantine.borrow_mut();
+            quarantine.frames.push_back((fake_frame as usize, size));
+            quarantine.size += size;
+            while quarantine.size > FAKE_STACK_QUARANTINE_SIZE {
+                let (frame, size) = quarantine.frames.pop_front().unwrap();
+                quarantine.size -= size;
+                release_fake_frame(frame, size);
+            }
+        });
+        // the thread is exiting
+        if quarantined.is_err() {
+            release_fake_frame(fake_frame as usize, size);
+        }
+    });
+}
+
+/// Poisons a stack object whose scope has ended (`llvm.lifetime.end`)
+#[no_mangle]
+pub extern "C" fn __asan_poison_stack_scope(usable_ptr: *mut c_void, size: size_t) {
+    let end = (usable_ptr as usize + size).next_multiple_of(1 << SHADOW_SCALE);
+    poison_shadow_range(
+        usable_ptr as usize,
+        end - usable_ptr as usize,
+        STACK_AFTER_SCOPE_MARKER,
+    );
+}
+
+/// Unpoisons a stack object entering its scope (`llvm.lifetime.start`)
+#[no_mangle]
+pub extern "C" fn __asan_unpoison_stack_scope(usable_ptr: *mut c_void, size: size_t) {
+    let start = usable_ptr as usize;
+    poison_shadow_range(start, size & !0x07, CLEAN_BYTE_MARKER);
+    // the last granule is partially addressable
+    let remaining = size & 0x07;
+    if remaining != 0 {
+        write_shadow_mem(convert_to_shadow_idx(start + size), remaining as i8);
+    }
+}
@@ -1,3 +1,6 @@
 pub mod asan_hook;
 pub mod asan_intrinsic;
+pub mod asan_leak;
+pub mod asan_report;
 pub mod asan_runtime;
+pub mod asan_stack;
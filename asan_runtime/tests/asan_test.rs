@@ -0,0 +1,74 @@
+use asan_runtime::asan_hook::{calloc, free, malloc, realloc};
+use asan_runtime::asan_report::find_poisoned_granule;
+use std::thread;
+
+const NUM_THREADS: usize = 16;
+const NUM_ITERS: usize = 2000;
+
+fn is_addressable(ptr: *mut libc::c_void, size: usize) -> bool {
+    find_poisoned_granule(ptr as usize, size).is_none()
+}
+
+#[test]
+fn test_concurrent_malloc_free() {
+    let handles: Vec<_> = (0..NUM_THREADS)
+        .map(|tid| {
+            thread::spawn(move || unsafe {
+                for i in 0..NUM_ITERS {
+                    let size = (tid * NUM_ITERS + i) % 256 + 1;
+                    let ptr = malloc(size);
+                    assert!(is_addressable(ptr, size));
+                    assert!(!is_addressable(ptr, size + 1));
+                    ptr.cast::<u8>().write_bytes(tid as u8, size);
+                    free(ptr);
+                    assert!(!is_addressable(ptr, 1));
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+#[test]
+fn test_concurrent_realloc() {
+    let handles: Vec<_> = (0..NUM_THREADS)
+        .map(|tid| {
+            thread::spawn(move || unsafe {
+                let mut ptr = calloc(1, 1).cast::<u8>();
+                *ptr = tid as u8;
+                for size in 1..NUM_ITERS {
+                    ptr = realloc(ptr.cast(), size + 1).cast();
+                    assert!(is_addressable(ptr.cast(), size + 1));
+                    // contents written before growing are kept
+                    assert!((0..size).all(|i| *ptr.add(i) == tid as u8));
+                    *ptr.add(size) = tid as u8;
+                }
+                free(ptr.cast());
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+#[test]
+fn test_cross_thread_free() {
+    // chunks allocated by one thread are released by another
+    let (sender, receiver) = std::sync::mpsc::channel::<usize>();
+    let producer = thread::spawn(move || unsafe {
+        for i in 0..NUM_ITERS * NUM_THREADS {
+            sender.send(malloc(i % 64 + 1) as usize).unwrap();
+        }
+    });
+    let consumer = thread::spawn(move || unsafe {
+        for ptr in receiver {
+            free(ptr as *mut libc::c_void);
+            assert!(!is_addressable(ptr as *mut libc::c_void, 1));
+        }
+    });
+    producer.join().unwrap();
+    consumer.join().unwrap();
+}
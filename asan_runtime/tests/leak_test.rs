@@ -0,0 +1,55 @@
+use asan_runtime::asan_hook::{free, malloc};
+use asan_runtime::asan_leak::__asan_do_leak_check;
+use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+
+// sizes unlikely to be allocated by the test harness, so that reports can be told apart
+const LEAKED_SIZE: usize = 12345;
+const HELD_SIZE: usize = 6789;
+
+/// Allocates a chunk on a thread which exits without releasing it
+fn leak_chunk() {
+    thread::spawn(|| unsafe {
+        std::hint::black_box(malloc(LEAKED_SIZE));
+    })
+    .join()
+    .unwrap();
+}
+
+// run by `test_leak_check` in a child process, as allocations are tracked from the start only
+// with `ASAN_OPTIONS=detect_leaks=1`
+#[test]
+#[ignore]
+fn leak_check_child() {
+    leak_chunk();
+    // a chunk referenced only from the stack of another thread is not a leak
+    let (ready_sender, ready_receiver) = mpsc::channel();
+    let (done_sender, done_receiver) = mpsc::channel::<()>();
+    let holder = thread::spawn(move || unsafe {
+        let held = std::hint::black_box(malloc(HELD_SIZE));
+        ready_sender.send(()).unwrap();
+        done_receiver.recv().unwrap();
+        free(std::hint::black_box(held));
+    });
+    ready_receiver.recv().unwrap();
+    assert!(__asan_do_leak_check() >= LEAKED_SIZE);
+    done_sender.send(()).unwrap();
+    holder.join().unwrap();
+}
+
+#[test]
+fn test_leak_check() {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["leak_check_child", "--exact", "--ignored", "--nocapture"])
+        .env("ASAN_OPTIONS", "detect_leaks=1")
+        .env_remove("ASAN_UNIT_TEST_ENABLED")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // reported on demand and once more at exit, which fails the process
+    assert_eq!(output.status.code(), Some(99), "{}", stderr);
+    let leak_report = format!("[ASAN] {} byte(s) leaked in 1 allocation(s) from:", LEAKED_SIZE);
+    assert_eq!(stderr.matches(&leak_report).count(), 2, "{}", stderr);
+    assert!(!stderr.contains(&format!("[ASAN] {} byte(s)", HELD_SIZE)), "{}", stderr);
+}
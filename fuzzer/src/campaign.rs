@@ -5,19 +5,32 @@ use std::time::Duration;
 #[derive(Debug)]
 pub struct CrashInfo {
     pub crashes: usize,
+    pub bug_type: String,
     pub origin: Seed,
     pub minimized: Seed,
 }
 impl CrashInfo {
-    pub fn new(crashes: usize, origin: Seed, minimized: Seed) -> Self {
+    pub fn new(crashes: usize, bug_type: String, origin: Seed, minimized: Seed) -> Self {
         return Self {
             crashes,
+            bug_type,
             origin,
             minimized,
         };
     }
 }
 
+#[derive(Debug)]
+pub struct HangInfo {
+    pub hangs: usize,
+    pub seed: Seed,
+}
+impl HangInfo {
+    pub fn new(hangs: usize, seed: Seed) -> Self {
+        Self { hangs, seed }
+    }
+}
+
 #[derive(Debug)]
 pub struct FuzzerMetadata {
     pub fuzz_cnt: u64,
@@ -75,6 +88,7 @@ pub enum FuzzShot {
     ProgramOutput(String),
     Coverage(Vec<CovReport>),
     Crash(CrashInfo),
+    Hang(HangInfo),
     Metadata(FuzzerMetadata),
     SeedInfo(FuzzerSeed),
     Terminated,
@@ -85,6 +99,7 @@ pub struct FuzzingCampaign {
     pub program_output: Vec<String>,
     pub coverage: Vec<CovReport>,
     pub crash: CrashInfo,
+    pub hang: HangInfo,
     pub metadata: FuzzerMetadata,
     pub seed_info: FuzzerSeed,
 }
@@ -101,7 +116,13 @@ impl FuzzingCampaign {
                 lines_hit_ratio: "".to_string(),
                 uncovered_lines: "".to_string(),
             }],
-            crash: CrashInfo::new(0, Seed::new(vec![], 0), Seed::new(vec![], 0)),
+            crash: CrashInfo::new(
+                0,
+                "".to_string(),
+                Seed::new(vec![], 0),
+                Seed::new(vec![], 0),
+            ),
+            hang: HangInfo::new(0, Seed::new(vec![], 0)),
             metadata: FuzzerMetadata::new(
                 0,
                 fuzz_input,
@@ -123,6 +144,9 @@ impl FuzzingCampaign {
     pub fn set_crash(&mut self, crash: CrashInfo) {
         self.crash = crash;
     }
+    pub fn set_hang(&mut self, hang: HangInfo) {
+        self.hang = hang;
+    }
     pub fn set_metadata(&mut self, metadata: FuzzerMetadata) {
         self.metadata = metadata;
     }
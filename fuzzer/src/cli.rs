@@ -1,11 +1,15 @@
+use crate::output::DEFAULT_OUTPUT_DIR;
 use anyhow::Result;
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use std::path::Path;
+use std::time::Duration;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum FuzzInput {
     ProgramArgument,
     Stdin,
+    // placed in shared memory, and read through the runtime as if from the stdin or a file
+    SharedMemory,
 }
 
 impl FuzzInput {
@@ -13,83 +17,402 @@ impl FuzzInput {
         match self {
             Self::ProgramArgument => "file",
             Self::Stdin => "stdin",
+            Self::SharedMemory => "shm",
         }
     }
 
     fn from_str(input_typ: &str) -> Option<Self> {
-        [Self::ProgramArgument, Self::Stdin]
+        [Self::ProgramArgument, Self::Stdin, Self::SharedMemory]
             .iter()
             .copied()
             .find(|t| t.as_str() == input_typ)
     }
 }
 
-pub fn get_args() -> Result<(String, String, FuzzInput)> {
+/// Power schedule assigning the energy of the seeds (AFLFast)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PowerSchedule {
+    Explore,
+    Fast,
+    Coe,
+    Exploit,
+}
+
+impl PowerSchedule {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Explore => "explore",
+            Self::Fast => "fast",
+            Self::Coe => "coe",
+            Self::Exploit => "exploit",
+        }
+    }
+
+    fn from_str(schedule: &str) -> Option<Self> {
+        [Self::Explore, Self::Fast, Self::Coe, Self::Exploit]
+            .iter()
+            .copied()
+            .find(|s| s.as_str() == schedule)
+    }
+}
+
+#[derive(Debug)]
+pub struct FuzzArgs {
+    pub program_path: String,
+    // not given when resuming
+    pub seed_dir: Option<String>,
+    pub input_typ: FuzzInput,
+    pub output_dir: String,
+    pub resume: bool,
+    // number of parallel workers, each with its own forkserver
+    pub jobs: usize,
+    // derived from the first run if not given
+    pub timeout: Option<Duration>,
+    pub dict_paths: Vec<String>,
+    // random if not given
+    pub rng_seed: Option<u64>,
+    pub schedule: PowerSchedule,
+    pub persistent: bool,
+}
+
+/// Arguments of `cmin`, minimizing a corpus
+#[derive(Debug)]
+pub struct CminArgs {
+    pub program_path: String,
+    pub input_dir: String,
+    pub output_dir: String,
+    pub input_typ: FuzzInput,
+    pub timeout: Option<Duration>,
+}
+
+/// Arguments of `tmin`, minimizing a test case
+#[derive(Debug)]
+pub struct TminArgs {
+    pub program_path: String,
+    pub input_path: String,
+    pub output_path: String,
+    pub input_typ: FuzzInput,
+    pub timeout: Option<Duration>,
+}
+
+/// Arguments of `repro`, running the target once with an input
+#[derive(Debug)]
+pub struct ReproArgs {
+    pub program_path: String,
+    pub input_path: String,
+    pub input_typ: FuzzInput,
+    // waits for the target to exit if not given
+    pub timeout: Option<Duration>,
+    pub coverage: bool,
+}
+
+#[derive(Debug)]
+pub enum FuzzCommand {
+    Fuzz(FuzzArgs),
+    Cmin(CminArgs),
+    Tmin(TminArgs),
+    Repro(ReproArgs),
+}
+
+fn program_arg() -> Arg {
+    Arg::new("target_program_path")
+        .short('p')
+        .long("program")
+        .value_name("string")
+        .help("Specify input program path")
+        .required(true) // Make it mandatory <- (yes)
+}
+
+fn input_type_arg() -> Arg {
+    Arg::new("input_type")
+        .short('i')
+        .long("input_type")
+        .value_name("string")
+        .help("<file | stdin | shm>, shm serves `read(0, ...)`, the `stdin` stream and `fopen(<input file>, \"r\")` from shared memory, while other ways of reading (e.g., `open` or `mmap` of the input file) see no input")
+        .required(true) // Make it mandatory <- (yes)
+}
+
+fn timeout_arg() -> Arg {
+    Arg::new("timeout")
+        .short('t')
+        .long("timeout")
+        .value_name("msec")
+        .value_parser(clap::value_parser!(u64).range(1..))
+}
+
+fn get_program_path(matches: &ArgMatches) -> Result<String> {
+    let program_path = matches.get_one::<String>("target_program_path").unwrap();
+    if !check_if_exist(program_path) {
+        return Err(anyhow::anyhow!(format!(
+            "program path ({program_path}) does not exist"
+        )));
+    }
+    Ok(format!("./{}", program_path))
+}
+
+fn get_input_typ(matches: &ArgMatches) -> Result<FuzzInput> {
+    let input_typ_str = matches.get_one::<String>("input_type").unwrap();
+    FuzzInput::from_str(input_typ_str).ok_or(anyhow::anyhow!(
+        "Invalid input type. Only <file | stdin | shm> available "
+    ))
+}
+
+fn get_timeout(matches: &ArgMatches) -> Option<Duration> {
+    matches
+        .get_one::<u64>("timeout")
+        .map(|timeout| Duration::from_millis(*timeout))
+}
+
+pub fn get_args() -> Result<FuzzCommand> {
     let matches = Command::new("fuzz")
-        .arg(
-            Arg::new("target_program_path")
-                .short('p')
-                .long("program")
-                .value_name("string")
-                .help("Specify input program path")
-                .required(true), // Make it mandatory <- (yes)
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .subcommand(
+            Command::new("cmin")
+                .about("Keep the smallest set of the inputs preserving the union of their edges")
+                .arg(program_arg())
+                .arg(input_type_arg())
+                .arg(timeout_arg().help("Specify the timeout of a run in milliseconds (default: 1000)"))
+                .arg(
+                    Arg::new("seed_directory_path")
+                        .short('c')
+                        .long("corpus")
+                        .value_name("string")
+                        .help("Specify the directory of the inputs to minimize")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("output_directory_path")
+                        .short('o')
+                        .long("output")
+                        .value_name("string")
+                        .help("Specify the directory the kept inputs are copied to")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("tmin")
+                .about("Shrink an input while preserving the exact edges it exercises")
+                .arg(program_arg())
+                .arg(input_type_arg())
+                .arg(timeout_arg().help("Specify the timeout of a run in milliseconds (default: 1000)"))
+                .arg(
+                    Arg::new("input_path")
+                        .value_name("file")
+                        .help("Specify the input to minimize")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("output_path")
+                        .short('o')
+                        .long("output")
+                        .value_name("string")
+                        .help("Specify the file the minimized input is written to")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("repro")
+                .about("Run the target once with an input, without the forkserver")
+                .arg(program_arg())
+                .arg(input_type_arg())
+                .arg(timeout_arg().help("Specify the timeout of the run in milliseconds (default: none)"))
+                .arg(
+                    Arg::new("input_path")
+                        .value_name("file")
+                        .help("Specify the input to run (e.g., a saved crash)")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("coverage")
+                        .long("coverage")
+                        .action(ArgAction::SetTrue)
+                        .help("Report the lines hit by the run through the coverage runtime"),
+                ),
         )
+        .arg(program_arg())
         .arg(
             Arg::new("seed_directory_path")
                 .short('c')
-                .long("seed")
+                .long("corpus")
                 .value_name("string")
                 .help("Specify seed directory path")
-                .required(true), // Make it mandatory <- (yes)
+                .required_unless_present("resume"),
+        )
+        .arg(input_type_arg())
+        .arg(
+            Arg::new("output_directory_path")
+                .short('o')
+                .long("output")
+                .value_name("string")
+                .default_value(DEFAULT_OUTPUT_DIR)
+                .help("Specify output directory path holding queue, crashes, hangs and stats"),
+        )
+        .arg(
+            timeout_arg()
+                .help("Specify the timeout of a run in milliseconds (default: derived from the first run)"),
+        )
+        .arg(
+            Arg::new("dict")
+                .short('x')
+                .long("dict")
+                .value_name("string")
+                .action(ArgAction::Append)
+                .help("Specify dictionary file path (`<program>.dict` made by instrumentation is loaded if exists)"),
+        )
+        .arg(
+            Arg::new("rng_seed")
+                .short('s')
+                .long("seed")
+                .value_name("number")
+                .value_parser(clap::value_parser!(u64))
+                .help("Specify the seed of the random number generator to replay a campaign (default: random, logged in fuzzer_stats)"),
         )
         .arg(
-            Arg::new("input_type")
-                .short('t')
-                .long("input_type")
+            Arg::new("schedule")
+                .short('P')
+                .long("schedule")
                 .value_name("string")
-                .help("<file | stdin>")
-                .required(true), // Make it mandatory <- (yes)
+                .default_value("fast")
+                .help("<explore | fast | coe | exploit>, power schedule assigning the energy of the seeds"),
+        )
+        .arg(
+            Arg::new("persistent")
+                .long("persistent")
+                .action(ArgAction::SetTrue)
+                .help("Run many inputs per process, fed through shared memory (the target loops on `__fuzzer_loop` or defines `LLVMFuzzerTestOneInput`)"),
+        )
+        .arg(
+            Arg::new("jobs")
+                .short('j')
+                .long("jobs")
+                .value_name("number")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1")
+                .help("Specify the number of parallel workers syncing their seeds (threads of one process, each forking its own target)"),
+        )
+        .arg(
+            Arg::new("resume")
+                .long("resume")
+                .action(ArgAction::SetTrue)
+                .help("Resume the stopped campaign from its queue instead of the seed directory"),
         )
         .get_matches();
 
+    match matches.subcommand() {
+        Some(("cmin", matches)) => return get_cmin_args(matches).map(FuzzCommand::Cmin),
+        Some(("tmin", matches)) => return get_tmin_args(matches).map(FuzzCommand::Tmin),
+        Some(("repro", matches)) => return get_repro_args(matches).map(FuzzCommand::Repro),
+        _ => {}
+    }
+
     // Get values
-    let program_path: String = matches
-        .get_one::<String>("target_program_path")
+    let program_path = get_program_path(&matches)?;
+    let seed_dirname = matches.get_one::<String>("seed_directory_path").cloned();
+    let output_dir = matches
+        .get_one::<String>("output_directory_path")
         .unwrap()
-        .parse()
-        .expect("Invalid path");
-    let seed_dirname: String = matches
+        .clone();
+    let resume = matches.get_flag("resume");
+    let persistent = matches.get_flag("persistent");
+    let jobs = *matches.get_one::<usize>("jobs").unwrap();
+    let rng_seed = matches.get_one::<u64>("rng_seed").copied();
+    let dict_paths: Vec<String> = matches
+        .get_many::<String>("dict")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    let timeout = get_timeout(&matches);
+    let schedule_str = matches.get_one::<String>("schedule").unwrap();
+    let input_typ = get_input_typ(&matches)?;
+
+    if let Some(seed_dirname) = seed_dirname.as_ref().filter(|_| !resume) {
+        if !check_if_exist(seed_dirname) {
+            return Err(anyhow::anyhow!(format!(
+                "seed directory path ({seed_dirname}) does not exist"
+            )));
+        }
+    }
+    if let Some(dict_path) = dict_paths.iter().find(|path| !check_if_exist(path)) {
+        return Err(anyhow::anyhow!(format!(
+            "dictionary path ({dict_path}) does not exist"
+        )));
+    }
+    if jobs == 0 {
+        return Err(anyhow::anyhow!("the number of workers must be positive"));
+    }
+    let Some(schedule) = PowerSchedule::from_str(schedule_str) else {
+        return Err(anyhow::anyhow!(
+            "Invalid power schedule. Only <explore | fast | coe | exploit> available"
+        ));
+    };
+    Ok(FuzzCommand::Fuzz(FuzzArgs {
+        program_path,
+        seed_dir: seed_dirname,
+        input_typ,
+        output_dir,
+        resume,
+        jobs,
+        timeout,
+        dict_paths,
+        rng_seed,
+        schedule,
+        persistent,
+    }))
+}
+
+fn get_cmin_args(matches: &ArgMatches) -> Result<CminArgs> {
+    let input_dir = matches
         .get_one::<String>("seed_directory_path")
         .unwrap()
-        .parse()
-        .expect("Invalid path");
-    let input_typ_str: String = matches
-        .get_one::<String>("input_type")
-        .unwrap()
-        .parse()
-        .expect("invalid input type");
-
-    if !check_if_exist(&program_path) {
+        .clone();
+    if !check_if_exist(&input_dir) {
         return Err(anyhow::anyhow!(format!(
-            "program path ({program_path}) does not exist"
+            "seed directory path ({input_dir}) does not exist"
         )));
     }
-    if !check_if_exist(&seed_dirname) {
+    Ok(CminArgs {
+        program_path: get_program_path(matches)?,
+        input_dir,
+        output_dir: matches
+            .get_one::<String>("output_directory_path")
+            .unwrap()
+            .clone(),
+        input_typ: get_input_typ(matches)?,
+        timeout: get_timeout(matches),
+    })
+}
+
+fn get_tmin_args(matches: &ArgMatches) -> Result<TminArgs> {
+    let input_path = matches.get_one::<String>("input_path").unwrap().clone();
+    if !check_if_exist(&input_path) {
         return Err(anyhow::anyhow!(format!(
-            "seed directory path ({seed_dirname}) does not exist"
+            "input path ({input_path}) does not exist"
         )));
     }
-    let input_typ = FuzzInput::from_str(&input_typ_str);
-    if input_typ.is_none() {
-        return Err(anyhow::anyhow!(
-            "Invalid input type. Only <file | stdin> available "
-        ));
+    Ok(TminArgs {
+        program_path: get_program_path(matches)?,
+        input_path,
+        output_path: matches.get_one::<String>("output_path").unwrap().clone(),
+        input_typ: get_input_typ(matches)?,
+        timeout: get_timeout(matches),
+    })
+}
+
+fn get_repro_args(matches: &ArgMatches) -> Result<ReproArgs> {
+    let input_path = matches.get_one::<String>("input_path").unwrap().clone();
+    if !check_if_exist(&input_path) {
+        return Err(anyhow::anyhow!(format!(
+            "input path ({input_path}) does not exist"
+        )));
     }
-    Ok((
-        format!("./{}", program_path),
-        seed_dirname,
-        input_typ.unwrap(),
-    ))
+    Ok(ReproArgs {
+        program_path: get_program_path(matches)?,
+        input_path,
+        input_typ: get_input_typ(matches)?,
+        timeout: get_timeout(matches),
+        coverage: matches.get_flag("coverage"),
+    })
 }
 
 fn check_if_exist(filepath: &str) -> bool {
@@ -0,0 +1,91 @@
+use fuzzer_runtime::{
+    cmplog::{
+        CmpKind, CMP_LOG_COUNT, CMP_LOG_ENABLED, CMP_LOG_ENTRIES, CMP_LOG_ENTRY_SIZE,
+        CMP_LOG_OPERAND_SIZE,
+    },
+    internal::{read_u64, write_u64},
+};
+use std::collections::BTreeSet;
+
+// a comparison of a byte matches many positions of the input, so the mutants are capped
+const MAX_CMP_MUTANTS: usize = 1024;
+
+/// Operands of a comparison logged by the target
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CmpOperands {
+    pub kind: CmpKind,
+    pub lhs: Vec<u8>,
+    pub rhs: Vec<u8>,
+}
+
+/// Enables logging comparisons for the next run
+pub fn start_cmp_log(mem: &mut [u8]) {
+    write_u64(mem, CMP_LOG_COUNT, 0);
+    write_u64(mem, CMP_LOG_ENABLED, 1);
+}
+
+/// Disables logging comparisons and returns the distinct ones logged by the last run, except
+/// those already satisfied
+pub fn stop_cmp_log(mem: &mut [u8]) -> Vec<CmpOperands> {
+    write_u64(mem, CMP_LOG_ENABLED, 0);
+    let count = read_u64(mem, CMP_LOG_COUNT) as usize;
+    write_u64(mem, CMP_LOG_COUNT, 0);
+    let mut cmps = BTreeSet::new();
+    for i in 0..count {
+        let entry = CMP_LOG_ENTRIES + i * CMP_LOG_ENTRY_SIZE;
+        let Some(kind) = CmpKind::from_u8(mem[entry]) else {
+            continue;
+        };
+        let lhs_start = entry + 8;
+        let rhs_start = lhs_start + CMP_LOG_OPERAND_SIZE;
+        let lhs = mem[lhs_start..lhs_start + mem[entry + 1] as usize].to_vec();
+        let rhs = mem[rhs_start..rhs_start + mem[entry + 2] as usize].to_vec();
+        if lhs != rhs {
+            cmps.insert(CmpOperands { kind, lhs, rhs });
+        }
+    }
+    cmps.into_iter().collect()
+}
+
+// (bytes to find, bytes to replace them with)
+fn replacements(cmp: &CmpOperands) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut replacements = vec![];
+    for (from, to) in [(&cmp.lhs, &cmp.rhs), (&cmp.rhs, &cmp.lhs)] {
+        replacements.push((from.clone(), to.clone()));
+        // integers may be read from the input in big endian
+        if cmp.kind == CmpKind::Int && from.len() > 1 {
+            let reversed = |bytes: &Vec<u8>| bytes.iter().rev().copied().collect::<Vec<_>>();
+            replacements.push((reversed(from), reversed(to)));
+        }
+    }
+    replacements
+}
+
+/// Finds an operand of each comparison in the input and replaces it with the other operand,
+/// which makes the comparison go the other way if the operand comes from the input as is
+pub fn input_to_state(input: &[u8], cmps: &[CmpOperands]) -> Vec<Vec<u8>> {
+    let mut seen = BTreeSet::new();
+    let mut mutants = vec![];
+    for cmp in cmps {
+        for (from, to) in replacements(cmp) {
+            if from.is_empty() || from.len() > input.len() {
+                continue;
+            }
+            for pos in 0..=input.len() - from.len() {
+                if input[pos..pos + from.len()] != from[..] {
+                    continue;
+                }
+                let mut mutant = input[..pos].to_vec();
+                mutant.extend_from_slice(&to);
+                mutant.extend_from_slice(&input[pos + from.len()..]);
+                if mutant != input && seen.insert(mutant.clone()) {
+                    mutants.push(mutant);
+                    if mutants.len() >= MAX_CMP_MUTANTS {
+                        return mutants;
+                    }
+                }
+            }
+        }
+    }
+    mutants
+}
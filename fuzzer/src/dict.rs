@@ -0,0 +1,87 @@
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+pub const DICT_FILE_EXT: &str = "dict";
+
+/// Tokens inserted into seeds as a whole (e.g., magic values and keywords), which are hardly
+/// made by byte-level mutations
+#[derive(Debug, Default, Clone)]
+pub struct Dictionary {
+    tokens: Vec<Vec<u8>>,
+}
+
+impl Dictionary {
+    /// Reads an AFL-style dictionary, where each line is a (optionally named) quoted token
+    /// with `\xNN` escapes:
+    ///
+    /// ```text
+    /// # comment
+    /// header="\x89PNG"
+    /// "GET"
+    /// ```
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let mut dict = Self::default();
+        for (line_no, line) in fs::read_to_string(&path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let token = parse_token(line).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "invalid token at {}:{}",
+                    path.as_ref().display(),
+                    line_no + 1
+                )
+            })?;
+            dict.add_token(token);
+        }
+        Ok(dict)
+    }
+
+    pub fn add_token(&mut self, token: Vec<u8>) {
+        if !token.is_empty() && !self.tokens.contains(&token) {
+            self.tokens.push(token);
+        }
+    }
+
+    pub fn extend(&mut self, other: Dictionary) {
+        for token in other.tokens {
+            self.add_token(token);
+        }
+    }
+
+    pub fn tokens(&self) -> &[Vec<u8>] {
+        &self.tokens
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+}
+
+fn parse_token(line: &str) -> Option<Vec<u8>> {
+    // the name and the level (e.g., `name@1="..."`) are not used
+    let quoted = &line[line.find('"')?..];
+    let quoted = quoted.strip_prefix('"')?.strip_suffix('"')?;
+    let mut token = vec![];
+    let mut bytes = quoted.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            token.push(byte);
+            continue;
+        }
+        match bytes.next()? {
+            b'x' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                token.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            escaped => token.push(escaped),
+        }
+    }
+    Some(token)
+}
@@ -1,25 +1,40 @@
 use crate::{
     bucket::get_score,
     campaign::{FuzzShot, FuzzerSeed},
-    mmap::SHM,
-    seed::{Seed, SeedPool},
+    cmplog::{input_to_state, start_cmp_log, stop_cmp_log, CmpOperands},
+    dict::Dictionary,
+    mmap::{
+        SHM, SHM_AUX_PATH, SHM_AUX_SIZE, SHM_CMP_PATH, SHM_CMP_SIZE, SHM_COV_PATH, SHM_COV_SIZE,
+        SHM_INPUT_PATH, SHM_INPUT_SIZE, SHM_PATH, SHM_SIZE,
+    },
+    output::{CampaignStats, OutputDir},
+    parallel::WorkerSync,
+    queue::Queue,
+    rng::{new_rng, random_seed, FuzzRng},
+    seed::{Seed, SeedPool, CRASH_FILE_EXT, CRASH_OUTPUT_FILE_EXT},
+    stage::DeterministicStages,
+    triage::{bucket_from_name, CrashReport},
     util::write_seed,
 };
 use crate::{
-    campaign::{CrashInfo, FuzzerMetadata},
-    cli::FuzzInput,
+    campaign::{CrashInfo, FuzzerMetadata, HangInfo},
+    cli::{FuzzInput, PowerSchedule},
 };
 use anyhow::Result;
 use fuzzer_runtime::{
     coverage::PROCESS_EXIT_NORMAL,
-    internal::{read_cov_report, read_u64, write_u64},
+    input::write_input,
+    internal::{read_cov_report, read_u64, write_u128, write_u64},
 };
 use libc::{c_void, eventfd, read, write, SIGKILL};
+use rand::Rng;
 use std::{
     cmp::{max, min},
-    collections::HashSet,
+    collections::{HashMap, HashSet, VecDeque},
+    hash::{DefaultHasher, Hash, Hasher},
     os::unix::io::RawFd,
     process::{ChildStderr, ChildStdin, ChildStdout},
+    sync::{Arc, Mutex},
     thread,
 };
 use std::{
@@ -32,21 +47,39 @@ use std::{
     sync::mpsc,
 };
 
+use crate::minimize::{minimize_corpus, minimize_input, EdgeSet};
 use delta_debugging::{split, TestResult};
 use std::time::Duration;
 use std::time::Instant;
-use uuid::Uuid;
 
+const PREV_LOC_IDX: usize = fuzzer_runtime::coverage::PREV_LOC_IDX;
 const NEW_COVERAGES: usize = fuzzer_runtime::coverage::NEW_COVERAGES;
 const VISIT_EDGES_INDICIES: usize = fuzzer_runtime::coverage::VISIT_EDGES_INDICIES;
 const VISIT_MARK: usize = fuzzer_runtime::coverage::VISIT_MARK;
 const VISIT_EDGES_INDEX_SIZE: usize = fuzzer_runtime::coverage::VISIT_EDGES_INDEX_SIZE;
 
 const INITIAL_TIMEOUT_UPPER_BOUND: u32 = 5;
+const MIN_INITIAL_TIMEOUT: Duration = Duration::from_secs(1);
+// a timed out seed is re-run with a longer timeout to confirm the hang
+const HANG_CONFIRM_TIMEOUT_FACTOR: u32 = 2;
+// output of the target is read by other threads, so it is taken once no more line arrives
+const OUTPUT_SETTLE_INTERVAL: Duration = Duration::from_millis(10);
+const MAX_OUTPUT_SETTLE_ROUNDS: usize = 20;
+const STATS_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+// one in SPLICE_RATIO havoc rounds splices the seed with another queued seed first
+const SPLICE_RATIO: u32 = 8;
 
 pub enum HostSend {
     Terminate,   // 1
-    Wakeup(u64), // 2,
+    Wakeup(u64), // 2, timeout in milliseconds
+}
+
+/// Where the seed of a run comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SeedSource {
+    Pool,  // given to the pool (e.g., initial and synced seeds), run as it is
+    Stage, // mutant of the deterministic stages
+    Havoc, // havoc mutant of a kept seed
 }
 
 #[derive(Debug)]
@@ -56,54 +89,184 @@ pub enum FuzzResult {
     Success,
 }
 
+/// What a fuzzer is created with. The shared memories default to the ones of the campaign
+/// in `output`.
+pub struct FuzzerConfig {
+    pub shm_path: String,
+    pub shm_size: usize,
+    pub shm_aux_path: String,
+    pub shm_aux_size: usize,
+    pub shm_cov_path: String,
+    pub shm_cov_size: usize,
+    pub seeds: SeedPool,
+    pub queue: Queue,
+    pub output: OutputDir,
+    pub input_typ: FuzzInput,
+}
+
+impl FuzzerConfig {
+    pub fn new(seeds: SeedPool, queue: Queue, output: OutputDir, input_typ: FuzzInput) -> Self {
+        Self {
+            shm_path: output.shm_path(SHM_PATH),
+            shm_size: SHM_SIZE,
+            shm_aux_path: output.shm_path(SHM_AUX_PATH),
+            shm_aux_size: SHM_AUX_SIZE,
+            shm_cov_path: output.shm_path(SHM_COV_PATH),
+            shm_cov_size: SHM_COV_SIZE,
+            seeds,
+            queue,
+            output,
+            input_typ,
+        }
+    }
+}
+
 pub struct Fuzzer {
     shm: SHM,
     shm_aux: SHM,
     shm_cov: SHM,
+    shm_cmp: SHM,   // operands of comparisons, logged on demand
+    shm_input: SHM, // input of a run fed through shared memory
     pub forkserver_host: i32,
     pub forkserver_runtime: i32,
     seeds: SeedPool,
+    stages: VecDeque<DeterministicStages>, // of new queued seeds, run before the seed pool
+    queue: Queue,
+    output: OutputDir,
+    sync: Option<WorkerSync>, // set if the fuzzer is one of the parallel workers
     new_paths: usize,
     input_typ: FuzzInput,
-    seed_file_path: String, // if fuzz input is `ProgramArgument`, we write seed into this file path
-    crashes: HashSet<Seed>,
+    persistent: bool, // the target runs many inputs per process, read from `shm_input`
+    crashes: HashMap<u64, Seed>, // minimized reproducer of each crash bucket
+    crash_paths: HashMap<u64, u64>, // bucket of each crashing (status, path)
+    output_lines: Arc<Mutex<Vec<String>>>, // output of the current run
+    hangs: HashSet<u64>, // hashes of the paths of hangs
+    timeout: Option<Duration>, // derived from the first run if not given
+    dict: Dictionary,
+    rng: FuzzRng,
+    rng_seed: u64,
     tx: mpsc::Sender<FuzzShot>,
     fuzz_terminate: bool,
 }
 
 impl Fuzzer {
-    pub fn new(
-        shm_path: &str,
-        shm_size: usize,
-        shm_aux_path: &str,
-        shm_aux_size: usize,
-        shm_cov_path: &str,
-        shm_cov_size: usize,
-        init_seeds: SeedPool,
-        input_typ: FuzzInput,
-        tx: mpsc::Sender<FuzzShot>,
-    ) -> Self {
-        let shm = SHM::new(shm_path, shm_size);
-        let shm_aux = SHM::new(shm_aux_path, shm_aux_size);
-        let shm_cov = SHM::new(shm_cov_path, shm_cov_size);
+    pub fn new(config: FuzzerConfig, tx: mpsc::Sender<FuzzShot>) -> Self {
+        let FuzzerConfig {
+            shm_path,
+            shm_size,
+            shm_aux_path,
+            shm_aux_size,
+            shm_cov_path,
+            shm_cov_size,
+            seeds,
+            queue,
+            output,
+            input_typ,
+        } = config;
+        let shm = SHM::new(&shm_path, shm_size);
+        let shm_aux = SHM::new(&shm_aux_path, shm_aux_size);
+        let shm_cov = SHM::new(&shm_cov_path, shm_cov_size);
+        let shm_cmp = SHM::new(&output.shm_path(SHM_CMP_PATH), SHM_CMP_SIZE);
+        let shm_input = SHM::new(&output.shm_path(SHM_INPUT_PATH), SHM_INPUT_SIZE);
         let host_efd: RawFd = unsafe { eventfd(0, 0) };
         let runtime_efd: RawFd = unsafe { eventfd(0, 0) };
+        let rng_seed = random_seed();
         Self {
             shm,
             shm_aux,
             shm_cov,
+            shm_cmp,
+            shm_input,
             forkserver_host: host_efd,
             forkserver_runtime: runtime_efd,
-            seeds: init_seeds,
+            seeds,
+            stages: VecDeque::new(),
+            queue,
+            output,
+            sync: None,
             new_paths: 0,
             input_typ,
-            seed_file_path: format!("{}.seed", Uuid::new_v4()),
-            crashes: HashSet::new(),
+            persistent: false,
+            crashes: HashMap::new(),
+            crash_paths: HashMap::new(),
+            output_lines: Arc::new(Mutex::new(vec![])),
+            hangs: HashSet::new(),
+            timeout: None,
+            dict: Dictionary::default(),
+            rng: new_rng(rng_seed),
+            rng_seed,
             tx,
             fuzz_terminate: false,
         }
     }
 
+    /// Restores the edge bitmap and the crashes of a stopped campaign, so that only coverage
+    /// and crashes not found before are reported
+    pub fn resume(&mut self) -> Result<()> {
+        if let Some(bitmap) = self.queue.load_bitmap() {
+            if bitmap.len() == self.shm.size() {
+                self.shm.mut_mem().copy_from_slice(&bitmap);
+            }
+        }
+        for entry in std::fs::read_dir(self.output.crash_dir())? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != CRASH_FILE_EXT) {
+                continue;
+            }
+            let bucket = path
+                .file_stem()
+                .and_then(|name| name.to_str())
+                .and_then(bucket_from_name);
+            if let Some(bucket) = bucket {
+                self.crashes
+                    .insert(bucket, Seed::new(std::fs::read(&path)?, 0));
+            }
+        }
+        self.hangs.extend(
+            std::fs::read_dir(self.output.hang_dir())?
+                .filter_map(|entry| entry.ok()?.path().file_stem()?.to_str().map(String::from))
+                .filter_map(|path_hash| u64::from_str_radix(&path_hash, 16).ok()),
+        );
+        self.new_paths = self.queue.len();
+        Ok(())
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    pub fn set_dict(&mut self, dict: Dictionary) {
+        self.dict = dict;
+    }
+
+    pub fn set_schedule(&mut self, schedule: PowerSchedule) {
+        self.seeds.set_schedule(schedule);
+    }
+
+    /// Replays the random decisions of a campaign started with the same seed
+    pub fn set_rng_seed(&mut self, rng_seed: u64) {
+        self.rng = new_rng(rng_seed);
+        self.rng_seed = rng_seed;
+    }
+
+    /// Feeds the inputs to a target running many of them per process (see `__fuzzer_loop`)
+    pub fn set_persistent(&mut self, persistent: bool) {
+        self.persistent = persistent;
+    }
+
+    pub fn set_sync(&mut self, sync: WorkerSync) {
+        self.sync = Some(sync);
+    }
+
+    fn sync_workers(&mut self) -> Result<()> {
+        if let Some(sync) = self.sync.as_mut() {
+            for seed in sync.sync(self.shm.mut_mem())? {
+                self.seeds.add_seed(seed);
+            }
+        }
+        Ok(())
+    }
+
     pub fn wakeup_forkserver(&self, kind: HostSend) {
         let value = match kind {
             HostSend::Wakeup(timeout) => {
@@ -138,21 +301,92 @@ impl Fuzzer {
     }
 
     fn is_crash(&self, status: i32) -> bool {
-        status != PROCESS_EXIT_NORMAL as i32 && status != SIGKILL
+        status != PROCESS_EXIT_NORMAL as i32 && !self.is_hang(status)
+    }
+
+    fn is_hang(&self, status: i32) -> bool {
+        status == SIGKILL
     }
 
+    /// Edges visited by the last run, as the indices of the edge bitmap
+    fn visited_edges(&self) -> EdgeSet {
+        let visit_edges = read_u64(self.shm_aux.mem(), VISIT_MARK);
+        (0..visit_edges)
+            .map(|i| {
+                read_u64(
+                    self.shm_aux.mem(),
+                    VISIT_EDGES_INDICIES + (i as usize) * VISIT_EDGES_INDEX_SIZE,
+                )
+            })
+            .collect()
+    }
+
+    /// Identifies the path of the last run by the set of the edges visited
+    fn path_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.visited_edges().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Re-runs a timed out seed with a longer timeout and saves it if the hang is confirmed and
+    /// its path is not seen before. Returns the path hash of a new hang.
+    fn confirm_hang(
+        &mut self,
+        child_stdin: &mut Option<ChildStdin>,
+        timeout: Duration,
+        seed: &Seed,
+    ) -> Result<Option<u64>> {
+        self.feed_seed(child_stdin, seed)?;
+        let confirm_timeout = timeout * HANG_CONFIRM_TIMEOUT_FACTOR;
+        self.wakeup_forkserver(HostSend::Wakeup(confirm_timeout.as_millis() as u64));
+        let status = self.wait_forkserver();
+        let path_hash = self.path_hash();
+        self.clear_new_coverage();
+        self.clear_visited_edges();
+        if !self.is_hang(status) || !self.hangs.insert(path_hash) {
+            return Ok(None);
+        }
+        write_seed(
+            self.output
+                .hang_dir()
+                .join(format!("{:016x}.hang", path_hash)),
+            seed.get_input(),
+        )?;
+        Ok(Some(path_hash))
+    }
+
+    /// Re-runs a seed logging the operands of its comparisons
+    fn trace_cmps(
+        &mut self,
+        child_stdin: &mut Option<ChildStdin>,
+        timeout: Duration,
+        seed: &Seed,
+    ) -> Result<Vec<CmpOperands>> {
+        start_cmp_log(self.shm_cmp.mut_mem());
+        self.feed_seed(child_stdin, seed)?;
+        self.wakeup_forkserver(HostSend::Wakeup(timeout.as_millis() as u64));
+        self.wait_forkserver();
+        self.clear_new_coverage();
+        self.clear_visited_edges();
+        Ok(stop_cmp_log(self.shm_cmp.mut_mem()))
+    }
+
+    /// Fails if the seed crashes the target in the same way (i.e., into the same bucket)
     fn oracle(
         &mut self,
         child_stdin: &mut Option<ChildStdin>,
         timeout: Duration,
         seed: &Seed,
+        bucket: u64,
     ) -> Result<TestResult> {
+        self.output_lines.lock().unwrap().clear();
         self.feed_seed(child_stdin, seed)?;
-        self.wakeup_forkserver(HostSend::Wakeup(timeout.as_secs()));
+        self.wakeup_forkserver(HostSend::Wakeup(timeout.as_millis() as u64));
         let status = self.wait_forkserver();
+        let path_hash = self.path_hash();
         self.clear_new_coverage();
         self.clear_visited_edges();
-        if self.is_crash(status) {
+        if self.is_crash(status) && self.bucket_crash(status, path_hash).0 == bucket {
             Ok(TestResult::Fail)
         } else {
             Ok(TestResult::Pass)
@@ -164,8 +398,9 @@ impl Fuzzer {
         child_stdin: &mut Option<ChildStdin>,
         timeout: Duration,
         seed: &Seed,
+        bucket: u64,
     ) -> Result<Seed> {
-        self.do_ddmin(child_stdin, timeout, seed, 2)
+        self.do_ddmin(child_stdin, timeout, seed, bucket, 2)
     }
 
     fn do_ddmin(
@@ -173,31 +408,38 @@ impl Fuzzer {
         child_stdin: &mut Option<ChildStdin>,
         timeout: Duration,
         seed: &Seed,
+        bucket: u64,
         n: usize,
     ) -> Result<Seed> {
         let (delta_set, complement_set) = split(&seed.get_input().to_vec(), n);
         for delta in &delta_set {
             let delta_seed = Seed::new(delta.to_vec(), 0);
-            if let Ok(test_result) = self.oracle(child_stdin, timeout, &delta_seed) {
+            if let Ok(test_result) = self.oracle(child_stdin, timeout, &delta_seed, bucket) {
                 if test_result == TestResult::Fail {
                     if delta.len() == 1 {
                         return Ok(delta_seed);
                     }
-                    return self.do_ddmin(child_stdin, timeout, &delta_seed, 2);
+                    return self.do_ddmin(child_stdin, timeout, &delta_seed, bucket, 2);
                 }
             }
         }
         for complement in &complement_set {
             let complement_seed = Seed::new(complement.to_vec(), 0);
-            if let Ok(test_result) = self.oracle(child_stdin, timeout, &complement_seed) {
+            if let Ok(test_result) = self.oracle(child_stdin, timeout, &complement_seed, bucket) {
                 if test_result == TestResult::Fail {
-                    return self.do_ddmin(child_stdin, timeout, &complement_seed, max(n - 1, 2));
+                    return self.do_ddmin(
+                        child_stdin,
+                        timeout,
+                        &complement_seed,
+                        bucket,
+                        max(n - 1, 2),
+                    );
                 }
             }
         }
         let seed_len = seed.get_input().len();
         if n < seed_len {
-            return self.do_ddmin(child_stdin, timeout, seed, min(seed_len, 2 * n));
+            return self.do_ddmin(child_stdin, timeout, seed, bucket, min(seed_len, 2 * n));
         }
         Ok(seed.clone())
     }
@@ -216,17 +458,33 @@ impl Fuzzer {
             );
         }
         write_u64(self.shm_aux.mut_mem(), VISIT_MARK, 0);
+        // the first edge of the next run does not depend on the last one of this run
+        write_u128(self.shm_aux.mut_mem(), PREV_LOC_IDX, 0);
     }
 
-    fn add_seed(&mut self, seed: Seed) {
-        self.seeds.add_seed(seed);
-    }
-
-    fn pop_seed(&mut self) -> Option<Seed> {
-        self.seeds.pop_seed()
+    /// Takes the next mutant of the deterministic stages if any, otherwise a seed given to the
+    /// pool, otherwise a havoc mutant of the kept seed picked by the power schedule
+    fn next_seed(&mut self) -> Option<(Seed, SeedSource)> {
+        while let Some(stages) = self.stages.front_mut() {
+            if let Some(mutant) = stages.next_mutant() {
+                return Some((mutant, SeedSource::Stage));
+            }
+            self.stages.pop_front();
+        }
+        if let Some(seed) = self.seeds.pop_seed() {
+            return Some((seed, SeedSource::Pool));
+        }
+        let mut seed = self.seeds.next_entry()?.clone();
+        if self.rng.gen_ratio(1, SPLICE_RATIO) {
+            if let Some(other) = self.seeds.pick_queued(&seed, &mut self.rng).cloned() {
+                seed.splice(&other, &mut self.rng);
+            }
+        }
+        seed.mutate(&self.dict, &mut self.rng);
+        Some((seed, SeedSource::Havoc))
     }
 
-    fn is_seed_empty(&self) -> bool {
+    pub fn is_seed_empty(&self) -> bool {
         self.seeds.is_empty()
     }
 
@@ -235,12 +493,22 @@ impl Fuzzer {
         new_covs != 0
     }
 
-    fn feed_seed(&self, child_stdin: &mut Option<ChildStdin>, seed: &Seed) -> Result<()> {
+    // persistent targets read the input of each run from the shared memory, as the stdin and
+    // the input file are not read again by the same process
+    fn is_input_shared(&self) -> bool {
+        self.persistent || self.input_typ == FuzzInput::SharedMemory
+    }
+
+    fn feed_seed(&mut self, child_stdin: &mut Option<ChildStdin>, seed: &Seed) -> Result<()> {
+        if self.is_input_shared() {
+            write_input(self.shm_input.mut_mem(), seed.get_input());
+            return Ok(());
+        }
         if let Some(ref mut stdin) = child_stdin {
             stdin.write_all(seed.get_input())?;
         }
         if self.input_typ == FuzzInput::ProgramArgument {
-            write_seed(&self.seed_file_path, seed.get_input())?;
+            write_seed(self.output.input_path(), seed.get_input())?;
         }
         Ok(())
     }
@@ -250,11 +518,10 @@ impl Fuzzer {
             return (0, 0); // give zero score for hangs
         }
         let visit_edges = read_u64(self.shm_aux.mem(), VISIT_MARK);
-        // if new coverage found, give max score, otherwise give higher score if the path is rare
         if self.is_new_coverage() {
             self.new_paths += 1;
-            return (visit_edges, u64::MAX);
         }
+        // give higher score if the path is rare
         let mut score = 0;
         for i in 0..visit_edges {
             let edge = read_u64(
@@ -276,6 +543,7 @@ impl Fuzzer {
     ) where
         T: std::io::Read + Send + 'static,
     {
+        let output_lines = self.output_lines.clone();
         thread::spawn(move || {
             let reader = BufReader::new(reader_stream);
             let mut reader_iter = reader.lines();
@@ -283,6 +551,7 @@ impl Fuzzer {
             loop {
                 match reader_iter.next() {
                     Some(Ok(line)) => {
+                        output_lines.lock().unwrap().push(line.clone());
                         let _ = sender.send(FuzzShot::ProgramOutput(line));
                     }
                     Some(Err(e)) => {
@@ -301,6 +570,73 @@ impl Fuzzer {
         self.spawn_reader_thread(stderr, self.tx.clone(), "stderr");
     }
 
+    /// Returns the output of the current run once the reader threads have settled
+    fn take_output(&self) -> String {
+        let mut settled_len = usize::MAX;
+        for _ in 0..MAX_OUTPUT_SETTLE_ROUNDS {
+            let len = self.output_lines.lock().unwrap().len();
+            if len == settled_len {
+                break;
+            }
+            settled_len = len;
+            thread::sleep(OUTPUT_SETTLE_INTERVAL);
+        }
+        mem::take(&mut *self.output_lines.lock().unwrap()).join("\n")
+    }
+
+    /// Returns the bucket of the crash of the last run, along with its report and output. A
+    /// crash with the status and the path of a bucketed one is of the same bucket, so the output
+    /// is not waited for.
+    fn bucket_crash(
+        &mut self,
+        status: i32,
+        path_hash: u64,
+    ) -> (u64, Option<(CrashReport, String)>) {
+        let mut hasher = DefaultHasher::new();
+        (status, path_hash).hash(&mut hasher);
+        let crash_key = hasher.finish();
+        if let Some(&bucket) = self.crash_paths.get(&crash_key) {
+            return (bucket, None);
+        }
+        let output = self.take_output();
+        let report = CrashReport::parse(status, &output);
+        let bucket = report.bucket();
+        self.crash_paths.insert(crash_key, bucket);
+        (bucket, Some((report, output)))
+    }
+
+    /// Saves the minimized reproducer of a crash and the output of the target, unless a crash
+    /// of the same bucket is already found
+    fn triage_crash(
+        &mut self,
+        child_stdin: &mut Option<ChildStdin>,
+        timeout: Duration,
+        seed: &Seed,
+        status: i32,
+        path_hash: u64,
+    ) -> Result<Option<(CrashReport, Seed)>> {
+        let (bucket, report) = self.bucket_crash(status, path_hash);
+        if self.crashes.contains_key(&bucket) {
+            return Ok(None);
+        }
+        // bucketed before by its path but not saved, as its minimization failed
+        let (report, output) = report.unwrap_or_else(|| {
+            let output = self.take_output();
+            (CrashReport::parse(status, &output), output)
+        });
+        let minimized = self.ddmin(child_stdin, timeout, seed, bucket)?;
+        let bucket_name = report.bucket_name();
+        minimized.to_file(&self.output.crash_dir(), &bucket_name);
+        std::fs::write(
+            self.output
+                .crash_dir()
+                .join(format!("{}.{}", bucket_name, CRASH_OUTPUT_FILE_EXT)),
+            output,
+        )?;
+        self.crashes.insert(bucket, minimized.clone());
+        Ok(Some((report, minimized)))
+    }
+
     fn send(&mut self, fuzz_result: FuzzShot) {
         if self.tx.send(fuzz_result).is_err() {
             self.fuzz_terminate = true;
@@ -311,6 +647,21 @@ impl Fuzzer {
         self.wakeup_forkserver(HostSend::Terminate);
     }
 
+    /// Writes the stats and the coverage bitmap of the campaign, periodically and on exit
+    /// rather than per new seed, as the bitmap is as large as the shared memory
+    fn write_stats(&self, execs_done: u64, run_time: Duration) -> Result<()> {
+        self.queue.save_bitmap(self.shm.mem())?;
+        self.output.write_stats(&CampaignStats {
+            run_time,
+            execs_done,
+            corpus_count: self.queue.len(),
+            new_paths: self.new_paths,
+            saved_crashes: self.crashes.len(),
+            saved_hangs: self.hangs.len(),
+            rng_seed: self.rng_seed,
+        })
+    }
+
     fn debug(
         &mut self,
         loop_cnt: u64,
@@ -343,7 +694,9 @@ impl Fuzzer {
         // );
     }
 
-    pub fn run(&mut self, program_file: &str) -> Result<FuzzResult> {
+    /// Spawns the target waiting for the forkserver to wake it up, and returns its stdin if
+    /// seeds are fed through it
+    fn spawn_target(&mut self, program_file: &str) -> Result<Option<ChildStdin>> {
         let mut cmd = Command::new(program_file);
         let child_process_cmd = cmd
             .env("LD_LIBRARY_PATH", ".")
@@ -353,6 +706,8 @@ impl Fuzzer {
             .env("SHM_AUX_SIZE", format!("{}", &self.shm_aux.size()))
             .env("SHM_COV_ID", self.shm_cov.path())
             .env("SHM_COV_SIZE", format!("{}", &self.shm_cov.size()))
+            .env("SHM_CMP_ID", self.shm_cmp.path())
+            .env("SHM_CMP_SIZE", format!("{}", &self.shm_cmp.size()))
             .env("FORK_SERVER_HOST", format!("{}", self.forkserver_host))
             .env(
                 "FORK_SERVER_RUNTIME",
@@ -361,14 +716,29 @@ impl Fuzzer {
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
-        if self.input_typ == FuzzInput::ProgramArgument {
-            child_process_cmd.args(vec![&self.seed_file_path]);
-        } else {
-            child_process_cmd.stdin(Stdio::piped());
+        if self.is_input_shared() {
+            // opening the input file is served from the shared memory, so it is never written
+            child_process_cmd
+                .env("SHM_INPUT_ID", self.shm_input.path())
+                .env("SHM_INPUT_SIZE", format!("{}", &self.shm_input.size()))
+                .env("FUZZER_INPUT_PATH", self.output.input_path());
+        }
+        match self.input_typ {
+            FuzzInput::Stdin => {
+                child_process_cmd.stdin(Stdio::piped());
+            }
+            FuzzInput::ProgramArgument => {
+                child_process_cmd.arg(self.output.input_path());
+            }
+            FuzzInput::SharedMemory => {
+                child_process_cmd
+                    .arg(self.output.input_path())
+                    .stdin(Stdio::null());
+            }
         }
 
         let mut child_process = child_process_cmd.spawn()?;
-        let mut child_stdin = if self.input_typ == FuzzInput::Stdin {
+        let child_stdin = if self.input_typ == FuzzInput::Stdin {
             Some(child_process.stdin.take().unwrap())
         } else {
             None
@@ -378,35 +748,98 @@ impl Fuzzer {
             child_process.stdout.take().unwrap(),
             child_process.stderr.take().unwrap(),
         );
-        let mut init_set_timeout = false;
-        let mut timeout = Duration::new(9999, 0);
+        Ok(child_stdin)
+    }
+
+    /// Runs a seed and returns the exit status and the edges visited
+    fn replay(
+        &mut self,
+        child_stdin: &mut Option<ChildStdin>,
+        timeout: Duration,
+        seed: &Seed,
+    ) -> Result<(i32, EdgeSet)> {
+        self.output_lines.lock().unwrap().clear();
+        self.feed_seed(child_stdin, seed)?;
+        self.wakeup_forkserver(HostSend::Wakeup(timeout.as_millis() as u64));
+        let status = self.wait_forkserver();
+        let edges = self.visited_edges();
+        self.clear_new_coverage();
+        self.clear_visited_edges();
+        Ok((status, edges))
+    }
+
+    /// Returns the indices of the smallest set of the inputs preserving the union of their
+    /// edges. Inputs crashing or timing out are left out.
+    pub fn cmin(&mut self, program_file: &str, inputs: &[Vec<u8>]) -> Result<Vec<usize>> {
+        let mut child_stdin = self.spawn_target(program_file)?;
+        let timeout = self.timeout.unwrap_or(MIN_INITIAL_TIMEOUT);
+        let mut edge_sets = vec![];
+        for input in inputs {
+            let seed = Seed::new(input.clone(), 0);
+            let (status, edges) = self.replay(&mut child_stdin, timeout, &seed)?;
+            if self.is_crash(status) || self.is_hang(status) {
+                edge_sets.push((input.len(), EdgeSet::new()));
+            } else {
+                edge_sets.push((input.len(), edges));
+            }
+        }
+        self.terminate();
+        Ok(minimize_corpus(&edge_sets))
+    }
+
+    /// Shrinks the input as long as it exercises the exact same edges and ends in the same way
+    pub fn tmin(&mut self, program_file: &str, input: &[u8]) -> Result<Vec<u8>> {
+        let mut child_stdin = self.spawn_target(program_file)?;
+        let timeout = self.timeout.unwrap_or(MIN_INITIAL_TIMEOUT);
+        let expected = self.replay(&mut child_stdin, timeout, &Seed::new(input.to_vec(), 0))?;
+        let minimized = minimize_input(input, |candidate| {
+            let seed = Seed::new(candidate.to_vec(), 0);
+            Ok(self.replay(&mut child_stdin, timeout, &seed)? == expected)
+        });
+        self.terminate();
+        minimized
+    }
+
+    pub fn run(&mut self, program_file: &str) -> Result<FuzzResult> {
+        let mut child_stdin = self.spawn_target(program_file)?;
+        let mut init_set_timeout = self.timeout.is_some();
+        let mut timeout = self.timeout.unwrap_or(Duration::new(9999, 0));
         let fuzzer_started = Instant::now();
+        let mut stats_updated = Instant::now();
 
         let mut loop_cnt = 0;
-        if self.is_seed_empty() {
+        self.sync_workers()?;
+        let Some((mut seed, mut source)) = self.next_seed() else {
             return Ok(FuzzResult::AllSeedConsumed(loop_cnt));
-        }
-        let mut seed = self.pop_seed().unwrap();
+        };
 
         loop {
             if self.fuzz_terminate {
+                self.write_stats(loop_cnt, fuzzer_started.elapsed())?;
                 self.terminate();
                 self.send(FuzzShot::Terminated);
                 return Ok(FuzzResult::UserTerminated);
             }
             loop_cnt += 1;
+            self.output_lines.lock().unwrap().clear();
             self.feed_seed(&mut child_stdin, &seed)?;
-            self.wakeup_forkserver(HostSend::Wakeup(timeout.as_secs()));
+            self.wakeup_forkserver(HostSend::Wakeup(timeout.as_millis() as u64));
             let target_started = Instant::now();
             let status = self.wait_forkserver();
 
             let elapsed = target_started.elapsed();
             if !init_set_timeout {
-                timeout = elapsed * INITIAL_TIMEOUT_UPPER_BOUND;
+                timeout = max(elapsed * INITIAL_TIMEOUT_UPPER_BOUND, MIN_INITIAL_TIMEOUT);
                 init_set_timeout = true;
             }
             // evalulate seed and add it
+            let is_new_coverage = self.is_new_coverage();
             let (visit_edges, score) = self.eval_seed(status);
+            let path_hash = self.path_hash();
+            self.seeds.record_path(path_hash);
+            if source == SeedSource::Stage {
+                self.stages.front_mut().unwrap().report(path_hash);
+            }
             self.debug(
                 loop_cnt,
                 &seed,
@@ -417,41 +850,71 @@ impl Fuzzer {
             );
             self.clear_new_coverage();
             self.clear_visited_edges();
-            if self.is_seed_empty() {
-                self.terminate();
-                self.send(FuzzShot::Terminated);
-                return Ok(FuzzResult::AllSeedConsumed(loop_cnt));
+            if self.sync.as_ref().is_some_and(|sync| sync.is_due()) {
+                self.sync_workers()?;
+            }
+            if stats_updated.elapsed() >= STATS_UPDATE_INTERVAL {
+                self.write_stats(loop_cnt, fuzzer_started.elapsed())?;
+                stats_updated = Instant::now();
             }
 
-            // crash found
-            if self.is_crash(status) {
-                if let Ok(minimized) = self.ddmin(&mut child_stdin, timeout, &seed) {
-                    self.crashes.insert(minimized.clone());
-                    minimized.to_file(self.crashes.len());
+            // hang found
+            let mut ran_seed = None;
+            if self.is_hang(status) {
+                if self
+                    .confirm_hang(&mut child_stdin, timeout, &seed)?
+                    .is_some()
+                {
+                    self.send(FuzzShot::Hang(HangInfo::new(self.hangs.len(), seed)));
+                }
+            } else if self.is_crash(status) {
+                // crash found
+                if let Some((report, minimized)) =
+                    self.triage_crash(&mut child_stdin, timeout, &seed, status, path_hash)?
+                {
                     self.send(FuzzShot::Crash(CrashInfo::new(
                         self.crashes.len(),
+                        report.bug_type,
                         seed,
                         minimized,
                     )));
                 }
-            } else if score > 0 {
-                // re-evaulate score after execution
+            } else {
                 seed.set_score(score);
-                self.add_seed(seed.clone());
-                let cur_seed = seed.clone();
-                seed.mutate();
-                seed.set_score(score.saturating_add(1));
-                self.add_seed(seed.clone());
-
+                // keep seeds given to the pool and those exercising new edges, persist the new
+                // ones and run deterministic stages on them
+                if score > 0 && (source == SeedSource::Pool || is_new_coverage) {
+                    if is_new_coverage && seed.get_queue_id().is_none() {
+                        self.queue
+                            .save(&mut seed, visit_edges, fuzzer_started.elapsed())?;
+                        let cmps = self.trace_cmps(&mut child_stdin, timeout, &seed)?;
+                        let cmp_mutants = input_to_state(seed.get_input(), &cmps);
+                        self.stages.push_back(DeterministicStages::new(
+                            &seed,
+                            path_hash,
+                            cmp_mutants,
+                        ));
+                    }
+                    self.seeds.keep(seed.clone(), elapsed, path_hash);
+                }
+                ran_seed = Some(seed);
+            }
+            let Some(next) = self.next_seed() else {
+                self.write_stats(loop_cnt, fuzzer_started.elapsed())?;
+                self.terminate();
+                self.send(FuzzShot::Terminated);
+                return Ok(FuzzResult::AllSeedConsumed(loop_cnt));
+            };
+            if let Some(ran_seed) = ran_seed {
                 self.send(FuzzShot::SeedInfo(FuzzerSeed::new(
                     self.seeds.len(),
-                    cur_seed,
-                    seed,
+                    ran_seed,
+                    next.0.clone(),
                     visit_edges,
                     self.new_paths,
                 )));
             }
-            seed = self.pop_seed().unwrap();
+            (seed, source) = next;
         }
     }
 }
@@ -1,10 +1,21 @@
 pub mod bucket;
 pub mod campaign;
 pub mod cli;
+pub mod cmplog;
+pub mod dict;
 pub mod fuzzer;
+pub mod minimize;
 pub mod mmap;
 pub mod mutator;
+pub mod output;
+pub mod parallel;
+pub mod queue;
+pub mod repro;
+pub mod rng;
+pub mod schedule;
 pub mod seed;
+pub mod stage;
+pub mod triage;
 pub mod ui;
 pub mod util;
 pub mod ver;
@@ -1,32 +1,256 @@
 use anyhow::Result;
 use fuzzer::{
-    cli::get_args,
-    fuzzer::Fuzzer,
-    mmap::{SHM_AUX_PATH, SHM_AUX_SIZE, SHM_COV_PATH, SHM_COV_SIZE, SHM_PATH, SHM_SIZE},
+    campaign::FuzzShot,
+    cli::{get_args, CminArgs, FuzzArgs, FuzzCommand, FuzzInput, ReproArgs, TminArgs},
+    dict::{Dictionary, DICT_FILE_EXT},
+    fuzzer::{Fuzzer, FuzzerConfig},
+    mmap::SHM_SIZE,
+    output::OutputDir,
+    parallel::{aggregate_shots, new_global_bitmap, worker_dir, WorkerSync},
+    queue::Queue,
+    repro::repro,
+    rng::random_seed,
     seed::SeedPool,
+    triage::report_excerpt,
     ui::run_ui,
 };
-use std::sync::mpsc;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+use uuid::Uuid;
 
-fn main() -> Result<()> {
-    let (pgm_path, seed_dir_path, input_typ) = get_args()?;
-    let init_seeds = SeedPool::new(&seed_dir_path);
-    let (tx, rx) = mpsc::channel();
+/// Loads the given dictionaries along with the one extracted from the program by instrumentation
+fn load_dict(args: &FuzzArgs) -> Result<Dictionary> {
+    let mut dict = Dictionary::default();
+    let auto_dict_path = format!("{}.{}", args.program_path, DICT_FILE_EXT);
+    if Path::new(&auto_dict_path).exists() {
+        dict.extend(Dictionary::from_file(&auto_dict_path)?);
+    }
+    for dict_path in &args.dict_paths {
+        dict.extend(Dictionary::from_file(dict_path)?);
+    }
+    Ok(dict)
+}
+
+fn new_fuzzer(
+    args: &FuzzArgs,
+    output: OutputDir,
+    dict: &Dictionary,
+    rng_seed: u64,
+    tx: mpsc::Sender<FuzzShot>,
+) -> Result<Fuzzer> {
+    let (init_seeds, queue) = if args.resume {
+        let (queue, queued_seeds) = Queue::resume(output.queue_dir())?;
+        (SeedPool::from_seeds(queued_seeds), queue)
+    } else {
+        (
+            SeedPool::new(args.seed_dir.as_ref().unwrap()),
+            Queue::new(output.queue_dir())?,
+        )
+    };
     let mut fuzzer = Fuzzer::new(
-        SHM_PATH,
-        SHM_SIZE,
-        SHM_AUX_PATH,
-        SHM_AUX_SIZE,
-        SHM_COV_PATH,
-        SHM_COV_SIZE,
-        init_seeds,
-        input_typ,
+        FuzzerConfig::new(init_seeds, queue, output, args.input_typ),
         tx,
     );
-    let handle = std::thread::spawn(move || {
+    fuzzer.set_dict(dict.clone());
+    fuzzer.set_rng_seed(rng_seed);
+    fuzzer.set_schedule(args.schedule);
+    fuzzer.set_persistent(args.persistent);
+    if let Some(timeout) = args.timeout {
+        fuzzer.set_timeout(timeout);
+    }
+    if args.resume {
+        fuzzer.resume()?;
+    }
+    Ok(fuzzer)
+}
+
+/// Fuzzer replaying inputs for the commands other than fuzzing, whose campaign files are
+/// kept in the given scratch directory
+fn new_replayer(
+    input_typ: FuzzInput,
+    timeout: Option<Duration>,
+    scratch_dir: &Path,
+) -> Result<Fuzzer> {
+    let output = OutputDir::new(scratch_dir)?;
+    let (tx, _) = mpsc::channel();
+    let queue = Queue::new(output.queue_dir())?;
+    let mut fuzzer = Fuzzer::new(
+        FuzzerConfig::new(SeedPool::from_seeds(vec![]), queue, output, input_typ),
+        tx,
+    );
+    if let Some(timeout) = timeout {
+        fuzzer.set_timeout(timeout);
+    }
+    Ok(fuzzer)
+}
+
+fn scratch_dir() -> PathBuf {
+    env::temp_dir().join(format!("fuzzer_{}", Uuid::new_v4().simple()))
+}
+
+fn cmin(args: CminArgs) -> Result<()> {
+    let mut input_paths = vec![];
+    for entry in fs::read_dir(&args.input_dir)? {
+        let path = entry?.path();
+        if path.is_file() {
+            input_paths.push(path);
+        }
+    }
+    input_paths.sort();
+    let inputs = input_paths
+        .iter()
+        .map(fs::read)
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    let scratch_dir = scratch_dir();
+    let kept = new_replayer(args.input_typ, args.timeout, &scratch_dir)
+        .and_then(|mut fuzzer| fuzzer.cmin(&args.program_path, &inputs));
+    fs::remove_dir_all(&scratch_dir)?;
+    let kept = kept?;
+
+    fs::create_dir_all(&args.output_dir)?;
+    for &idx in &kept {
+        let path = &input_paths[idx];
+        fs::copy(
+            path,
+            Path::new(&args.output_dir).join(path.file_name().unwrap()),
+        )?;
+    }
+    println!(
+        "cmin: kept {} of {} inputs in {}",
+        kept.len(),
+        inputs.len(),
+        args.output_dir
+    );
+    Ok(())
+}
+
+fn tmin(args: TminArgs) -> Result<()> {
+    let input = fs::read(&args.input_path)?;
+    let scratch_dir = scratch_dir();
+    let minimized = new_replayer(args.input_typ, args.timeout, &scratch_dir)
+        .and_then(|mut fuzzer| fuzzer.tmin(&args.program_path, &input));
+    fs::remove_dir_all(&scratch_dir)?;
+    let minimized = minimized?;
+    fs::write(&args.output_path, &minimized)?;
+    println!(
+        "tmin: shrank {} bytes to {} bytes into {}",
+        input.len(),
+        minimized.len(),
+        args.output_path
+    );
+    Ok(())
+}
+
+fn run_repro(args: ReproArgs) -> Result<()> {
+    let repro = repro(
+        &args.program_path,
+        &args.input_path,
+        args.input_typ,
+        args.timeout,
+        args.coverage,
+    )?;
+    // runtimes report bugs into stderr, which is shown after the status
+    let excerpt = report_excerpt(&repro.stderr);
+    let stderr = &repro.stderr[..repro.stderr.len() - excerpt.map_or(0, str::len)];
+    print!("{}", repro.stdout);
+    eprint!("{}", stderr);
+    let Some(status) = repro.status else {
+        println!("repro: timed out");
+        return Ok(());
+    };
+    match repro.crash_report() {
+        Some(report) => {
+            println!("repro: crashed ({}, status {})", report.bug_type, status);
+            for (i, frame) in report.frames.iter().enumerate() {
+                println!("  #{} {}", i, frame);
+            }
+        }
+        None => println!("repro: exited normally"),
+    }
+    if let Some(excerpt) = excerpt {
+        eprint!("{}", excerpt);
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    match get_args()? {
+        FuzzCommand::Fuzz(args) => fuzz(args),
+        FuzzCommand::Cmin(args) => cmin(args),
+        FuzzCommand::Tmin(args) => tmin(args),
+        FuzzCommand::Repro(args) => run_repro(args),
+    }
+}
+
+fn fuzz(args: FuzzArgs) -> Result<()> {
+    let dict = load_dict(&args)?;
+    let rng_seed = args.rng_seed.unwrap_or_else(random_seed);
+    let (tx, rx) = mpsc::channel();
+    let mut fuzzers = vec![];
+    if args.jobs == 1 {
+        let fuzzer = new_fuzzer(
+            &args,
+            OutputDir::new(&args.output_dir)?,
+            &dict,
+            rng_seed,
+            tx,
+        )?;
+        if args.resume && fuzzer.is_seed_empty() {
+            return Err(anyhow::anyhow!(
+                "no queued seed to resume in {}",
+                args.output_dir
+            ));
+        }
+        fuzzers.push(fuzzer);
+    } else {
+        // each worker has its own output directory under the one of the campaign
+        let global_bitmap = new_global_bitmap(SHM_SIZE);
+        let mut worker_rxs = vec![];
+        for worker_id in 0..args.jobs {
+            let (worker_tx, worker_rx) = mpsc::channel();
+            let output = OutputDir::new(worker_dir(&args.output_dir, worker_id))?;
+            // workers are seeded differently not to repeat the mutations of each other
+            let worker_rng_seed = rng_seed.wrapping_add(worker_id as u64);
+            let mut fuzzer = new_fuzzer(&args, output, &dict, worker_rng_seed, worker_tx)?;
+            fuzzer.set_sync(WorkerSync::new(
+                &args.output_dir,
+                worker_id,
+                args.jobs,
+                global_bitmap.clone(),
+            ));
+            fuzzers.push(fuzzer);
+            worker_rxs.push(worker_rx);
+        }
+        thread::spawn(move || aggregate_shots(worker_rxs, tx));
+    }
+
+    let handle = thread::spawn(move || {
         let _ = run_ui(rx);
     });
-    let _ = fuzzer.run(&pgm_path);
+    // workers are threads of this process, sharing the global bitmap in memory
+    let workers: Vec<_> = fuzzers
+        .into_iter()
+        .map(|mut fuzzer| {
+            let program_path = args.program_path.clone();
+            thread::spawn(move || fuzzer.run(&program_path).map(|_| ()))
+        })
+        .collect();
+    // every worker is joined before the first error is reported
+    let mut result = Ok(());
+    for (worker_id, worker) in workers.into_iter().enumerate() {
+        let worker_result = worker
+            .join()
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("worker {} panicked", worker_id)));
+        if result.is_ok() {
+            result = worker_result;
+        }
+    }
     handle.join().unwrap();
-    Ok(())
+    result
 }
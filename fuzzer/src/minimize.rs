@@ -0,0 +1,82 @@
+use anyhow::Result;
+use std::collections::BTreeSet;
+
+// byte the bytes of a minimized input are normalized to, as afl-tmin does
+const NORMALIZED_BYTE: u8 = b'0';
+
+/// Edges visited by a run, which are the indices of the edge bitmap
+pub type EdgeSet = BTreeSet<u64>;
+
+/// Picks the inputs covering the union of the edges of all of them, given their sizes and
+/// edge sets. The input covering the most edges not covered yet is picked first (the smaller
+/// one if tied), which keeps the set small.
+pub fn minimize_corpus(inputs: &[(usize, EdgeSet)]) -> Vec<usize> {
+    let mut uncovered: EdgeSet = inputs
+        .iter()
+        .flat_map(|(_, edges)| edges)
+        .copied()
+        .collect();
+    let mut picked = vec![];
+    while !uncovered.is_empty() {
+        let best = inputs
+            .iter()
+            .enumerate()
+            .map(|(idx, (len, edges))| (edges.intersection(&uncovered).count(), *len, idx))
+            .max_by(|(cover_a, len_a, idx_a), (cover_b, len_b, idx_b)| {
+                cover_a
+                    .cmp(cover_b)
+                    .then(len_b.cmp(len_a))
+                    .then(idx_b.cmp(idx_a))
+            });
+        let Some((_, _, idx)) = best else {
+            break;
+        };
+        for edge in &inputs[idx].1 {
+            uncovered.remove(edge);
+        }
+        picked.push(idx);
+    }
+    picked.sort_unstable();
+    picked
+}
+
+/// Shrinks an input as long as the oracle holds (e.g., the input exercises the same edges).
+/// Blocks of the input are removed from the half of it down to a byte until none can be,
+/// and then the remaining bytes are normalized where possible.
+pub fn minimize_input(
+    input: &[u8],
+    mut oracle: impl FnMut(&[u8]) -> Result<bool>,
+) -> Result<Vec<u8>> {
+    let mut input = input.to_vec();
+    loop {
+        let len = input.len();
+        let mut block = (len / 2).max(1);
+        while block > 0 && !input.is_empty() {
+            let mut pos = 0;
+            while pos < input.len() {
+                let end = (pos + block).min(input.len());
+                let candidate = [&input[..pos], &input[end..]].concat();
+                if oracle(&candidate)? {
+                    input = candidate;
+                } else {
+                    pos += block;
+                }
+            }
+            block /= 2;
+        }
+        if input.len() == len {
+            break;
+        }
+    }
+    for pos in 0..input.len() {
+        if input[pos] == NORMALIZED_BYTE {
+            continue;
+        }
+        let mut candidate = input.clone();
+        candidate[pos] = NORMALIZED_BYTE;
+        if oracle(&candidate)? {
+            input = candidate;
+        }
+    }
+    Ok(input)
+}
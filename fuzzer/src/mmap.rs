@@ -5,9 +5,13 @@ use std::fs::OpenOptions;
 pub const SHM_PATH: &str = "/tmp/fuzzer_shared_mem";
 pub const SHM_AUX_PATH: &str = "/tmp/fuzzer_shared_aux_mem";
 pub const SHM_COV_PATH: &str = "/tmp/fuzzer_shared_cov_mem";
+pub const SHM_CMP_PATH: &str = "/tmp/fuzzer_shared_cmp_mem";
+pub const SHM_INPUT_PATH: &str = "/tmp/fuzzer_shared_input_mem";
 pub const SHM_SIZE: usize = 1 << 16;
 pub const SHM_AUX_SIZE: usize = 1 << 20;
 pub const SHM_COV_SIZE: usize = 1 << 13;
+pub const SHM_CMP_SIZE: usize = 1 << 16;
+pub const SHM_INPUT_SIZE: usize = 1 << 20;
 
 pub struct SHM {
     mem: MmapMut,
@@ -60,3 +64,10 @@ impl SHM {
         self.mem[..].fill(0);
     }
 }
+
+impl Drop for SHM {
+    // shared memory files are named per campaign, so they are not reused by later campaigns
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.mem_path);
+    }
+}
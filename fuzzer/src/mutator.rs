@@ -1,3 +1,4 @@
+use crate::{dict::Dictionary, rng::FuzzRng};
 use rand::Rng;
 
 // 1. random insert
@@ -5,52 +6,86 @@ use rand::Rng;
 // e. random change
 // 4. random flip
 // 5. arithmetic mutation
+// 6. dictionary token insert
+// 7. dictionary token overwrite
+// 8. interesting value overwrite
+// (deterministic stages are in `stage.rs`)
 
 const KEEP_SEED_MIN_LEN: usize = 1;
+// havoc stacks 2 ~ 2^HAVOC_STACK_POW2 mutations
+const HAVOC_STACK_POW2: u32 = 7;
+pub const ARITH_MAX: i64 = 35;
+
+// values likely to hit boundary conditions (e.g., off-by-one and sign errors)
+pub const INTERESTING_8: &[i8] = &[-128, -1, 0, 1, 16, 32, 64, 100, 127];
+pub const INTERESTING_16: &[i16] = &[-32768, -129, 128, 255, 256, 512, 1000, 1024, 4096, 32767];
+pub const INTERESTING_32: &[i32] = &[
+    -2147483648,
+    -100663046,
+    -32769,
+    32768,
+    65535,
+    65536,
+    100663045,
+    2147483647,
+];
 
 #[derive(PartialEq, Eq, Debug)]
 pub enum MutateResult {
     EmptyInput,
     SeedTooShortToDelete,
     SeedTooShortToArithmeticMutate,
+    SeedTooShortToOverwriteToken,
+    EmptyDictionary,
+    NoSplicePoint,
     Done,
 }
 
-fn gen_new_byte() -> u8 {
-    rand::random::<u8>()
+fn gen_new_byte(rng: &mut FuzzRng) -> u8 {
+    rng.gen::<u8>()
 }
 
-fn gen_random_idx(len: usize) -> usize {
-    rand::random::<usize>() % len
+fn gen_random_idx(rng: &mut FuzzRng, len: usize) -> usize {
+    rng.gen_range(0..len)
 }
 
-type MutatorFn = fn(&mut Vec<u8>) -> MutateResult;
+type MutatorFn = fn(&mut Vec<u8>, &Dictionary, &mut FuzzRng) -> MutateResult;
 
-const MUTATORS: &[MutatorFn] = &[insert, change, delete, flip, arithmetic];
+const MUTATORS: &[MutatorFn] = &[
+    insert,
+    change,
+    delete,
+    flip,
+    arithmetic,
+    insert_token,
+    // adapted to `MutatorFn`, as they take a slice
+    |seed, dict, rng| overwrite_token(seed, dict, rng),
+    |seed, dict, rng| interest(seed, dict, rng),
+];
 
-fn insert(seed: &mut Vec<u8>) -> MutateResult {
+fn insert(seed: &mut Vec<u8>, _dict: &Dictionary, rng: &mut FuzzRng) -> MutateResult {
     if seed.is_empty() {
         return MutateResult::EmptyInput;
     }
-    let idx = gen_random_idx(seed.len());
-    seed.insert(idx, gen_new_byte());
+    let idx = gen_random_idx(rng, seed.len());
+    seed.insert(idx, gen_new_byte(rng));
     MutateResult::Done
 }
 
-fn change(seed: &mut Vec<u8>) -> MutateResult {
+fn change(seed: &mut Vec<u8>, _dict: &Dictionary, rng: &mut FuzzRng) -> MutateResult {
     if seed.is_empty() {
         return MutateResult::EmptyInput;
     }
-    let idx = gen_random_idx(seed.len());
-    seed[idx] = gen_new_byte();
+    let idx = gen_random_idx(rng, seed.len());
+    seed[idx] = gen_new_byte(rng);
     MutateResult::Done
 }
 
-fn delete(seed: &mut Vec<u8>) -> MutateResult {
+fn delete(seed: &mut Vec<u8>, _dict: &Dictionary, rng: &mut FuzzRng) -> MutateResult {
     if seed.is_empty() {
         return MutateResult::EmptyInput;
     }
-    let idx = gen_random_idx(seed.len());
+    let idx = gen_random_idx(rng, seed.len());
     if seed.len() > KEEP_SEED_MIN_LEN {
         seed.remove(idx);
         MutateResult::Done
@@ -59,20 +94,19 @@ fn delete(seed: &mut Vec<u8>) -> MutateResult {
     }
 }
 
-fn flip(seed: &mut Vec<u8>) -> MutateResult {
+fn flip(seed: &mut Vec<u8>, _dict: &Dictionary, rng: &mut FuzzRng) -> MutateResult {
     if seed.is_empty() {
         return MutateResult::EmptyInput;
     }
-    let idx = gen_random_idx(seed.len());
+    let idx = gen_random_idx(rng, seed.len());
     seed[idx] ^= 0xFF;
     MutateResult::Done
 }
 
-fn arithmetic(seed: &mut Vec<u8>) -> MutateResult {
+fn arithmetic(seed: &mut Vec<u8>, _dict: &Dictionary, rng: &mut FuzzRng) -> MutateResult {
     if seed.is_empty() {
         return MutateResult::EmptyInput;
     }
-    let mut rng = rand::thread_rng();
     let mutate_widths = [1, 2, 4];
     let width = match seed.len() {
         0..=1 => return MutateResult::SeedTooShortToArithmeticMutate,
@@ -82,7 +116,7 @@ fn arithmetic(seed: &mut Vec<u8>) -> MutateResult {
     };
     let idx = rng.gen_range(0..=seed.len() - width);
     // choose a random value from -35 ~ +35
-    let delta = rng.gen_range(-35i64..=35);
+    let delta = rng.gen_range(-ARITH_MAX..=ARITH_MAX);
     match width {
         1 => {
             let val = seed[idx];
@@ -106,24 +140,111 @@ fn arithmetic(seed: &mut Vec<u8>) -> MutateResult {
     MutateResult::Done
 }
 
-pub fn mutate(seed: &mut Vec<u8>) -> Vec<MutateResult> {
-    // 50% mutation example
-    // let n = if seed.len() <= 1 {
-    //     seed.len()
-    // } else {
-    //     gen_random_idx(seed.len() / 2) // mutate 50% for a given seed
-    // };
+fn pick_token<'a>(dict: &'a Dictionary, rng: &mut FuzzRng) -> Option<&'a [u8]> {
+    if dict.is_empty() {
+        return None;
+    }
+    Some(&dict.tokens()[gen_random_idx(rng, dict.len())])
+}
+
+fn insert_token(seed: &mut Vec<u8>, dict: &Dictionary, rng: &mut FuzzRng) -> MutateResult {
+    let Some(token) = pick_token(dict, rng) else {
+        return MutateResult::EmptyDictionary;
+    };
+    let idx = gen_random_idx(rng, seed.len() + 1);
+    seed.splice(idx..idx, token.iter().copied());
+    MutateResult::Done
+}
+
+fn overwrite_token(seed: &mut [u8], dict: &Dictionary, rng: &mut FuzzRng) -> MutateResult {
+    let Some(token) = pick_token(dict, rng) else {
+        return MutateResult::EmptyDictionary;
+    };
+    if seed.len() < token.len() {
+        return MutateResult::SeedTooShortToOverwriteToken;
+    }
+    let idx = gen_random_idx(rng, seed.len() - token.len() + 1);
+    seed[idx..idx + token.len()].copy_from_slice(token);
+    MutateResult::Done
+}
+
+/// Interesting values of a width in bytes, narrower ones sign-extended
+pub fn interesting_values(width: usize) -> Vec<i64> {
+    let values_8 = INTERESTING_8.iter().map(|v| *v as i64);
+    let values_16 = INTERESTING_16.iter().map(|v| *v as i64);
+    let values_32 = INTERESTING_32.iter().map(|v| *v as i64);
+    match width {
+        1 => values_8.collect(),
+        2 => values_8.chain(values_16).collect(),
+        4 => values_8.chain(values_16).chain(values_32).collect(),
+        _ => unreachable!(),
+    }
+}
+
+/// Writes the lower `width` bytes of a value at `idx`
+pub fn write_int(seed: &mut [u8], idx: usize, width: usize, value: u64, big_endian: bool) {
+    let bytes = if big_endian {
+        value.to_be_bytes()[8 - width..].to_vec()
+    } else {
+        value.to_le_bytes()[..width].to_vec()
+    };
+    seed[idx..idx + width].copy_from_slice(&bytes);
+}
+
+/// Reads `width` bytes at `idx` as an unsigned integer
+pub fn read_int(seed: &[u8], idx: usize, width: usize, big_endian: bool) -> u64 {
+    let mut bytes = [0; 8];
+    if big_endian {
+        bytes[8 - width..].copy_from_slice(&seed[idx..idx + width]);
+        u64::from_be_bytes(bytes)
+    } else {
+        bytes[..width].copy_from_slice(&seed[idx..idx + width]);
+        u64::from_le_bytes(bytes)
+    }
+}
+
+fn interest(seed: &mut [u8], _dict: &Dictionary, rng: &mut FuzzRng) -> MutateResult {
+    if seed.is_empty() {
+        return MutateResult::EmptyInput;
+    }
+    let widths: Vec<usize> = [1, 2, 4]
+        .into_iter()
+        .filter(|width| *width <= seed.len())
+        .collect();
+    let width = widths[rng.gen_range(0..widths.len())];
+    let values = interesting_values(width);
+    let value = values[rng.gen_range(0..values.len())];
+    let idx = rng.gen_range(0..=seed.len() - width);
+    write_int(seed, idx, width, value as u64, rng.gen());
+    MutateResult::Done
+}
 
-    // muate 1 ~ 10% for a given seed
-    let mut rng = rand::thread_rng();
-    let max = ((seed.len() as f32) * 0.2).ceil() as usize;
-    let max = max.max(1).min(seed.len());
-    let n = rng.gen_range(1..=max);
+/// Joins the head of a seed with the tail of another at a random point between the first and
+/// the last differing bytes
+pub fn splice(seed: &mut Vec<u8>, other: &[u8], rng: &mut FuzzRng) -> MutateResult {
+    let len = seed.len().min(other.len());
+    let differs = |idx: &usize| seed[*idx] != other[*idx];
+    let (Some(first_diff), Some(last_diff)) =
+        ((0..len).find(differs), (0..len).rev().find(differs))
+    else {
+        return MutateResult::NoSplicePoint;
+    };
+    if last_diff < 2 || first_diff == last_diff {
+        return MutateResult::NoSplicePoint;
+    }
+    let split_at = rng.gen_range(first_diff..last_diff);
+    seed.truncate(split_at);
+    seed.extend_from_slice(&other[split_at..]);
+    MutateResult::Done
+}
 
+/// Stacks random mutations (havoc)
+pub fn mutate(seed: &mut Vec<u8>, dict: &Dictionary, rng: &mut FuzzRng) -> Vec<MutateResult> {
+    let n = 1 << rng.gen_range(1..=HAVOC_STACK_POW2);
     let mut results = vec![];
-    for _ in 0..=n {
-        let idx = gen_random_idx(MUTATORS.len());
-        let result = MUTATORS[idx](seed);
+    for _ in 0..n {
+        let idx = gen_random_idx(rng, MUTATORS.len());
+        let result = MUTATORS[idx](seed, dict, rng);
         results.push(result);
     }
     results
@@ -0,0 +1,114 @@
+use crate::{queue::QUEUE_OUTPUT_DIR, seed::CRASH_OUTPUT_DIR};
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const DEFAULT_OUTPUT_DIR: &str = "out";
+pub const HANG_OUTPUT_DIR: &str = "hangs";
+const STATS_FILE: &str = "fuzzer_stats";
+// if fuzz input is `ProgramArgument`, the current seed is written into this file
+const INPUT_FILE: &str = ".cur_input";
+// the shared memory names of the campaign are kept, so that a resumed campaign can reuse them
+const CAMPAIGN_ID_FILE: &str = ".campaign_id";
+
+/// Layout of a campaign on disk. Every file a campaign creates is placed under its output
+/// directory (or suffixed with its campaign id for shared memory), so that campaigns running
+/// side by side do not collide.
+///
+/// ```text
+/// <output>/queue/         interesting seeds
+/// <output>/crashes/       minimized crashing inputs
+/// <output>/hangs/         inputs timed out
+/// <output>/fuzzer_stats   statistics of the campaign
+/// <output>/.cur_input     input file of the target
+/// ```
+pub struct OutputDir {
+    root: PathBuf,
+    campaign_id: String,
+}
+
+impl OutputDir {
+    pub fn new(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        for dir in [QUEUE_OUTPUT_DIR, CRASH_OUTPUT_DIR, HANG_OUTPUT_DIR] {
+            fs::create_dir_all(root.join(dir))?;
+        }
+        let campaign_id_path = root.join(CAMPAIGN_ID_FILE);
+        let campaign_id = match fs::read_to_string(&campaign_id_path) {
+            Ok(campaign_id) => campaign_id.trim().to_string(),
+            Err(_) => {
+                let campaign_id = Uuid::new_v4().simple().to_string();
+                fs::write(&campaign_id_path, &campaign_id)?;
+                campaign_id
+            }
+        };
+        Ok(Self { root, campaign_id })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn campaign_id(&self) -> &str {
+        &self.campaign_id
+    }
+
+    pub fn queue_dir(&self) -> PathBuf {
+        self.root.join(QUEUE_OUTPUT_DIR)
+    }
+
+    pub fn crash_dir(&self) -> PathBuf {
+        self.root.join(CRASH_OUTPUT_DIR)
+    }
+
+    pub fn hang_dir(&self) -> PathBuf {
+        self.root.join(HANG_OUTPUT_DIR)
+    }
+
+    pub fn stats_path(&self) -> PathBuf {
+        self.root.join(STATS_FILE)
+    }
+
+    pub fn input_path(&self) -> PathBuf {
+        self.root.join(INPUT_FILE)
+    }
+
+    /// Shared memory path of the campaign (e.g., `/tmp/fuzzer_shared_mem_<campaign id>`)
+    pub fn shm_path(&self, shm_path: &str) -> String {
+        format!("{}_{}", shm_path, self.campaign_id)
+    }
+
+    pub fn write_stats(&self, stats: &CampaignStats) -> Result<()> {
+        fs::write(self.stats_path(), stats.to_string())?;
+        Ok(())
+    }
+}
+
+/// Statistics written into `fuzzer_stats` as `key : value` lines
+#[derive(Debug, Default)]
+pub struct CampaignStats {
+    pub run_time: Duration,
+    pub execs_done: u64,
+    pub corpus_count: usize,
+    pub new_paths: usize,
+    pub saved_crashes: usize,
+    pub saved_hangs: usize,
+    // seed of the random number generator, to replay the campaign with `--seed`
+    pub rng_seed: u64,
+}
+
+impl std::fmt::Display for CampaignStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let execs_per_sec = self.execs_done as f64 / self.run_time.as_secs_f64().max(1e-3);
+        writeln!(f, "run_time          : {}", self.run_time.as_secs())?;
+        writeln!(f, "execs_done        : {}", self.execs_done)?;
+        writeln!(f, "execs_per_sec     : {:.2}", execs_per_sec)?;
+        writeln!(f, "corpus_count      : {}", self.corpus_count)?;
+        writeln!(f, "new_paths         : {}", self.new_paths)?;
+        writeln!(f, "saved_crashes     : {}", self.saved_crashes)?;
+        writeln!(f, "saved_hangs       : {}", self.saved_hangs)?;
+        writeln!(f, "rng_seed          : {}", self.rng_seed)
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{
+    campaign::{CrashInfo, FuzzShot, FuzzerMetadata, FuzzerSeed, HangInfo},
+    queue::{SeedMeta, QUEUE_OUTPUT_DIR},
+    seed::Seed,
+};
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    mpsc::{self, TryRecvError},
+    Arc, Mutex,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const WORKER_DIR_PREFIX: &str = "worker";
+const SYNC_INTERVAL: Duration = Duration::from_secs(5);
+const AGGREGATE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Edge bitmap of the whole campaign shared by the workers
+pub type GlobalBitmap = Arc<Mutex<Vec<u8>>>;
+
+pub fn new_global_bitmap(size: usize) -> GlobalBitmap {
+    Arc::new(Mutex::new(vec![0; size]))
+}
+
+/// Output directory of a worker (e.g., `<output>/worker3`)
+pub fn worker_dir(root: impl AsRef<Path>, worker_id: usize) -> PathBuf {
+    root.as_ref()
+        .join(format!("{}{}", WORKER_DIR_PREFIX, worker_id))
+}
+
+/// Shares the progress of a worker with the others. Seeds are exchanged through the queue
+/// directories of the workers, while edges are exchanged through the global bitmap.
+pub struct WorkerSync {
+    root: PathBuf,
+    worker_id: usize,
+    global_bitmap: GlobalBitmap,
+    // next queue id to import from each worker
+    synced: Vec<usize>,
+    last_synced: Option<Instant>,
+}
+
+impl WorkerSync {
+    pub fn new(
+        root: impl AsRef<Path>,
+        worker_id: usize,
+        workers: usize,
+        global_bitmap: GlobalBitmap,
+    ) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            worker_id,
+            global_bitmap,
+            synced: vec![0; workers],
+            last_synced: None,
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.last_synced
+            .is_none_or(|last_synced| last_synced.elapsed() >= SYNC_INTERVAL)
+    }
+
+    /// Merges the edge bitmap of the worker with the global one and returns the seeds queued
+    /// by the other workers since the last sync
+    pub fn sync(&mut self, bitmap: &mut [u8]) -> Result<Vec<Seed>> {
+        self.sync_bitmap(bitmap);
+        let mut seeds = vec![];
+        for worker_id in 0..self.synced.len() {
+            if worker_id != self.worker_id {
+                seeds.extend(self.import_seeds(worker_id)?);
+            }
+        }
+        self.last_synced = Some(Instant::now());
+        Ok(seeds)
+    }
+
+    // an edge found by any worker is no longer new to the others
+    fn sync_bitmap(&self, bitmap: &mut [u8]) {
+        let mut global_bitmap = self.global_bitmap.lock().unwrap();
+        for (global, local) in global_bitmap.iter_mut().zip(bitmap.iter_mut()) {
+            let merged = (*global).max(*local);
+            *global = merged;
+            *local = merged;
+        }
+    }
+
+    fn import_seeds(&mut self, worker_id: usize) -> Result<Vec<Seed>> {
+        let queue_dir = worker_dir(&self.root, worker_id).join(QUEUE_OUTPUT_DIR);
+        // the worker may not have created its queue yet
+        let Ok(entries) = fs::read_dir(&queue_dir) else {
+            return Ok(vec![]);
+        };
+        let mut seeds = vec![];
+        let mut next_id = self.synced[worker_id];
+        for entry in entries {
+            let path = entry?.path();
+            let Some(meta) = path
+                .file_name()
+                .and_then(|filename| filename.to_str())
+                .and_then(SeedMeta::from_filename)
+            else {
+                continue;
+            };
+            if meta.id < self.synced[worker_id] {
+                continue;
+            }
+            next_id = next_id.max(meta.id + 1);
+            // imported seeds are kept as they cover edges new to the campaign
+            seeds.push(Seed::new(fs::read(&path)?, 0));
+        }
+        self.synced[worker_id] = next_id;
+        Ok(seeds)
+    }
+}
+
+/// Merges the shots of the workers into the ones of a single campaign, so that the UI shows
+/// the campaign as a whole. It returns when every worker is terminated or the UI is closed.
+pub fn aggregate_shots(rxs: Vec<mpsc::Receiver<FuzzShot>>, tx: mpsc::Sender<FuzzShot>) {
+    let workers = rxs.len();
+    let mut metadata: Vec<Option<FuzzerMetadata>> = (0..workers).map(|_| None).collect();
+    let mut seed_infos: Vec<(usize, usize)> = vec![(0, 0); workers];
+    let mut crashes = vec![0; workers];
+    let mut hangs = vec![0; workers];
+    let mut alive = vec![true; workers];
+
+    loop {
+        let mut received = false;
+        for (worker_id, rx) in rxs.iter().enumerate() {
+            while alive[worker_id] {
+                let shot = match rx.try_recv() {
+                    Ok(shot) => shot,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        alive[worker_id] = false;
+                        break;
+                    }
+                };
+                received = true;
+                let aggregated = match shot {
+                    FuzzShot::ProgramOutput(output) => Some(FuzzShot::ProgramOutput(format!(
+                        "[#{}] {}",
+                        worker_id, output
+                    ))),
+                    FuzzShot::Coverage(cov) => Some(FuzzShot::Coverage(cov)),
+                    FuzzShot::Crash(crash) => {
+                        crashes[worker_id] = crash.crashes;
+                        Some(FuzzShot::Crash(CrashInfo::new(
+                            crashes.iter().sum(),
+                            crash.bug_type,
+                            crash.origin,
+                            crash.minimized,
+                        )))
+                    }
+                    FuzzShot::Hang(hang) => {
+                        hangs[worker_id] = hang.hangs;
+                        Some(FuzzShot::Hang(HangInfo::new(hangs.iter().sum(), hang.seed)))
+                    }
+                    FuzzShot::Metadata(md) => {
+                        let target_elpased_time = md.target_elpased_time;
+                        metadata[worker_id] = Some(md);
+                        Some(FuzzShot::Metadata(aggregate_metadata(
+                            &metadata,
+                            target_elpased_time,
+                        )))
+                    }
+                    FuzzShot::SeedInfo(seed_info) => {
+                        seed_infos[worker_id] = (seed_info.seeds, seed_info.new_paths);
+                        Some(FuzzShot::SeedInfo(FuzzerSeed::new(
+                            seed_infos.iter().map(|(seeds, _)| seeds).sum(),
+                            seed_info.cur_seed,
+                            seed_info.next_seed,
+                            seed_info.visit_edges,
+                            seed_infos.iter().map(|(_, new_paths)| new_paths).sum(),
+                        )))
+                    }
+                    FuzzShot::Terminated => {
+                        alive[worker_id] = false;
+                        None
+                    }
+                };
+                if let Some(aggregated) = aggregated {
+                    if tx.send(aggregated).is_err() {
+                        // workers are terminated as their receivers are dropped
+                        return;
+                    }
+                }
+            }
+        }
+        if alive.iter().all(|alive| !alive) {
+            let _ = tx.send(FuzzShot::Terminated);
+            return;
+        }
+        if !received {
+            thread::sleep(AGGREGATE_POLL_INTERVAL);
+        }
+    }
+}
+
+fn aggregate_metadata(
+    metadata: &[Option<FuzzerMetadata>],
+    target_elpased_time: Duration,
+) -> FuzzerMetadata {
+    let metadata = metadata.iter().flatten();
+    FuzzerMetadata::new(
+        metadata.clone().map(|md| md.fuzz_cnt).sum(),
+        metadata.clone().next().unwrap().fuzz_input_typ,
+        metadata.clone().map(|md| md.timeout).max().unwrap(),
+        target_elpased_time,
+        metadata.map(|md| md.total_elpased_time).max().unwrap(),
+    )
+}
@@ -0,0 +1,148 @@
+use crate::seed::Seed;
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const QUEUE_OUTPUT_DIR: &str = "queue";
+// the edge bitmap of the campaign is saved along with the queue to resume it
+const BITMAP_FILE: &str = ".bitmap";
+
+/// Metadata of a queued seed, which is encoded into its filename as AFL does
+/// (e.g., `id:000042,src:000012,op:insert+flip,edges:17,time:1234`)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeedMeta {
+    pub id: usize,
+    // the queued seed this one is mutated from (`None` for initial seeds)
+    pub parent: Option<usize>,
+    pub mutations: Vec<String>,
+    pub edges: u64,
+    // elapsed time of the campaign when the seed is found
+    pub found: Duration,
+}
+
+impl SeedMeta {
+    pub fn to_filename(&self) -> String {
+        let mut fields = vec![format!("id:{:06}", self.id)];
+        if let Some(parent) = self.parent {
+            fields.push(format!("src:{:06}", parent));
+        }
+        let op = if self.mutations.is_empty() {
+            "init".to_string()
+        } else {
+            self.mutations.join("+")
+        };
+        fields.push(format!("op:{}", op));
+        fields.push(format!("edges:{}", self.edges));
+        fields.push(format!("time:{}", self.found.as_millis()));
+        fields.join(",")
+    }
+
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        let mut meta = SeedMeta::default();
+        let mut has_id = false;
+        for field in filename.split(',') {
+            let (key, val) = field.split_once(':')?;
+            match key {
+                "id" => {
+                    meta.id = val.parse().ok()?;
+                    has_id = true;
+                }
+                "src" => meta.parent = Some(val.parse().ok()?),
+                "op" if val != "init" => {
+                    meta.mutations = val.split('+').map(|op| op.to_string()).collect()
+                }
+                "edges" => meta.edges = val.parse().ok()?,
+                "time" => meta.found = Duration::from_millis(val.parse().ok()?),
+                _ => {}
+            }
+        }
+        has_id.then_some(meta)
+    }
+}
+
+/// Interesting seeds of a campaign persisted on disk, so that a stopped campaign can be resumed
+pub struct Queue {
+    dir: PathBuf,
+    next_id: usize,
+    // campaign time already spent before resuming
+    elapsed_before: Duration,
+}
+
+impl Queue {
+    pub fn new(dir: impl AsRef<Path>) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            next_id: 0,
+            elapsed_before: Duration::default(),
+        })
+    }
+
+    /// Opens the queue of a previous campaign and returns it along with the queued seeds
+    /// (which may be none, e.g., for a worker that has not found a seed on its own)
+    pub fn resume(dir: impl AsRef<Path>) -> Result<(Self, Vec<Seed>)> {
+        let mut queue = Self::new(&dir)?;
+        let mut entries = vec![];
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let Some(meta) = path
+                .file_name()
+                .and_then(|filename| filename.to_str())
+                .and_then(SeedMeta::from_filename)
+            else {
+                continue;
+            };
+            entries.push((meta, path));
+        }
+        // seeds are restored in the order they are queued
+        entries.sort_by_key(|(meta, _)| meta.id);
+        let mut seeds = vec![];
+        for (meta, path) in entries {
+            queue.next_id = queue.next_id.max(meta.id + 1);
+            queue.elapsed_before = queue.elapsed_before.max(meta.found);
+            seeds.push(Seed::from_queue(fs::read(&path)?, meta.id));
+        }
+        Ok((queue, seeds))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn len(&self) -> usize {
+        self.next_id
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.next_id == 0
+    }
+
+    /// Writes a seed found at `elapsed` of the current run and assigns its queue id
+    pub fn save(&mut self, seed: &mut Seed, edges: u64, elapsed: Duration) -> Result<usize> {
+        let meta = SeedMeta {
+            id: self.next_id,
+            parent: seed.get_parent(),
+            mutations: seed.get_mutations().to_vec(),
+            edges,
+            found: self.elapsed_before + elapsed,
+        };
+        // written under a hidden name first, so that other workers never import a partial seed
+        let filename = meta.to_filename();
+        let tmp_path = self.dir.join(format!(".{}", filename));
+        fs::write(&tmp_path, seed.get_input())?;
+        fs::rename(&tmp_path, self.dir.join(filename))?;
+        seed.set_queue_id(meta.id);
+        self.next_id += 1;
+        Ok(meta.id)
+    }
+
+    pub fn save_bitmap(&self, bitmap: &[u8]) -> Result<()> {
+        fs::write(self.dir.join(BITMAP_FILE), bitmap)?;
+        Ok(())
+    }
+
+    pub fn load_bitmap(&self) -> Option<Vec<u8>> {
+        fs::read(self.dir.join(BITMAP_FILE)).ok()
+    }
+}
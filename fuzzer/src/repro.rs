@@ -0,0 +1,118 @@
+use crate::{cli::FuzzInput, triage::CrashReport};
+use anyhow::Result;
+use fuzzer_runtime::coverage::{PROCESS_EXIT_NORMAL, SIGNAL_STATUS_BASE};
+use std::{
+    io::{Read, Write},
+    os::unix::process::{CommandExt, ExitStatusExt},
+    process::{Child, Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Result of running the target once with an input
+#[derive(Debug)]
+pub struct Repro {
+    // as reported by the forkserver (e.g., `SIGNAL_STATUS_BASE + SIGABRT`), `None` if timed out
+    pub status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Repro {
+    pub fn is_crash(&self) -> bool {
+        self.status
+            .is_some_and(|status| status != PROCESS_EXIT_NORMAL as i32)
+    }
+
+    /// Classification of the crash, if the run crashed
+    pub fn crash_report(&self) -> Option<CrashReport> {
+        let status = self.status.filter(|_| self.is_crash())?;
+        Some(CrashReport::parse(
+            status,
+            &format!("{}\n{}", self.stdout, self.stderr),
+        ))
+    }
+}
+
+// the forkserver reports exit statuses in the same way
+fn to_status(exit_status: ExitStatus) -> i32 {
+    match (exit_status.code(), exit_status.signal()) {
+        (_, Some(signal)) => SIGNAL_STATUS_BASE + signal,
+        (Some(0), _) | (None, None) => PROCESS_EXIT_NORMAL as i32,
+        (Some(code), _) => code,
+    }
+}
+
+fn read_to_string(stream: Option<impl Read + Send + 'static>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut buf = vec![];
+        if let Some(mut stream) = stream {
+            let _ = stream.read_to_end(&mut buf);
+        }
+        String::from_utf8_lossy(&buf).into_owned()
+    })
+}
+
+fn wait(child: &mut Child, timeout: Option<Duration>) -> Result<Option<ExitStatus>> {
+    let started = Instant::now();
+    loop {
+        if let Some(exit_status) = child.try_wait()? {
+            return Ok(Some(exit_status));
+        }
+        if timeout.is_some_and(|timeout| started.elapsed() >= timeout) {
+            // children of the target holding its output are killed as well
+            unsafe { libc::kill(-(child.id() as i32), libc::SIGKILL) };
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(WAIT_POLL_INTERVAL);
+    }
+}
+
+/// Runs the target once with the input, without the forkserver. With `coverage`, the coverage
+/// runtime of the target reports the lines hit into its output.
+pub fn repro(
+    program_path: &str,
+    input_path: &str,
+    input_typ: FuzzInput,
+    timeout: Option<Duration>,
+    coverage: bool,
+) -> Result<Repro> {
+    let input = std::fs::read(input_path)?;
+    let mut cmd = Command::new(program_path);
+    cmd.env("COVERAGE_ENABLED", if coverage { "1" } else { "0" })
+        .env("COLOR", "0")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0);
+    if coverage {
+        cmd.env("COVERAGE_DEBUG", "1");
+    }
+    // without the fuzzer, a target fed through shared memory reads the stdin or the file
+    if input_typ != FuzzInput::Stdin {
+        cmd.arg(input_path);
+    }
+    if input_typ == FuzzInput::ProgramArgument {
+        cmd.stdin(Stdio::null());
+    } else {
+        cmd.stdin(Stdio::piped());
+    }
+
+    let mut child = cmd.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // the target may not read the whole input
+        thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        });
+    }
+    let stdout = read_to_string(child.stdout.take());
+    let stderr = read_to_string(child.stderr.take());
+    let status = wait(&mut child, timeout)?.map(to_status);
+    Ok(Repro {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
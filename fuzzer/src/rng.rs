@@ -0,0 +1,14 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+/// Random number generator of a campaign. Every random decision of the fuzzer (mutations and
+/// scheduling) is drawn from it, so that a campaign started with the same seed is replayed.
+pub type FuzzRng = StdRng;
+
+pub fn new_rng(seed: u64) -> FuzzRng {
+    StdRng::seed_from_u64(seed)
+}
+
+/// Seed of a campaign not given one
+pub fn random_seed() -> u64 {
+    rand::random()
+}
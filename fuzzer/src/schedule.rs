@@ -0,0 +1,88 @@
+use crate::cli::PowerSchedule;
+use std::time::Duration;
+
+// number of havoc mutants of a seed of the average execution time and size
+const HAVOC_CYCLES: f64 = 256.0;
+const HAVOC_MIN: u64 = 16;
+// bounds of the performance score, 100 being the one of an average seed
+const BASE_PERF_SCORE: f64 = 100.0;
+const MAX_PERF_SCORE: f64 = 1600.0;
+// bound of the factor of the schedules, growing exponentially with the times a seed is picked
+const MAX_FACTOR: f64 = 32.0;
+
+/// What the energy of a kept seed is assigned from
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SeedStats {
+    pub exec_time: Duration,
+    pub len: usize,
+    // times the seed is picked to be havoced
+    pub fuzz_level: u32,
+    // runs exercising the path of the seed
+    pub path_freq: u64,
+}
+
+/// Sums of the stats of the kept seeds, updated as seeds are kept and paths are exercised, so
+/// that they are averaged without a pass over the seeds
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SeedStatsSum {
+    pub n: u32,
+    pub exec_time: Duration,
+    pub len: usize,
+    pub path_freq: u64,
+}
+
+impl SeedStatsSum {
+    pub fn average(&self) -> SeedStats {
+        if self.n == 0 {
+            return SeedStats::default();
+        }
+        SeedStats {
+            exec_time: self.exec_time / self.n,
+            len: self.len / self.n as usize,
+            fuzz_level: 0,
+            path_freq: self.path_freq / self.n as u64,
+        }
+    }
+}
+
+// seeds cheaper than the average are fuzzed more, as AFL does
+fn cost_factor(cost: f64, avg: f64) -> f64 {
+    match cost {
+        _ if cost * 0.1 > avg => 0.1,
+        _ if cost * 0.25 > avg => 0.25,
+        _ if cost * 0.5 > avg => 0.5,
+        _ if cost * 0.75 > avg => 0.75,
+        _ if cost * 4.0 < avg => 3.0,
+        _ if cost * 3.0 < avg => 2.0,
+        _ if cost * 2.0 < avg => 1.5,
+        _ => 1.0,
+    }
+}
+
+fn fuzz_level_factor(fuzz_level: u32) -> f64 {
+    (2f64.powi(fuzz_level.min(16) as i32)).min(MAX_FACTOR)
+}
+
+/// Number of havoc mutants made from a seed when it is picked (AFLFast). Zero tells the seed
+/// is skipped this time.
+///
+/// - explore: the energy of AFL, from the execution time and the size of the seed
+/// - fast: grows with the times the seed is picked, and shrinks with the runs exercising
+///   its path, so that the seeds of rare paths are fuzzed more
+/// - coe: as fast, but the seeds of paths exercised more than the average are skipped
+/// - exploit: the maximum energy
+pub fn energy(schedule: PowerSchedule, stats: &SeedStats, avg: &SeedStats) -> u64 {
+    let mut perf_score = BASE_PERF_SCORE
+        * cost_factor(stats.exec_time.as_secs_f64(), avg.exec_time.as_secs_f64())
+        * cost_factor(stats.len as f64, avg.len as f64);
+    let path_freq = stats.path_freq.max(1) as f64;
+    let factor = match schedule {
+        PowerSchedule::Explore => 1.0,
+        PowerSchedule::Fast => fuzz_level_factor(stats.fuzz_level) / path_freq,
+        PowerSchedule::Coe if stats.path_freq > avg.path_freq => return 0,
+        PowerSchedule::Coe => fuzz_level_factor(stats.fuzz_level),
+        PowerSchedule::Exploit => MAX_FACTOR,
+    };
+    perf_score = (perf_score * factor.min(MAX_FACTOR)).min(MAX_PERF_SCORE);
+    ((HAVOC_CYCLES * perf_score / BASE_PERF_SCORE) as u64).max(HAVOC_MIN)
+}
@@ -1,27 +1,94 @@
-use std::hash::{Hash, Hasher};
-use std::{collections::BTreeSet, fs, path::PathBuf};
+use rand::Rng;
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::Path,
+    time::Duration,
+};
 
 use crate::{
+    cli::PowerSchedule,
+    dict::Dictionary,
     mutator::{self, MutateResult},
+    rng::FuzzRng,
+    schedule::{energy, SeedStats, SeedStatsSum},
     util::read_seed_dir,
 };
 
-const MAX_SEED_LEN: usize = 1000;
-const CRASH_OUTPUT_DIR: &str = "crashes";
+pub const CRASH_OUTPUT_DIR: &str = "crashes";
+pub const CRASH_FILE_EXT: &str = "crash";
+// output of the target captured when a crash is found
+pub const CRASH_OUTPUT_FILE_EXT: &str = "out";
+// keeping more seeds evicts the one of the most exercised path
+pub const MAX_KEPT_SEEDS: usize = 1 << 14;
+// draws of a splicing partner before giving up on finding one other than the seed
+const PICK_QUEUED_ATTEMPTS: usize = 8;
+const HAVOC_OP: &str = "havoc";
+const SPLICE_OP: &str = "splice";
 
 #[derive(Debug, Clone)]
 pub struct Seed {
     input: Vec<u8>,
     score: u64,
+    // id in the on-disk queue once the seed is found interesting
+    queue_id: Option<usize>,
+    // the closest queued ancestor and the mutations applied since then
+    parent: Option<usize>,
+    mutations: Vec<String>,
 }
 
 impl Seed {
     pub fn new(input: Vec<u8>, score: u64) -> Self {
-        Self { input, score }
+        Self {
+            input,
+            score,
+            queue_id: None,
+            parent: None,
+            mutations: vec![],
+        }
+    }
+
+    pub fn from_queue(input: Vec<u8>, queue_id: usize) -> Self {
+        Self {
+            queue_id: Some(queue_id),
+            ..Self::new(input, 0)
+        }
+    }
+
+    /// Mutant of a queued seed made by a deterministic stage
+    pub fn from_stage(input: Vec<u8>, parent: Option<usize>, stage: &str) -> Self {
+        Self {
+            parent,
+            mutations: vec![stage.to_string()],
+            ..Self::new(input, 0)
+        }
+    }
+
+    // a mutant of a queued seed starts a new lineage
+    fn start_lineage(&mut self) {
+        if let Some(queue_id) = self.queue_id.take() {
+            self.parent = Some(queue_id);
+            self.mutations.clear();
+        }
     }
 
-    pub fn mutate(&mut self) -> Vec<MutateResult> {
-        mutator::mutate(&mut self.input)
+    pub fn mutate(&mut self, dict: &Dictionary, rng: &mut FuzzRng) -> Vec<MutateResult> {
+        self.start_lineage();
+        let results = mutator::mutate(&mut self.input, dict, rng);
+        // stacked mutations are recorded as a single op to keep the queue filename short
+        if self.mutations.last().is_none_or(|op| op != HAVOC_OP) {
+            self.mutations.push(HAVOC_OP.to_string());
+        }
+        results
+    }
+
+    pub fn splice(&mut self, other: &Seed, rng: &mut FuzzRng) -> MutateResult {
+        self.start_lineage();
+        let result = mutator::splice(&mut self.input, other.get_input(), rng);
+        if result == MutateResult::Done {
+            self.mutations.push(SPLICE_OP.to_string());
+        }
+        result
     }
 
     pub fn get_input(&self) -> &[u8] {
@@ -40,29 +107,30 @@ impl Seed {
             .join("")
     }
 
-    pub fn to_file(&self, cnt: usize) {
-        fs::create_dir_all(CRASH_OUTPUT_DIR).unwrap();
-        let mut file_path = PathBuf::from(CRASH_OUTPUT_DIR);
-        file_path.push(format!("{}.crash", cnt));
+    pub fn to_file(&self, dir: &Path, name: &str) {
+        fs::create_dir_all(dir).unwrap();
+        let file_path = dir.join(format!("{}.{}", name, CRASH_FILE_EXT));
         fs::write(&file_path, self.input.clone()).unwrap();
     }
 
     pub fn set_score(&mut self, v: u64) {
         self.score = v;
     }
-}
 
-impl Ord for Seed {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.score
-            .cmp(&other.score)
-            .then_with(|| self.input.cmp(&other.input))
+    pub fn get_queue_id(&self) -> Option<usize> {
+        self.queue_id
+    }
+
+    pub fn set_queue_id(&mut self, queue_id: usize) {
+        self.queue_id = Some(queue_id);
+    }
+
+    pub fn get_parent(&self) -> Option<usize> {
+        self.parent
     }
-}
 
-impl PartialOrd for Seed {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+    pub fn get_mutations(&self) -> &[String] {
+        &self.mutations
     }
 }
 
@@ -74,58 +142,168 @@ impl PartialEq for Seed {
 
 impl Eq for Seed {}
 
-impl Hash for Seed {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.input.hash(state);
-        self.score.hash(state);
-    }
+/// Seed kept in the pool, which is havoced as many times as its energy whenever picked
+struct PoolEntry {
+    seed: Seed,
+    path_hash: u64,
+    stats: SeedStats,
+}
+
+/// Runs exercising a path and the kept seeds of the path
+#[derive(Default)]
+struct PathStats {
+    runs: u64,
+    kept: u64,
 }
 
+/// Seeds to be fuzzed. Seeds given to the pool (e.g., initial and synced ones) are run as
+/// they are first, and those kept afterwards are picked in turn with the energy assigned by
+/// the power schedule.
 pub struct SeedPool {
-    seeds: BTreeSet<Seed>,
+    pending: VecDeque<Seed>,
+    entries: Vec<PoolEntry>,
+    paths: HashMap<u64, PathStats>,
+    // of the stats of the entries, the path frequency being the runs of their paths
+    stats_sum: SeedStatsSum,
+    schedule: PowerSchedule,
+    // entry being havoced, its remaining energy and the entry picked next
+    cur: usize,
+    energy: u64,
+    next: usize,
 }
 
 impl SeedPool {
     pub fn new(seed_dir: &str) -> Self {
-        let mut btree = BTreeSet::new();
         let init_seeds = read_seed_dir(seed_dir).unwrap();
-        for init_seed in init_seeds {
-            btree.insert(Seed::new(init_seed, 0));
+        Self::from_seeds(
+            init_seeds
+                .into_iter()
+                .map(|init_seed| Seed::new(init_seed, 0))
+                .collect(),
+        )
+    }
+
+    pub fn from_seeds(seeds: Vec<Seed>) -> Self {
+        Self {
+            pending: seeds.into(),
+            entries: vec![],
+            paths: HashMap::new(),
+            stats_sum: SeedStatsSum::default(),
+            schedule: PowerSchedule::Fast,
+            cur: 0,
+            energy: 0,
+            next: 0,
         }
-        Self { seeds: btree }
     }
 
+    pub fn set_schedule(&mut self, schedule: PowerSchedule) {
+        self.schedule = schedule;
+    }
+
+    /// Adds a seed to be run as it is
     pub fn add_seed(&mut self, seed: Seed) {
-        if self.seeds.len() > MAX_SEED_LEN {
-            if let Some(min_seed) = self.get_min_score_seed() {
-                self.seeds.remove(&min_seed);
-            }
+        self.pending.push_back(seed);
+    }
+
+    pub fn pop_seed(&mut self) -> Option<Seed> {
+        self.pending.pop_front()
+    }
+
+    /// Keeps a seed to be havoced
+    pub fn keep(&mut self, seed: Seed, exec_time: Duration, path_hash: u64) {
+        let stats = SeedStats {
+            exec_time,
+            len: seed.get_input().len(),
+            ..Default::default()
+        };
+        let path = self.paths.entry(path_hash).or_default();
+        path.kept += 1;
+        self.stats_sum.n += 1;
+        self.stats_sum.exec_time += stats.exec_time;
+        self.stats_sum.len += stats.len;
+        self.stats_sum.path_freq += path.runs;
+        self.entries.push(PoolEntry {
+            seed,
+            path_hash,
+            stats,
+        });
+        if self.entries.len() > MAX_KEPT_SEEDS {
+            self.evict();
         }
-        self.seeds.insert(seed);
     }
 
-    fn get_min_score_seed(&self) -> Option<Seed> {
-        self.seeds.iter().next().cloned()
+    /// Drops the entry of the most exercised path, the most picked one among them
+    fn evict(&mut self) {
+        let paths = &self.paths;
+        let Some(idx) = (0..self.entries.len()).max_by_key(|idx| {
+            let entry = &self.entries[*idx];
+            (paths[&entry.path_hash].runs, entry.stats.fuzz_level)
+        }) else {
+            return;
+        };
+        let entry = self.entries.swap_remove(idx);
+        let path = self.paths.get_mut(&entry.path_hash).unwrap();
+        path.kept -= 1;
+        self.stats_sum.n -= 1;
+        self.stats_sum.exec_time -= entry.stats.exec_time;
+        self.stats_sum.len -= entry.stats.len;
+        self.stats_sum.path_freq -= path.runs;
+        // the entry being havoced is gone or moved, so the next one is picked
+        if idx == self.cur || self.cur >= self.entries.len() {
+            self.energy = 0;
+        }
     }
 
-    fn get_max_score_seed(&self) -> Option<Seed> {
-        self.seeds.iter().next_back().cloned()
+    /// Counts a run exercising the path
+    pub fn record_path(&mut self, path_hash: u64) {
+        let path = self.paths.entry(path_hash).or_default();
+        path.runs += 1;
+        self.stats_sum.path_freq += path.kept;
     }
 
-    pub fn pop_seed(&mut self) -> Option<Seed> {
-        if let Some(seed) = self.get_max_score_seed() {
-            self.seeds.remove(&seed);
-            Some(seed)
-        } else {
-            None
+    /// Returns the kept seed to be havoced next, moving on to the next one once the energy of
+    /// the current one is spent
+    pub fn next_entry(&mut self) -> Option<&Seed> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        // the schedule may skip seeds, but not all of them
+        for _ in 0..self.entries.len() {
+            if self.energy > 0 {
+                break;
+            }
+            self.cur = self.next % self.entries.len();
+            self.next = self.cur + 1;
+            self.energy = self.assign_energy(self.cur);
+        }
+        self.energy = self.energy.saturating_sub(1);
+        Some(&self.entries[self.cur].seed)
+    }
+
+    fn assign_energy(&mut self, idx: usize) -> u64 {
+        let avg = self.stats_sum.average();
+        let entry = &mut self.entries[idx];
+        entry.stats.path_freq = self.paths.get(&entry.path_hash).map_or(0, |path| path.runs);
+        let energy = energy(self.schedule, &entry.stats, &avg);
+        entry.stats.fuzz_level += 1;
+        energy
+    }
+
+    /// Picks a random kept seed other than the given one (e.g., as a splicing partner)
+    pub fn pick_queued(&self, seed: &Seed, rng: &mut FuzzRng) -> Option<&Seed> {
+        if self.entries.is_empty() {
+            return None;
         }
+        (0..PICK_QUEUED_ATTEMPTS)
+            .map(|_| &self.entries[rng.gen_range(0..self.entries.len())].seed)
+            .find(|other| other.input != seed.input)
     }
 
     pub fn is_empty(&self) -> bool {
-        self.seeds.len() == 0
+        self.pending.is_empty() && self.entries.is_empty()
     }
 
     pub fn len(&self) -> usize {
-        self.seeds.len()
+        self.pending.len() + self.entries.len()
     }
 }
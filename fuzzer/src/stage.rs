@@ -0,0 +1,196 @@
+use crate::{
+    mutator::{interesting_values, read_int, write_int, ARITH_MAX},
+    seed::Seed,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StageKind {
+    FlipBits(usize),  // walking flip of the number of bits
+    FlipBytes(usize), // walking flip of the number of bytes
+    Arith(usize),     // ±1 ~ ARITH_MAX on integers of the width in bytes
+    Interest(usize),  // interesting values on integers of the width in bytes
+}
+
+use StageKind::*;
+
+// named to record how a queued seed is derived from its parent
+const STAGES: &[(&str, StageKind)] = &[
+    ("flip1", FlipBits(1)),
+    ("flip2", FlipBits(2)),
+    ("flip4", FlipBits(4)),
+    ("flip8", FlipBytes(1)),
+    ("flip16", FlipBytes(2)),
+    ("flip32", FlipBytes(4)),
+    ("arith8", Arith(1)),
+    ("arith16", Arith(2)),
+    ("arith32", Arith(4)),
+    ("interest8", Interest(1)),
+    ("interest16", Interest(2)),
+    ("interest32", Interest(4)),
+];
+
+// the effector map is built by the walking byte flip
+const EFFECTOR_STAGE: StageKind = FlipBytes(1);
+// input-to-state mutants replacing operands of comparisons, run before the other stages
+const CMP_LOG_STAGE: &str = "cmplog";
+
+/// Deterministic mutations of a queued seed, run once before the seed is havoced. Each
+/// mutant is made lazily, so that the result of the previous one can be reported back.
+///
+/// Bytes whose flip does not change the path of the seed (e.g., bytes of a checksum-free
+/// payload) are recorded in the effector map and skipped by the stages following
+/// the walking byte flip.
+pub struct DeterministicStages {
+    input: Vec<u8>,
+    parent: Option<usize>,
+    // path hash of the seed itself
+    base_path: u64,
+    // whether flipping each byte changes the path
+    eff_map: Vec<bool>,
+    stage: usize,
+    step: usize,
+    // (stage, byte) of the last mutant
+    last: Option<(usize, usize)>,
+    cmp_mutants: std::vec::IntoIter<Vec<u8>>,
+}
+
+impl DeterministicStages {
+    pub fn new(seed: &Seed, base_path: u64, cmp_mutants: Vec<Vec<u8>>) -> Self {
+        let input = seed.get_input().to_vec();
+        Self {
+            eff_map: vec![true; input.len()],
+            input,
+            parent: seed.get_queue_id(),
+            base_path,
+            stage: 0,
+            step: 0,
+            last: None,
+            cmp_mutants: cmp_mutants.into_iter(),
+        }
+    }
+
+    /// Returns the next mutant, or `None` if all the stages are done
+    pub fn next_mutant(&mut self) -> Option<Seed> {
+        if let Some(mutant) = self.cmp_mutants.next() {
+            return Some(Seed::from_stage(mutant, self.parent, CMP_LOG_STAGE));
+        }
+        while let Some((name, kind)) = STAGES.get(self.stage) {
+            if self.step >= self.steps(*kind) {
+                self.stage += 1;
+                self.step = 0;
+                continue;
+            }
+            let step = self.step;
+            self.step += 1;
+            if let Some((mutant, idx)) = self.make_mutant(*kind, step) {
+                // mutants same as the seed (e.g., an interesting value already there) are
+                // not worth running
+                if mutant != self.input {
+                    self.last = Some((self.stage, idx));
+                    return Some(Seed::from_stage(mutant, self.parent, name));
+                }
+            }
+        }
+        None
+    }
+
+    /// Reports the path hash of the run of the last mutant
+    pub fn report(&mut self, path_hash: u64) {
+        if let Some((stage, idx)) = self.last.take() {
+            if STAGES[stage].1 == EFFECTOR_STAGE && path_hash == self.base_path {
+                self.eff_map[idx] = false;
+            }
+        }
+    }
+
+    fn steps(&self, kind: StageKind) -> usize {
+        let len = self.input.len();
+        match kind {
+            FlipBits(bits) => (len * 8).saturating_sub(bits - 1),
+            FlipBytes(width) => (len + 1).saturating_sub(width),
+            Arith(width) => (len + 1).saturating_sub(width) * Self::arith_steps(width),
+            Interest(width) => (len + 1).saturating_sub(width) * Self::interest_steps(width),
+        }
+    }
+
+    // single bytes have no endianness
+    fn endians(width: usize) -> usize {
+        if width == 1 {
+            1
+        } else {
+            2
+        }
+    }
+
+    fn arith_steps(width: usize) -> usize {
+        ARITH_MAX as usize * 2 * Self::endians(width)
+    }
+
+    fn interest_steps(width: usize) -> usize {
+        interesting_values(width).len() * Self::endians(width)
+    }
+
+    fn is_effective(&self, idx: usize, width: usize) -> bool {
+        let eff_map_built = STAGES[..self.stage]
+            .iter()
+            .any(|(_, kind)| *kind == EFFECTOR_STAGE);
+        !eff_map_built || self.eff_map[idx..idx + width].iter().any(|eff| *eff)
+    }
+
+    // returns the mutant of a step and the first byte it touches
+    fn make_mutant(&self, kind: StageKind, step: usize) -> Option<(Vec<u8>, usize)> {
+        let mut mutant = self.input.clone();
+        let idx = match kind {
+            FlipBits(bits) => {
+                for bit in step..step + bits {
+                    mutant[bit / 8] ^= 0x80 >> (bit % 8);
+                }
+                step / 8
+            }
+            FlipBytes(width) => {
+                if !self.is_effective(step, width) {
+                    return None;
+                }
+                for byte in &mut mutant[step..step + width] {
+                    *byte ^= 0xFF;
+                }
+                step
+            }
+            Arith(width) => {
+                let (idx, step) = (
+                    step / Self::arith_steps(width),
+                    step % Self::arith_steps(width),
+                );
+                if !self.is_effective(idx, width) {
+                    return None;
+                }
+                let big_endian = step >= ARITH_MAX as usize * 2;
+                let step = step % (ARITH_MAX as usize * 2);
+                let delta = (step / 2 + 1) as u64;
+                let value = read_int(&mutant, idx, width, big_endian);
+                let value = if step % 2 == 1 {
+                    value.wrapping_sub(delta)
+                } else {
+                    value.wrapping_add(delta)
+                };
+                write_int(&mut mutant, idx, width, value, big_endian);
+                idx
+            }
+            Interest(width) => {
+                let (idx, step) = (
+                    step / Self::interest_steps(width),
+                    step % Self::interest_steps(width),
+                );
+                if !self.is_effective(idx, width) {
+                    return None;
+                }
+                let values = interesting_values(width);
+                let big_endian = step >= values.len();
+                let value = values[step % values.len()];
+                write_int(&mut mutant, idx, width, value as u64, big_endian);
+                idx
+            }
+        };
+        Some((mutant, idx))
+    }
+}
@@ -0,0 +1,152 @@
+use fuzzer_runtime::coverage::SIGNAL_STATUS_BASE;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+// crashes are bucketed by the frames closest to the bug
+const MAX_BUCKET_FRAMES: usize = 5;
+
+// frames of runtimes and of the standard libraries do not tell bugs apart, which are matched
+// by prefix or as a whole not to ignore user frames (e.g., `parse_start` or `print_backtrace`)
+const IGNORED_FRAME_PREFIXES: &[&str] = &[
+    "std::",
+    "core::",
+    "alloc::",
+    "backtrace::",
+    "__asan_",
+    "__msan_",
+    "__race_",
+    "__libc_start",
+];
+const IGNORED_FRAMES: &[&str] = &["_start", "<unknown>"];
+// crates of the runtimes (e.g., `asan_runtime::asan_runtime::report_asan_violated`)
+const RUNTIME_CRATE_SUFFIX: &str = "_runtime";
+
+// (text printed by a runtime, bug type)
+const REPORT_PATTERNS: &[(&str, &str)] = &[
+    (
+        "[ASAN] invalid memory access detected",
+        "asan-invalid-access",
+    ),
+    (
+        "[ASAN] alloc-dealloc mismatch",
+        "asan-alloc-dealloc-mismatch",
+    ),
+    ("[ASAN] memory leaks detected", "asan-leak"),
+    ("[MSAN] use of uninitialized value", "msan-uninit-use"),
+    ("Data race detected", "data-race"),
+];
+
+/// Classification of a crashing run by its exit status and by the report a runtime prints
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashReport {
+    pub bug_type: String,
+    // innermost first
+    pub frames: Vec<String>,
+}
+
+impl CrashReport {
+    pub fn parse(status: i32, output: &str) -> Self {
+        let bug_type = REPORT_PATTERNS
+            .iter()
+            .find(|(pattern, _)| output.contains(pattern))
+            .map_or_else(
+                || status_to_bug_type(status),
+                |(_, bug_type)| bug_type.to_string(),
+            );
+        let frames = output
+            .lines()
+            .filter_map(parse_frame)
+            .filter(|frame| !is_ignored_frame(frame))
+            .take(MAX_BUCKET_FRAMES)
+            .collect();
+        Self { bug_type, frames }
+    }
+
+    /// Crashes of the same bug type with the same top frames are the same bug
+    pub fn bucket(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.bug_type.hash(&mut hasher);
+        self.frames.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Name of the files of the bucket (e.g., `asan-invalid-access-3f2a...`)
+    pub fn bucket_name(&self) -> String {
+        format!("{}-{:016x}", self.bug_type, self.bucket())
+    }
+}
+
+/// Returns the report a runtime printed in the output, from its first line to the end
+pub fn report_excerpt(output: &str) -> Option<&str> {
+    let pos = REPORT_PATTERNS
+        .iter()
+        .filter_map(|(pattern, _)| output.find(pattern))
+        .min()?;
+    let line_start = output[..pos].rfind('\n').map_or(0, |newline| newline + 1);
+    Some(&output[line_start..])
+}
+
+/// Returns the bucket encoded in a file name made by `CrashReport::bucket_name`
+pub fn bucket_from_name(name: &str) -> Option<u64> {
+    let (_, bucket) = name.rsplit_once('-')?;
+    u64::from_str_radix(bucket, 16).ok()
+}
+
+fn status_to_bug_type(status: i32) -> String {
+    let signal = status - SIGNAL_STATUS_BASE;
+    let signal_name = match signal {
+        libc::SIGSEGV => Some("SIGSEGV"),
+        libc::SIGABRT => Some("SIGABRT"),
+        libc::SIGBUS => Some("SIGBUS"),
+        libc::SIGFPE => Some("SIGFPE"),
+        libc::SIGILL => Some("SIGILL"),
+        libc::SIGTRAP => Some("SIGTRAP"),
+        _ => None,
+    };
+    match signal_name {
+        Some(signal_name) => signal_name.to_string(),
+        None if signal > 0 => format!("signal-{}", signal),
+        None => format!("exit-{}", status),
+    }
+}
+
+/// Extracts a location identifying a frame from a line of a report, ignoring addresses which
+/// differ across runs
+fn is_ignored_frame(frame: &str) -> bool {
+    // a trait method of a Rust backtrace (e.g., `<alloc::vec::Vec<T> as core::ops::Drop>::drop`)
+    let path = frame.strip_prefix('<').unwrap_or(frame);
+    IGNORED_FRAMES.contains(&frame)
+        || IGNORED_FRAME_PREFIXES
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+        || path
+            .split_once("::")
+            .is_some_and(|(krate, _)| krate.ends_with(RUNTIME_CRATE_SUFFIX))
+}
+
+fn parse_frame(line: &str) -> Option<String> {
+    let line = line.trim();
+    // location of the report (e.g., `... detected at main.c:3:5 in main: 0x1234`)
+    if let Some((_, src_loc)) = line.split_once(" detected at ") {
+        let src_loc = src_loc.split(": 0x").next().unwrap();
+        return Some(src_loc.to_string());
+    }
+    // location of a data race
+    if let Some((_, src_loc)) = line.split_once("variable used line = ") {
+        return Some(src_loc.to_string());
+    }
+    // frame of a leak report (e.g., `#0 0x1234 in foo (a.out+0x12)`)
+    if let Some(frame) = line.strip_prefix('#') {
+        let (_, frame) = frame.split_once(' ')?;
+        let frame = match frame.split_once(" in ") {
+            Some((_, func)) => func.split(" (").next().unwrap(),
+            None => frame.trim_start_matches(|c: char| c != ' ').trim(),
+        };
+        return Some(frame.to_string());
+    }
+    // frame of a Rust backtrace (e.g., `3: foo`)
+    let (idx, symbol) = line.split_once(": ")?;
+    if idx.is_empty() || !idx.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(symbol.to_string())
+}
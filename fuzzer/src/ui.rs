@@ -57,6 +57,9 @@ fn run_app<B: ratatui::backend::Backend>(
                 FuzzShot::Crash(crash) => {
                     fuzz_result.set_crash(crash);
                 }
+                FuzzShot::Hang(hang) => {
+                    fuzz_result.set_hang(hang);
+                }
                 FuzzShot::Metadata(md) => {
                     fuzz_result.set_metadata(md);
                 }
@@ -192,6 +195,13 @@ fn render_ui(frame: &mut Frame, fuzz_result: &mut FuzzingCampaign) {
                     Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
                 ),
             ]),
+            Line::from(vec![
+                Span::raw("         Bug type: "),
+                Span::styled(
+                    &fuzz_result.crash.bug_type,
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                ),
+            ]),
             Line::from(vec![
                 Span::raw("           Origin: "),
                 Span::styled(
@@ -223,6 +233,15 @@ fn render_ui(frame: &mut Frame, fuzz_result: &mut FuzzingCampaign) {
                     Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
                 ),
             ]),
+            Line::from(vec![
+                Span::raw("            Hangs: "),
+                Span::styled(
+                    format!("{}", fuzz_result.hang.hangs),
+                    Style::default()
+                        .fg(Color::LightRed)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            ]),
         ]));
         frame.render_widget(&b_data, compartment_b.inner(ab_compartments[1]));
     }
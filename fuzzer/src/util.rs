@@ -1,21 +1,26 @@
 use std::fs;
 use std::fs::File;
 use std::io::{self, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-pub fn read_seed_dir(seed_dir: &str) -> io::Result<Vec<Vec<u8>>> {
-    fs::read_dir(seed_dir)?
-        .filter_map(|entry| entry.ok().filter(|e| e.path().is_file()))
-        .map(|entry| {
+/// Reads the seeds in the order of their paths, which does not depend on the file system
+pub fn read_seed_dir(seed_dir: impl AsRef<Path>) -> io::Result<Vec<Vec<u8>>> {
+    let mut paths: Vec<_> = fs::read_dir(seed_dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()).filter(|path| path.is_file()))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| {
             let mut content = Vec::new();
-            std::fs::File::open(entry.path())?.read_to_end(&mut content)?;
+            std::fs::File::open(path)?.read_to_end(&mut content)?;
             Ok(content)
         })
         .collect()
 }
 
-pub fn write_seed(filename: &str, seed: &[u8]) -> io::Result<PathBuf> {
-    let path = PathBuf::from(filename);
+pub fn write_seed(filename: impl AsRef<Path>, seed: &[u8]) -> io::Result<PathBuf> {
+    let path = filename.as_ref().to_path_buf();
     let mut file = File::create(&path)?;
     file.write_all(seed)?;
     Ok(path)
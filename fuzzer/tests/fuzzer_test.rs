@@ -1,74 +1,81 @@
 use defer_lite::defer;
-use fuzzer::fuzzer::Fuzzer;
-use fuzzer::{fuzzer::HostSend, mmap::SHM_COV_SIZE};
+use fuzzer::fuzzer::HostSend;
+use fuzzer::fuzzer::{Fuzzer, FuzzerConfig};
 use fuzzer::{
-    mmap::{SHM_AUX_SIZE, SHM_SIZE},
-    seed::SeedPool,
+    cli::{FuzzInput, PowerSchedule},
+    cmplog::{input_to_state, start_cmp_log, stop_cmp_log},
+    dict::Dictionary,
+    minimize::{minimize_corpus, minimize_input, EdgeSet},
+    mutator::{splice, MutateResult},
+    output::OutputDir,
+    parallel::{new_global_bitmap, worker_dir, WorkerSync},
+    queue::{Queue, SeedMeta},
+    repro::repro,
+    rng::new_rng,
+    schedule::{energy, SeedStats},
+    seed::{Seed, SeedPool, MAX_KEPT_SEEDS},
+    stage::DeterministicStages,
+    triage::{bucket_from_name, report_excerpt, CrashReport},
+};
+use fuzzer_runtime::{
+    cmplog::{log_cmp, CmpKind},
+    coverage::EdgeCoverage,
+    input::{read_input, write_input},
+    runtime::__fuzzer_trace_edge,
 };
-use fuzzer_runtime::runtime::__fuzzer_trace_edge;
 use serial_test::serial;
 use std::env;
 use std::fs;
+use std::process::{Command, Stdio};
 use std::sync::mpsc;
 use std::thread;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
-fn create_test_dir(dirname: &str) {
-    fs::create_dir(dirname).unwrap();
-}
-
-fn remove_test_dir(dirname: &str) {
-    fs::remove_dir(dirname).unwrap();
-}
-
 fn gen_filename() -> String {
     format!("/tmp/{}", Uuid::new_v4())
 }
 
-fn set_env(
-    shm_path: &str,
-    shm_size: usize,
-    shm_aux_path: &str,
-    shm_aux_size: usize,
-    forkserver_host: Option<i32>,
-    forkserver_runtime: Option<i32>,
-) {
-    env::set_var("SHM_ID", shm_path);
-    env::set_var("SHM_AUX_ID", shm_aux_path);
-    env::set_var("SHM_SIZE", format!("{}", shm_size));
-    env::set_var("SHM_AUX_SIZE", format!("{}", shm_aux_size));
-    if let Some(forkserver_host) = forkserver_host {
-        env::set_var("FORK_SERVER_HOST", format!("{}", forkserver_host));
-    }
-    if let Some(forkserver_runtime) = forkserver_runtime {
-        env::set_var("FORK_SERVER_RUNTIME", format!("{}", forkserver_runtime));
+/// Removes the output directory of a test fuzzer once the test ends
+struct OutputGuard(String);
+
+impl Drop for OutputGuard {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
     }
 }
 
+/// Fuzzer feeding stdin with no seed, whose shared memories and fork server are the ones of
+/// the runtime linked into this test
+fn new_test_fuzzer() -> (Fuzzer, OutputGuard) {
+    let output_dirname = gen_filename();
+    let output = OutputDir::new(&output_dirname).unwrap();
+    let queue = Queue::new(output.queue_dir()).unwrap();
+    let config = FuzzerConfig::new(
+        SeedPool::from_seeds(vec![]),
+        queue,
+        output,
+        FuzzInput::Stdin,
+    );
+    env::set_var("SHM_ID", &config.shm_path);
+    env::set_var("SHM_AUX_ID", &config.shm_aux_path);
+    env::set_var("SHM_SIZE", format!("{}", config.shm_size));
+    env::set_var("SHM_AUX_SIZE", format!("{}", config.shm_aux_size));
+    let (tx, _) = mpsc::channel();
+    let fuzzer = Fuzzer::new(config, tx);
+    env::set_var("FORK_SERVER_HOST", format!("{}", fuzzer.forkserver_host));
+    env::set_var(
+        "FORK_SERVER_RUNTIME",
+        format!("{}", fuzzer.forkserver_runtime),
+    );
+    fuzzer_runtime::runtime::__init();
+    (fuzzer, OutputGuard(output_dirname))
+}
+
 #[test]
 #[serial]
 fn test_path_coverage() {
-    let test_dirname = format!("{}", Uuid::new_v4());
-    create_test_dir(&test_dirname);
-    defer! {
-        remove_test_dir(&test_dirname);
-    }
-    let (shm_path, shm_aux_path, shm_cov_path) = (gen_filename(), gen_filename(), gen_filename());
-    let (tx, _) = mpsc::channel();
-    let mut fuzzer = Fuzzer::new(
-        &shm_path,
-        SHM_SIZE,
-        &shm_aux_path,
-        SHM_AUX_SIZE,
-        &shm_cov_path,
-        SHM_COV_SIZE,
-        SeedPool::new(&test_dirname),
-        fuzzer::cli::FuzzInput::Stdin,
-        tx,
-    );
-
-    set_env(&shm_path, SHM_SIZE, &shm_aux_path, SHM_AUX_SIZE, None, None);
-    fuzzer_runtime::runtime::__init();
+    let (mut fuzzer, _output_guard) = new_test_fuzzer();
 
     let (a, b, c, d, e) = (100, 200, 300, 400, 500);
     // path: a -> b -> c -> d -> e -> a
@@ -111,27 +118,7 @@ fn test_path_coverage() {
 #[test]
 #[serial]
 fn test_coverage_bucket() {
-    let test_dirname = format!("{}", Uuid::new_v4());
-    create_test_dir(&test_dirname);
-    defer! {
-        remove_test_dir(&test_dirname);
-    }
-    let (tx, _) = mpsc::channel();
-    let (shm_path, shm_aux_path, shm_cov_path) = (gen_filename(), gen_filename(), gen_filename());
-    let mut fuzzer = Fuzzer::new(
-        &shm_path,
-        SHM_SIZE,
-        &shm_aux_path,
-        SHM_AUX_SIZE,
-        &shm_cov_path,
-        SHM_COV_SIZE,
-        SeedPool::new(&test_dirname),
-        fuzzer::cli::FuzzInput::Stdin,
-        tx,
-    );
-
-    set_env(&shm_path, SHM_SIZE, &shm_aux_path, SHM_AUX_SIZE, None, None);
-    fuzzer_runtime::runtime::__init();
+    let (mut fuzzer, _output_guard) = new_test_fuzzer();
 
     let (a, b, c) = (100, 200, 300);
     // path: a -> b -> a
@@ -139,7 +126,7 @@ fn test_coverage_bucket() {
     __fuzzer_trace_edge(a);
     __fuzzer_trace_edge(b);
     __fuzzer_trace_edge(a);
-    assert!(fuzzer.eval_seed(0) == (3, u64::MAX));
+    assert!(fuzzer.eval_seed(0) == (3, 16));
     fuzzer.clear_new_coverage();
 
     // path: a -> b
@@ -185,43 +172,490 @@ fn test_coverage_bucket() {
 
     // path: b -> c
     __fuzzer_trace_edge(c);
-    assert!(fuzzer.eval_seed(0) == (4, u64::MAX));
+    assert!(fuzzer.eval_seed(0) == (4, 20));
 }
 
 #[test]
 #[serial]
 fn test_coverage_wakeup() {
-    let test_dirname = format!("{}", Uuid::new_v4());
-    create_test_dir(&test_dirname);
-    let (tx, _) = mpsc::channel();
-    let (shm_path, shm_aux_path, shm_cov_path) = (gen_filename(), gen_filename(), gen_filename());
-    let mut fuzzer = Fuzzer::new(
-        &shm_path,
-        SHM_SIZE,
-        &shm_aux_path,
-        SHM_AUX_SIZE,
-        &shm_cov_path,
-        SHM_COV_SIZE,
-        SeedPool::new(&test_dirname),
-        fuzzer::cli::FuzzInput::Stdin,
-        tx,
-    );
-
-    set_env(
-        &shm_path,
-        SHM_SIZE,
-        &shm_aux_path,
-        SHM_AUX_SIZE,
-        Some(fuzzer.forkserver_host),
-        Some(fuzzer.forkserver_runtime),
-    );
-    fuzzer_runtime::runtime::__init();
+    let (mut fuzzer, _output_guard) = new_test_fuzzer();
 
     thread::spawn(move || {
         fuzzer_runtime::runtime::__fuzzer_forkserver_init();
     });
-    fuzzer.wakeup_forkserver(HostSend::Wakeup(1));
+    fuzzer.wakeup_forkserver(HostSend::Wakeup(1000));
     fuzzer.wait_forkserver();
     fuzzer.wakeup_forkserver(HostSend::Terminate);
-    remove_test_dir(&test_dirname);
+}
+
+#[test]
+fn test_queue_resume() {
+    let meta = SeedMeta {
+        id: 42,
+        parent: Some(12),
+        mutations: vec!["insert".to_string(), "flip".to_string()],
+        edges: 17,
+        found: Duration::from_millis(1234),
+    };
+    let filename = meta.to_filename();
+    assert_eq!(
+        filename,
+        "id:000042,src:000012,op:insert+flip,edges:17,time:1234"
+    );
+    assert_eq!(SeedMeta::from_filename(&filename), Some(meta));
+    assert_eq!(SeedMeta::from_filename(".bitmap"), None);
+
+    let queue_dirname = gen_filename();
+    defer! {
+        fs::remove_dir_all(&queue_dirname).unwrap();
+    }
+    let mut queue = Queue::new(&queue_dirname).unwrap();
+    let mut init_seed = Seed::new(b"init".to_vec(), 0);
+    assert_eq!(
+        queue
+            .save(&mut init_seed, 3, Duration::from_secs(1))
+            .unwrap(),
+        0
+    );
+    let mut mutant = init_seed.clone();
+    let _ = mutant.mutate(&Dictionary::default(), &mut new_rng(0));
+    assert_eq!(mutant.get_parent(), Some(0));
+    assert_eq!(
+        queue.save(&mut mutant, 5, Duration::from_secs(2)).unwrap(),
+        1
+    );
+    queue.save_bitmap(&[1, 2, 3]).unwrap();
+
+    let (resumed, seeds) = Queue::resume(&queue_dirname).unwrap();
+    assert_eq!(resumed.len(), 2);
+    // in the order they are queued
+    let inputs: Vec<_> = seeds.iter().map(|seed| seed.get_input()).collect();
+    assert_eq!(inputs, [init_seed.get_input(), mutant.get_input()]);
+    assert_eq!(resumed.load_bitmap(), Some(vec![1, 2, 3]));
+}
+
+#[test]
+fn test_output_layout() {
+    let output_dirname = gen_filename();
+    defer! {
+        fs::remove_dir_all(&output_dirname).unwrap();
+    }
+    let output = OutputDir::new(&output_dirname).unwrap();
+    for dir in [output.queue_dir(), output.crash_dir(), output.hang_dir()] {
+        assert!(dir.is_dir());
+    }
+    assert!(output.shm_path("/tmp/shm").ends_with(output.campaign_id()));
+
+    // a campaign reopened from the same directory keeps its shared memory names
+    let reopened = OutputDir::new(&output_dirname).unwrap();
+    assert_eq!(reopened.campaign_id(), output.campaign_id());
+    let other_dirname = gen_filename();
+    defer! {
+        fs::remove_dir_all(&other_dirname).unwrap();
+    }
+    let other = OutputDir::new(&other_dirname).unwrap();
+    assert_ne!(other.shm_path("/tmp/shm"), output.shm_path("/tmp/shm"));
+}
+
+#[test]
+fn test_worker_sync() {
+    let output_dirname = gen_filename();
+    defer! {
+        fs::remove_dir_all(&output_dirname).unwrap();
+    }
+    let global_bitmap = new_global_bitmap(4);
+    let mut syncs: Vec<_> = (0..2)
+        .map(|worker_id| WorkerSync::new(&output_dirname, worker_id, 2, global_bitmap.clone()))
+        .collect();
+    let mut queue = Queue::new(worker_dir(&output_dirname, 1).join("queue")).unwrap();
+    let mut seed = Seed::new(b"found by worker1".to_vec(), 0);
+    queue.save(&mut seed, 1, Duration::from_secs(1)).unwrap();
+
+    // edges and seeds found by a worker are shared with the others
+    let mut bitmap1 = vec![0, 3, 0, 0];
+    assert!(syncs[1].sync(&mut bitmap1).unwrap().is_empty());
+    let mut bitmap0 = vec![1, 0, 0, 0];
+    let imported = syncs[0].sync(&mut bitmap0).unwrap();
+    assert_eq!(imported.len(), 1);
+    assert_eq!(imported[0].get_input(), seed.get_input());
+    assert_eq!(bitmap0, vec![1, 3, 0, 0]);
+    assert!(!syncs[0].is_due());
+
+    // seeds are imported once
+    assert!(syncs[0].sync(&mut bitmap0).unwrap().is_empty());
+    syncs[1].sync(&mut bitmap1).unwrap();
+    assert_eq!(bitmap1, vec![1, 3, 0, 0]);
+}
+
+#[test]
+fn test_crash_triage() {
+    let asan_report = |addr: &str, line: u32| {
+        format!(
+            "hello\n[ASAN] invalid memory access detected at main.c:{line}:5 in parse: {addr}\n\
+            stack backtrace:\n   0: asan_runtime::asan_runtime::report_asan_violated\n\
+             at /src/asan_runtime.rs:10:5\n   1: parse\n   2: main\n   3: __libc_start_main"
+        )
+    };
+    let report = CrashReport::parse(99, &asan_report("0x1234", 3));
+    assert_eq!(report.bug_type, "asan-invalid-access");
+    assert_eq!(report.frames, vec!["main.c:3:5 in parse", "parse", "main"]);
+    // addresses differ across runs of the same bug
+    assert_eq!(
+        report.bucket(),
+        CrashReport::parse(99, &asan_report("0x5678", 3)).bucket()
+    );
+    assert_ne!(
+        report.bucket(),
+        CrashReport::parse(99, &asan_report("0x1234", 4)).bucket()
+    );
+    assert_eq!(
+        bucket_from_name(&report.bucket_name()),
+        Some(report.bucket())
+    );
+    // user frames resembling ignored ones are kept
+    let report = CrashReport::parse(
+        101,
+        "stack backtrace:\n   0: std::panicking::begin_panic\n   1: <alloc::vec::Vec<T> as core::ops::Drop>::drop\n\
+           2: parse_start\n   3: print_backtrace\n   4: my_std::parse\n   5: _start",
+    );
+    assert_eq!(
+        report.frames,
+        vec!["parse_start", "print_backtrace", "my_std::parse"]
+    );
+
+    // crashes without a report are classified by the exit status
+    assert_eq!(CrashReport::parse(128 + 11, "").bug_type, "SIGSEGV");
+    assert_eq!(CrashReport::parse(3, "").bug_type, "exit-3");
+}
+
+#[test]
+fn test_dictionary() {
+    let dict_filename = gen_filename();
+    defer! {
+        fs::remove_file(&dict_filename).unwrap();
+    }
+    fs::write(
+        &dict_filename,
+        "# comment\nmagic=\"\\x89PNG\"\n\"GET \\\"/\\\"\"\nlevel@1=\"a\"\n\"a\"\n",
+    )
+    .unwrap();
+    let dict = Dictionary::from_file(&dict_filename).unwrap();
+    assert_eq!(
+        dict.tokens(),
+        &[b"\x89PNG".to_vec(), b"GET \"/\"".to_vec(), b"a".to_vec()]
+    );
+
+    // token mutations make a seed containing the token
+    let mut seed = Seed::new(b"xyz".to_vec(), 0);
+    let mut single_token = Dictionary::default();
+    single_token.add_token(b"MAGIC".to_vec());
+    let mut rng = new_rng(0);
+    while !seed.get_input().windows(5).any(|w| w == b"MAGIC") {
+        let _ = seed.mutate(&single_token, &mut rng);
+    }
+}
+
+#[test]
+fn test_deterministic_stages() {
+    // the path of the target depends only on the first byte
+    let seed = Seed::from_queue(vec![0, 0], 0);
+    let mut stages = DeterministicStages::new(&seed, 0, vec![]);
+    let mut mutants = vec![];
+    while let Some(mutant) = stages.next_mutant() {
+        stages.report(mutant.get_input()[0] as u64);
+        mutants.push(mutant);
+    }
+    assert!(mutants
+        .iter()
+        .all(|mutant| mutant.get_input() != seed.get_input()
+            && mutant.get_parent() == Some(0)
+            && mutant.get_mutations().len() == 1));
+    let has_mutant = |stage: &str, input: &[u8]| {
+        mutants
+            .iter()
+            .any(|mutant| mutant.get_mutations()[0] == stage && mutant.get_input() == input)
+    };
+    assert!(has_mutant("flip1", &[0x80, 0]));
+    assert!(has_mutant("flip8", &[0, 0xFF]));
+    assert!(has_mutant("arith8", &[0xDD, 0])); // -35
+    assert!(has_mutant("interest16", &[0xFF, 0x7F])); // 32767 in little endian
+                                                      // the second byte does not change the path, so it is skipped once the effector map is built
+    assert!(!has_mutant("arith8", &[0, 1]));
+    assert!(!has_mutant("interest8", &[0, 0x7F]));
+
+    // splicing takes the head of a seed and the tail of the other
+    let mut input = b"abcdef".to_vec();
+    let mut rng = new_rng(0);
+    assert_eq!(
+        splice(&mut input, b"abcdef", &mut rng),
+        MutateResult::NoSplicePoint
+    );
+    assert_eq!(splice(&mut input, b"aXXXXf", &mut rng), MutateResult::Done);
+    assert!(input == b"aXXXXf" || input == b"abXXXf" || input == b"abcXXf");
+}
+
+#[test]
+fn test_seeded_rng() {
+    // a fixed number of havoc and splice rounds produces the exact same corpus for a seed
+    let gen_corpus = |rng_seed: u64| {
+        let mut rng = new_rng(rng_seed);
+        let mut dict = Dictionary::default();
+        dict.add_token(b"MAGIC".to_vec());
+        let mut seeds = SeedPool::from_seeds(vec![
+            Seed::from_queue(b"hello".to_vec(), 0),
+            Seed::from_queue(b"world!".to_vec(), 1),
+        ]);
+        let mut corpus = vec![];
+        while let Some(seed) = seeds.pop_seed() {
+            seeds.keep(seed, Duration::from_millis(1), 0);
+        }
+        for _ in 0..100 {
+            let mut seed = seeds.next_entry().unwrap().clone();
+            if let Some(other) = seeds.pick_queued(&seed, &mut rng).cloned() {
+                let _ = seed.splice(&other, &mut rng);
+            }
+            let _ = seed.mutate(&dict, &mut rng);
+            corpus.push(seed.get_input().to_vec());
+        }
+        corpus
+    };
+    assert_eq!(gen_corpus(42), gen_corpus(42));
+    assert_ne!(gen_corpus(42), gen_corpus(43));
+}
+
+#[test]
+fn test_cmp_log() {
+    let mut mem = vec![0; 1 << 12];
+    // nothing is logged unless enabled
+    log_cmp(&mut mem, CmpKind::Int, &[1], &[2]);
+    assert!(stop_cmp_log(&mut mem).is_empty());
+
+    start_cmp_log(&mut mem);
+    log_cmp(
+        &mut mem,
+        CmpKind::Int,
+        &0x4142u16.to_le_bytes(),
+        &0xBEEFu16.to_le_bytes(),
+    );
+    log_cmp(&mut mem, CmpKind::Memory, b"xyz", b"FUZZ");
+    log_cmp(&mut mem, CmpKind::Memory, b"xyz", b"FUZZ");
+    // satisfied comparisons are not worth solving
+    log_cmp(&mut mem, CmpKind::Int, &[7], &[7]);
+    let cmps = stop_cmp_log(&mut mem);
+    assert_eq!(cmps.len(), 2);
+    log_cmp(&mut mem, CmpKind::Int, &[1], &[2]);
+    assert!(stop_cmp_log(&mut mem).is_empty());
+
+    // operands found in the input are replaced with the other side
+    let mutants = input_to_state(b"--AB--xyz", &cmps);
+    assert!(mutants.contains(&b"--\xBE\xEF--xyz".to_vec())); // big endian
+    assert!(mutants.contains(&b"--AB--FUZZ".to_vec()));
+    assert!(!mutants.contains(&b"--AB--xyz".to_vec()));
+}
+
+#[test]
+#[serial]
+fn test_persistent_input() {
+    let mut mem = vec![0; 16];
+    write_input(&mut mem, b"FUZZ");
+    assert_eq!(read_input(&mem), b"FUZZ");
+    // inputs longer than the buffer are truncated
+    write_input(&mut mem, b"0123456789");
+    assert_eq!(read_input(&mem), b"01234567");
+
+    // without the forkserver, a persistent target runs a single input
+    for id in [
+        "SHM_ID",
+        "SHM_AUX_ID",
+        "FORK_SERVER_HOST",
+        "FORK_SERVER_RUNTIME",
+    ] {
+        env::remove_var(id);
+    }
+    let mut edge_cov = EdgeCoverage::new();
+    assert!(edge_cov.next_iteration(1000));
+    assert!(!edge_cov.next_iteration(1000));
+}
+
+// run by `test_persistent_without_shared_input` in a child process, as waiting for the next
+// input stops the process
+#[test]
+#[ignore]
+fn persistent_without_shared_input_child() {
+    let mut edge_cov = EdgeCoverage::new();
+    assert!(edge_cov.next_iteration(1000));
+    assert!(!edge_cov.next_iteration(1000));
+}
+
+#[test]
+#[serial]
+fn test_persistent_without_shared_input() {
+    // with the forkserver but no shared input, the input read from stdin is run once
+    let mut child = Command::new(env::current_exe().unwrap())
+        .args([
+            "persistent_without_shared_input_child",
+            "--exact",
+            "--ignored",
+        ])
+        .env("FORK_SERVER_HOST", "-1")
+        .env("FORK_SERVER_RUNTIME", "-1")
+        .env_remove("SHM_ID")
+        .env_remove("SHM_AUX_ID")
+        .env_remove("SHM_INPUT_ID")
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break Some(status);
+        }
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            child.wait().unwrap();
+            break None;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    assert!(status.is_some_and(|status| status.success()));
+}
+
+#[test]
+fn test_power_schedules() {
+    let avg = SeedStats {
+        exec_time: Duration::from_millis(10),
+        len: 100,
+        fuzz_level: 0,
+        path_freq: 100,
+    };
+    let rare = SeedStats {
+        path_freq: 1,
+        ..avg
+    };
+    let frequent = SeedStats {
+        path_freq: 10000,
+        ..avg
+    };
+    // fast and small seeds get more energy
+    let cheap = SeedStats {
+        exec_time: Duration::from_millis(1),
+        len: 10,
+        ..avg
+    };
+    assert!(
+        energy(PowerSchedule::Explore, &cheap, &avg) > energy(PowerSchedule::Explore, &avg, &avg)
+    );
+    // seeds of rare paths get more energy, more as they are picked more
+    assert!(
+        energy(PowerSchedule::Fast, &rare, &avg) > energy(PowerSchedule::Fast, &frequent, &avg)
+    );
+    let picked = SeedStats {
+        fuzz_level: 3,
+        ..rare
+    };
+    assert!(energy(PowerSchedule::Fast, &picked, &avg) > energy(PowerSchedule::Fast, &rare, &avg));
+    assert_eq!(energy(PowerSchedule::Coe, &frequent, &avg), 0);
+    assert!(energy(PowerSchedule::Coe, &rare, &avg) > 0);
+    assert!(
+        energy(PowerSchedule::Exploit, &avg, &avg) > energy(PowerSchedule::Explore, &avg, &avg)
+    );
+
+    // seeds are kept in the pool and picked in turn, as many times as their energy
+    let mut seeds = SeedPool::from_seeds(vec![
+        Seed::new(b"a".to_vec(), 0),
+        Seed::new(b"b".to_vec(), 0),
+    ]);
+    seeds.set_schedule(PowerSchedule::Explore);
+    assert_eq!(seeds.pop_seed().unwrap().get_input(), b"a");
+    assert_eq!(seeds.pop_seed().unwrap().get_input(), b"b");
+    assert!(seeds.is_empty());
+    seeds.keep(Seed::new(b"a".to_vec(), 0), Duration::from_millis(1), 1);
+    seeds.keep(Seed::new(b"b".to_vec(), 0), Duration::from_millis(1), 2);
+    let picked: Vec<_> = (0..1000)
+        .map(|_| seeds.next_entry().unwrap().get_input().to_vec())
+        .collect();
+    let first_turn = picked.iter().take_while(|input| *input == b"a").count();
+    assert!(first_turn > 1 && first_turn < 1000);
+    assert!(picked.contains(&b"b".to_vec()));
+    assert_eq!(seeds.len(), 2);
+}
+
+#[test]
+fn test_seed_pool_eviction() {
+    let mut seeds = SeedPool::from_seeds(vec![]);
+    let input = |idx: usize| idx.to_le_bytes().to_vec();
+    // the path of the first seed is exercised the most
+    seeds.record_path(0);
+    seeds.record_path(0);
+    for idx in 0..=MAX_KEPT_SEEDS {
+        seeds.record_path(idx as u64);
+        seeds.keep(
+            Seed::new(input(idx), 0),
+            Duration::from_millis(1),
+            idx as u64,
+        );
+    }
+    assert_eq!(seeds.len(), MAX_KEPT_SEEDS);
+    assert_ne!(seeds.next_entry().unwrap().get_input(), input(0));
+}
+
+#[test]
+fn test_minimize() {
+    let edges = |edges: &[u64]| edges.iter().copied().collect::<EdgeSet>();
+    // (size, edges) of the corpus
+    let corpus = vec![
+        (10, edges(&[1, 2])),
+        (5, edges(&[1, 2])),
+        (50, edges(&[1, 2, 3, 4])),
+        (1, edges(&[5])),
+        (1, edges(&[])),
+    ];
+    assert_eq!(minimize_corpus(&corpus), vec![2, 3]);
+    assert_eq!(minimize_corpus(&corpus[..2]), vec![1]);
+
+    // the edges of the input depend on `MAGIC` preceded by a byte not `!`
+    let oracle = |input: &[u8]| -> anyhow::Result<bool> {
+        Ok(input
+            .windows(6)
+            .any(|w| &w[1..] == b"MAGIC" && w[0] != b'!'))
+    };
+    let minimized = minimize_input(b"xxxxxxxxxxyMAGICzzzzzzzzzzzzz", oracle).unwrap();
+    assert_eq!(minimized, b"0MAGIC");
+}
+
+#[test]
+fn test_repro() {
+    let input_path = gen_filename();
+    defer! {
+        fs::remove_file(&input_path).unwrap();
+    }
+    // the shell runs the input read from stdin
+    fs::write(
+        &input_path,
+        "echo out; echo warning >&2; echo '[ASAN] invalid memory access detected at a.c:3:5 in f: 0x10' >&2; exit 99",
+    )
+    .unwrap();
+    let result = repro("/bin/sh", &input_path, FuzzInput::Stdin, None, false).unwrap();
+    assert_eq!(result.status, Some(99));
+    assert_eq!(result.stdout, "out\n");
+    let report = result.crash_report().unwrap();
+    assert_eq!(report.bug_type, "asan-invalid-access");
+    assert_eq!(report.frames, vec!["a.c:3:5 in f"]);
+    assert!(report_excerpt(&result.stderr)
+        .unwrap()
+        .starts_with("[ASAN] invalid memory access"));
+
+    fs::write(&input_path, "exit 0").unwrap();
+    let result = repro("/bin/sh", &input_path, FuzzInput::Stdin, None, false).unwrap();
+    assert!(!result.is_crash() && result.crash_report().is_none());
+
+    // without the fuzzer, an input fed through shared memory is given as the file too
+    fs::write(&input_path, "exit 3").unwrap();
+    let result = repro("/bin/sh", &input_path, FuzzInput::SharedMemory, None, false).unwrap();
+    assert_eq!(result.status, Some(3));
+
+    fs::write(&input_path, "sleep 5").unwrap();
+    let timeout = Some(Duration::from_millis(100));
+    let result = repro("/bin/sh", &input_path, FuzzInput::Stdin, timeout, false).unwrap();
+    assert_eq!(result.status, None);
 }
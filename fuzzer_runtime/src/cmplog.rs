@@ -0,0 +1,52 @@
+use crate::internal::{read_u64, write_u64};
+
+// Layout of the comparison log shared with the fuzzer. Operands are logged only while the
+// fuzzer enables it, as logging every comparison of every run is costly.
+pub const CMP_LOG_ENABLED: usize = 0; // 8 bytes [0..7]
+pub const CMP_LOG_COUNT: usize = CMP_LOG_ENABLED + 8; // 8 bytes [8..15]
+pub const CMP_LOG_ENTRIES: usize = CMP_LOG_COUNT + 8; // n bytes [16..]
+                                                      // longer operands (e.g., of `memcmp`) are truncated
+pub const CMP_LOG_OPERAND_SIZE: usize = 32;
+// kind (1 byte), lengths of the operands (1 byte each), padding (5 bytes), operands
+pub const CMP_LOG_ENTRY_SIZE: usize = 8 + CMP_LOG_OPERAND_SIZE * 2;
+
+/// Kind of a logged comparison, which tells how the operands are laid out in the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CmpKind {
+    Int = 1,    // `icmp` and `switch`, little endian
+    Memory = 2, // `memcmp`, `strcmp` and their variants
+}
+
+impl CmpKind {
+    pub fn from_u8(kind: u8) -> Option<Self> {
+        match kind {
+            1 => Some(Self::Int),
+            2 => Some(Self::Memory),
+            _ => None,
+        }
+    }
+}
+
+/// Appends the operands of a comparison to the log if enabled and not full
+pub fn log_cmp(mem: &mut [u8], kind: CmpKind, lhs: &[u8], rhs: &[u8]) {
+    if mem[CMP_LOG_ENABLED] == 0 {
+        return;
+    }
+    let count = read_u64(mem, CMP_LOG_COUNT) as usize;
+    let entry = CMP_LOG_ENTRIES + count * CMP_LOG_ENTRY_SIZE;
+    if entry + CMP_LOG_ENTRY_SIZE > mem.len() {
+        return;
+    }
+    let (lhs, rhs) = (
+        &lhs[..lhs.len().min(CMP_LOG_OPERAND_SIZE)],
+        &rhs[..rhs.len().min(CMP_LOG_OPERAND_SIZE)],
+    );
+    mem[entry] = kind as u8;
+    mem[entry + 1] = lhs.len() as u8;
+    mem[entry + 2] = rhs.len() as u8;
+    let lhs_start = entry + 8;
+    let rhs_start = lhs_start + CMP_LOG_OPERAND_SIZE;
+    mem[lhs_start..lhs_start + lhs.len()].copy_from_slice(lhs);
+    mem[rhs_start..rhs_start + rhs.len()].copy_from_slice(rhs);
+    write_u64(mem, CMP_LOG_COUNT, count + 1);
+}
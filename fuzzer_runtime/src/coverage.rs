@@ -1,8 +1,17 @@
-use crate::internal::{init_forkserver_fd, init_shm, read_u128, read_u64, write_u128, write_u64};
-use libc::{c_void, kill, read, waitpid, write, SIGKILL, WEXITSTATUS};
+use crate::{
+    cmplog::{log_cmp, CmpKind},
+    input::SHARED_INPUT,
+    internal::{init_forkserver_fd, init_shm, read_u128, read_u64, write_u128, write_u64},
+};
+use libc::{
+    c_void, kill, raise, read, waitpid, write, SIGCONT, SIGKILL, SIGSTOP, WEXITSTATUS, WIFSIGNALED,
+    WIFSTOPPED, WNOHANG, WTERMSIG, WUNTRACED,
+};
 use memmap2::MmapMut;
+use std::io::Read;
 use std::sync::mpsc;
 use std::thread;
+use std::{env, fs, io};
 
 use std::time::Duration;
 use std::{
@@ -10,13 +19,15 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-const PREV_LOC_IDX: usize = 0; // 16 byte [0..15]
+pub const PREV_LOC_IDX: usize = 0; // 16 byte [0..15]
 pub const NEW_COVERAGES: usize = PREV_LOC_IDX + 16; // 8 byte [16..23]
 pub const VISIT_EDGES: usize = NEW_COVERAGES + 8; // 8 bytes [24..31]
 pub const VISIT_MARK: usize = VISIT_EDGES + 8; // 8 bytes [32..39]
 pub const VISIT_EDGES_INDICIES: usize = VISIT_MARK + 8; // n bytes [40..]
 pub const VISIT_EDGES_INDEX_SIZE: usize = 8;
 pub const PROCESS_EXIT_NORMAL: u64 = 1;
+// a process terminated by a signal other than `SIGKILL` (timeout) is reported as `128 + signal`
+pub const SIGNAL_STATUS_BASE: i32 = 128;
 
 lazy_static::lazy_static! {
     pub static ref EDGE_COVERAGE: Arc<Mutex<EdgeCoverage>> = Arc::new(Mutex::new(EdgeCoverage::new()));
@@ -26,6 +37,11 @@ pub struct EdgeCoverage {
     shm: Option<MmapMut>,
     shm_size: Option<usize>,
     aux: Option<MmapMut>,
+    cmp: Option<MmapMut>,
+    // input of a run without the fuzzer, read from the file given as the argument or the stdin
+    fallback_input: Option<Vec<u8>>,
+    // inputs run by the process in persistent mode
+    iterations: u32,
     fork_server_host: Option<i32>,
     fork_server_runtime: Option<i32>,
 }
@@ -40,10 +56,14 @@ impl EdgeCoverage {
     pub fn new() -> Self {
         let (shm_mmap, shm_size) = init_shm("SHM_ID", "SHM_SIZE");
         let (shm_aux_mmap, _) = init_shm("SHM_AUX_ID", "SHM_AUX_SIZE");
+        let (shm_cmp_mmap, _) = init_shm("SHM_CMP_ID", "SHM_CMP_SIZE");
         Self {
             shm: shm_mmap,
             shm_size,
             aux: shm_aux_mmap,
+            cmp: shm_cmp_mmap,
+            fallback_input: None,
+            iterations: 0,
             fork_server_host: init_forkserver_fd("FORK_SERVER_HOST"),
             fork_server_runtime: init_forkserver_fd("FORK_SERVER_RUNTIME"),
         }
@@ -55,6 +75,9 @@ impl EdgeCoverage {
         self.shm = new_edge_cov.shm;
         self.shm_size = new_edge_cov.shm_size;
         self.aux = new_edge_cov.aux;
+        self.cmp = new_edge_cov.cmp;
+        self.fallback_input = new_edge_cov.fallback_input;
+        self.iterations = new_edge_cov.iterations;
         self.fork_server_host = new_edge_cov.fork_server_host;
         self.fork_server_runtime = new_edge_cov.fork_server_runtime;
     }
@@ -74,6 +97,8 @@ impl EdgeCoverage {
 
     pub fn read_wakeup(&self) {
         if let Some(fd) = self.fork_server_runtime {
+            // process of a persistent target stopped after running its last input
+            let mut stopped_pid: Option<i32> = None;
             loop {
                 let mut host_sent: u64 = 0;
                 unsafe {
@@ -86,34 +111,53 @@ impl EdgeCoverage {
                 if host_sent == 99999999999 {
                     // exit signal
                     unsafe {
+                        if let Some(pid) = stopped_pid {
+                            kill(pid, SIGKILL);
+                            waitpid(pid, std::ptr::null_mut(), 0);
+                        }
                         libc::exit(0);
                     }
                 }
-                let pid = unsafe { libc::fork() };
+                let mut status: i32 = 0;
+                let pid = match stopped_pid.take() {
+                    // resume the stopped process to run the next input, unless it is killed
+                    Some(pid) if unsafe { waitpid(pid, &mut status, WNOHANG) } == 0 => {
+                        unsafe { kill(pid, SIGCONT) };
+                        pid
+                    }
+                    _ => unsafe { libc::fork() },
+                };
                 if pid == 0 {
                     // child process
+                    SHARED_INPUT.rewind();
                     return; // run target program's main logic
                 } else {
                     // parent process
+                    // the host sends the timeout in milliseconds
                     let (tx, rx) = mpsc::channel();
-                    thread::spawn(
-                        move || match rx.recv_timeout(Duration::from_secs(host_sent)) {
+                    thread::spawn(move || {
+                        match rx.recv_timeout(Duration::from_millis(host_sent)) {
                             Ok(_) => {}
                             Err(mpsc::RecvTimeoutError::Timeout) => unsafe {
                                 kill(pid, SIGKILL);
                             },
                             _ => {}
-                        },
-                    );
-                    let mut status: i32 = 0;
+                        }
+                    });
                     unsafe {
-                        waitpid(pid, &mut status, 0);
+                        waitpid(pid, &mut status, WUNTRACED);
                         tx.send(()).ok();
                     }
-                    status = if status != SIGKILL {
+                    status = if WIFSTOPPED(status) {
+                        // a persistent target stops itself once it has run the input
+                        stopped_pid = Some(pid);
+                        0
+                    } else if !WIFSIGNALED(status) {
                         WEXITSTATUS(status)
+                    } else if WTERMSIG(status) == SIGKILL {
+                        SIGKILL
                     } else {
-                        status
+                        SIGNAL_STATUS_BASE + WTERMSIG(status)
                     };
                     self.notify_process_exit(status.try_into().unwrap());
                 }
@@ -121,6 +165,48 @@ impl EdgeCoverage {
         }
     }
 
+    /// Tells whether a persistent target runs one more input (at most `max_iters` per
+    /// process). Every input but the first one is run once the forkserver resumes the process,
+    /// which stops itself to tell the forkserver the previous input is done.
+    pub fn next_iteration(&mut self, max_iters: u32) -> bool {
+        self.iterations += 1;
+        if self.iterations == 1 {
+            return true;
+        }
+        // the next inputs are fed through shared memory, so an input read otherwise (e.g.,
+        // from stdin) is run once
+        if self.fork_server_runtime.is_none()
+            || SHARED_INPUT.get().is_none()
+            || self.iterations > max_iters
+        {
+            return false;
+        }
+        unsafe { raise(SIGSTOP) };
+        SHARED_INPUT.rewind();
+        // the edges of the next input do not depend on the ones of the previous input
+        if let Some(ref mut shm_aux) = &mut self.aux {
+            write_u128(shm_aux, PREV_LOC_IDX, 0);
+        }
+        true
+    }
+
+    /// Input of the current run
+    pub fn input(&mut self) -> &[u8] {
+        if let Some(input) = SHARED_INPUT.get() {
+            return input;
+        }
+        self.fallback_input.get_or_insert_with(|| {
+            let mut input = vec![];
+            match env::args().nth(1) {
+                Some(path) => input = fs::read(path).unwrap_or_default(),
+                None => {
+                    io::stdin().read_to_end(&mut input).ok();
+                }
+            }
+            input
+        })
+    }
+
     pub fn trace_edge(&mut self, cur_loc: i64) {
         if let (Some(ref mut shm), Some(shm_size), Some(ref mut shm_aux)) =
             (&mut self.shm, self.shm_size, &mut self.aux)
@@ -152,4 +238,10 @@ impl EdgeCoverage {
             shm[edge] = shm[edge].saturating_add(1);
         }
     }
+
+    pub fn trace_cmp(&mut self, kind: CmpKind, lhs: &[u8], rhs: &[u8]) {
+        if let Some(ref mut shm_cmp) = &mut self.cmp {
+            log_cmp(shm_cmp, kind, lhs, rhs);
+        }
+    }
 }
@@ -0,0 +1,164 @@
+use crate::internal::{init_shm, read_u64, write_u64};
+use libc::{c_char, c_int, c_void, dlsym, size_t, ssize_t, FILE, RTLD_NEXT};
+use memmap2::MmapMut;
+use std::env;
+use std::ffi::{CStr, CString};
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+// Layout of the input shared with the fuzzer, which feeds the input of each run through it
+// instead of the stdin or a file
+pub const INPUT_LEN: usize = 0; // 8 bytes [0..7]
+pub const INPUT_DATA: usize = INPUT_LEN + 8; // n bytes [8..]
+
+type ReadFn = unsafe extern "C" fn(fd: c_int, buf: *mut c_void, count: size_t) -> ssize_t;
+type FopenFn = unsafe extern "C" fn(path: *const c_char, mode: *const c_char) -> *mut FILE;
+
+static READ: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static FOPEN: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+// memory stream of the shared input in place of the stdin stream
+static STDIN_STREAM: AtomicPtr<FILE> = AtomicPtr::new(ptr::null_mut());
+
+extern "C" {
+    static mut stdin: *mut FILE;
+}
+
+lazy_static::lazy_static! {
+    pub static ref SHARED_INPUT: SharedInput = SharedInput::new();
+}
+
+/// Places an input in the buffer, truncated to the size of the buffer
+pub fn write_input(mem: &mut [u8], input: &[u8]) {
+    let len = input.len().min(mem.len() - INPUT_DATA);
+    mem[INPUT_DATA..INPUT_DATA + len].copy_from_slice(&input[..len]);
+    write_u64(mem, INPUT_LEN, len);
+}
+
+/// Input placed in the buffer
+pub fn read_input(mem: &[u8]) -> &[u8] {
+    let len = (read_u64(mem, INPUT_LEN) as usize).min(mem.len() - INPUT_DATA);
+    &mem[INPUT_DATA..INPUT_DATA + len]
+}
+
+/// Input of the current run fed through the shared memory. The target reads it through
+/// `__fuzzer_input`, or as if it came from the stdin (by `read` or by the `stdin` stream) or
+/// the input file. It is not behind
+/// the lock of the edge coverage, as the forkserver reads its pipe while holding that lock.
+pub struct SharedInput {
+    mem: Option<MmapMut>,
+    // path of the input file given to the target, whose opening is served from the buffer
+    path: Option<CString>,
+    // bytes of the input consumed by reading the stdin
+    offset: AtomicUsize,
+}
+
+impl SharedInput {
+    fn new() -> Self {
+        let (mem, _) = init_shm("SHM_INPUT_ID", "SHM_INPUT_SIZE");
+        let path = env::var("FUZZER_INPUT_PATH")
+            .ok()
+            .and_then(|path| CString::new(path).ok());
+        Self {
+            mem,
+            path,
+            offset: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn get(&self) -> Option<&[u8]> {
+        self.mem.as_ref().map(|mem| read_input(mem))
+    }
+
+    /// Lets the input of the run be read from its start, also through the `stdin` stream
+    pub fn rewind(&self) {
+        self.offset.store(0, Ordering::Relaxed);
+        if let Some(input) = self.get() {
+            unsafe { reopen_stdin(input) };
+        }
+    }
+
+    fn read(&self, buf: &mut [u8]) -> Option<usize> {
+        let input = self.get()?;
+        let offset = self.offset.load(Ordering::Relaxed).min(input.len());
+        let len = buf.len().min(input.len() - offset);
+        buf[..len].copy_from_slice(&input[offset..offset + len]);
+        self.offset.store(offset + len, Ordering::Relaxed);
+        Some(len)
+    }
+
+    fn is_input_path(&self, path: &CStr) -> bool {
+        self.mem.is_some() && self.path.as_deref() == Some(path)
+    }
+}
+
+/// Resolves the next definition of `name` (i.e., the libc one) and caches it
+fn get_real_fn<F: Copy>(real_fn: &AtomicPtr<c_void>, name: &CStr) -> F {
+    let mut sym = real_fn.load(Ordering::Acquire);
+    if sym.is_null() {
+        sym = unsafe { dlsym(RTLD_NEXT, name.as_ptr()) };
+        assert!(!sym.is_null(), "failed to resolve {:?}", name);
+        real_fn.store(sym, Ordering::Release);
+    }
+    unsafe { std::mem::transmute_copy::<*mut c_void, F>(&sym) }
+}
+
+/// Replaces the `stdin` stream with a memory stream of `input`, closing the one of the previous
+/// input. Buffered bytes of the stdin are dropped.
+///
+/// # Safety
+/// No other thread uses the `stdin` stream
+unsafe fn reopen_stdin(input: &[u8]) {
+    let mode = c"r".as_ptr();
+    let stream = if input.is_empty() {
+        // a memory stream of no byte is not allowed
+        get_real_fn::<FopenFn>(&FOPEN, c"fopen")(c"/dev/null".as_ptr(), mode)
+    } else {
+        libc::fmemopen(input.as_ptr() as *mut c_void, input.len(), mode)
+    };
+    if stream.is_null() {
+        return;
+    }
+    let prev = STDIN_STREAM.swap(stream, Ordering::Relaxed);
+    if !prev.is_null() {
+        libc::fclose(prev);
+    }
+    stdin = stream;
+}
+
+/// Serves the stdin from the shared input if the fuzzer feeds it through the shared memory
+///
+/// # Safety
+/// `buf` points to `count` writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn read(fd: c_int, buf: *mut c_void, count: size_t) -> ssize_t {
+    if fd == libc::STDIN_FILENO && !buf.is_null() {
+        let buf = std::slice::from_raw_parts_mut(buf as *mut u8, count);
+        if let Some(len) = SHARED_INPUT.read(buf) {
+            return len as ssize_t;
+        }
+    }
+    get_real_fn::<ReadFn>(&READ, c"read")(fd, buf, count)
+}
+
+/// Opens the shared input as a memory stream if the input file is opened for reading
+///
+/// # Safety
+/// `path` and `mode` are null-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn fopen(path: *const c_char, mode: *const c_char) -> *mut FILE {
+    let real_fopen = get_real_fn::<FopenFn>(&FOPEN, c"fopen");
+    if path.is_null() || mode.is_null() || !SHARED_INPUT.is_input_path(CStr::from_ptr(path)) {
+        return real_fopen(path, mode);
+    }
+    let mode_str = CStr::from_ptr(mode).to_bytes();
+    if !mode_str.starts_with(b"r") || mode_str.contains(&b'+') {
+        return real_fopen(path, mode);
+    }
+    match SHARED_INPUT.get() {
+        // a memory stream of no byte is not allowed
+        Some(input) if !input.is_empty() => {
+            libc::fmemopen(input.as_ptr() as *mut c_void, input.len(), mode)
+        }
+        _ => real_fopen(c"/dev/null".as_ptr(), mode),
+    }
+}
@@ -1,3 +1,5 @@
+pub mod cmplog;
 pub mod coverage;
+pub mod input;
 pub mod internal;
 pub mod runtime;
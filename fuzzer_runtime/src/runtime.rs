@@ -1,4 +1,10 @@
+use crate::cmplog::{CmpKind, CMP_LOG_OPERAND_SIZE};
 use crate::coverage::EDGE_COVERAGE;
+use libc::{c_char, strnlen};
+use std::slice;
+
+// inputs run by a `LLVMFuzzerTestOneInput` target per process
+const PERSISTENT_ITERS: u32 = 1000;
 
 #[no_mangle]
 pub extern "C" fn __fuzzer_trace_edge(cur_loc: i64) {
@@ -14,3 +20,91 @@ pub fn __init() {
 pub extern "C" fn __fuzzer_forkserver_init() {
     EDGE_COVERAGE.lock().unwrap().read_wakeup();
 }
+
+/// Loop of a persistent target running the input of each iteration in the same process, e.g.,
+/// `while (__fuzzer_loop(1000)) { ... }`. A process runs at most `max_iters` inputs.
+#[no_mangle]
+pub extern "C" fn __fuzzer_loop(max_iters: u32) -> i32 {
+    EDGE_COVERAGE.lock().unwrap().next_iteration(max_iters) as i32
+}
+
+/// Returns the input of the current run and writes its length to `len`
+///
+/// # Safety
+/// `len` is either null or points to a writable `u64`
+#[no_mangle]
+pub unsafe extern "C" fn __fuzzer_input(len: *mut u64) -> *const u8 {
+    let mut edge_cov = EDGE_COVERAGE.lock().unwrap();
+    let input = edge_cov.input();
+    if !len.is_null() {
+        *len = input.len() as u64;
+    }
+    input.as_ptr()
+}
+
+/// `main` of a target defining `LLVMFuzzerTestOneInput`, which runs it in persistent mode
+///
+/// # Safety
+/// `test_one_input` reads at most the given number of bytes from the given input
+#[no_mangle]
+pub unsafe extern "C" fn __fuzzer_test_one_input_main(
+    test_one_input: unsafe extern "C" fn(*const u8, usize) -> i32,
+) -> i32 {
+    while __fuzzer_loop(PERSISTENT_ITERS) != 0 {
+        let mut len = 0;
+        let input = __fuzzer_input(&mut len);
+        test_one_input(input, len as usize);
+    }
+    0
+}
+
+/// Logs the operands of `icmp` or of `switch` (zero-extended, `size` in bytes)
+#[no_mangle]
+pub extern "C" fn __fuzzer_trace_cmp(lhs: u64, rhs: u64, size: u8) {
+    let size = (size as usize).min(8);
+    EDGE_COVERAGE.lock().unwrap().trace_cmp(
+        CmpKind::Int,
+        &lhs.to_le_bytes()[..size],
+        &rhs.to_le_bytes()[..size],
+    );
+}
+
+/// Logs the operands of `memcmp`-like calls comparing `len` bytes
+///
+/// # Safety
+/// `lhs` and `rhs` are either null or point to `len` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn __fuzzer_trace_memcmp(lhs: *const u8, rhs: *const u8, len: u64) {
+    if lhs.is_null() || rhs.is_null() {
+        return;
+    }
+    let len = (len as usize).min(CMP_LOG_OPERAND_SIZE);
+    let (lhs, rhs) = (
+        slice::from_raw_parts(lhs, len),
+        slice::from_raw_parts(rhs, len),
+    );
+    EDGE_COVERAGE
+        .lock()
+        .unwrap()
+        .trace_cmp(CmpKind::Memory, lhs, rhs);
+}
+
+/// Logs the operands of `strcmp`-like calls comparing up to `len` characters
+///
+/// # Safety
+/// `lhs` and `rhs` are either null or point to null-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn __fuzzer_trace_strcmp(lhs: *const c_char, rhs: *const c_char, len: u64) {
+    if lhs.is_null() || rhs.is_null() {
+        return;
+    }
+    let len = (len as usize).min(CMP_LOG_OPERAND_SIZE);
+    let (lhs, rhs) = (
+        slice::from_raw_parts(lhs as *const u8, strnlen(lhs, len)),
+        slice::from_raw_parts(rhs as *const u8, strnlen(rhs, len)),
+    );
+    EDGE_COVERAGE
+        .lock()
+        .unwrap()
+        .trace_cmp(CmpKind::Memory, lhs, rhs);
+}
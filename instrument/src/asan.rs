@@ -1,42 +1,45 @@
 use crate::{
+    asan_opt::{get_access, plan_checks, CheckPlan},
     inkwell_intrinsic::{
-        build_asan_init_redzone, build_asan_mem_check, can_skip_instrument, get_ptr_operand,
-        module_verify, set_filename,
+        build_asan_init_redzone, build_asan_mem_check, build_asan_stack_free,
+        build_asan_stack_malloc, build_asan_stack_scope, build_sprintf_done_len, build_sprintf_len,
+        can_preformat_sprintf, can_skip_instrument, get_callee_name, get_or_build_src_loc_str,
+        get_ptr_operand, get_target_data, is_static_alloca, module_verify,
     },
+    llvm_intrinsic::copy_debug_loc,
     module::InstrumentModule,
 };
 use anyhow::Result;
 use inkwell::{
+    basic_block::BasicBlock,
     builder::Builder,
     context::Context,
+    llvm_sys::prelude::LLVMValueRef,
     module::Module,
-    types::{BasicType, IntType},
-    values::{AnyValue, GlobalValue, InstructionOpcode, InstructionValue, IntValue, PointerValue},
-    AddressSpace,
+    targets::TargetData,
+    values::{
+        AsValueRef, FunctionValue, InstructionOpcode, InstructionValue, IntValue, PointerValue,
+    },
 };
 use std::collections::{HashMap, HashSet};
 
 const REDZONE_SIZE: u32 = 32;
+const SHADOW_GRANULE: u32 = 8;
+// set to `1` while instrumenting to keep every check (e.g., to debug the check elimination)
+const ASAN_DISABLE_CHECK_OPT: &str = "ASAN_DISABLE_CHECK_OPT";
 
 fn build_memcheck<'ctx>(
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-    filename_str_ptr: Option<GlobalValue<'ctx>>,
     instr: &InstructionValue<'ctx>,
     ptr: PointerValue<'ctx>,
     access_size: IntValue<'ctx>,
 ) -> Result<()> {
     // the index value must be integer type when using it as array index (RHS) value (e.g., int val = arr[idx] + 1)
     builder.position_before(&instr);
-    build_asan_mem_check(
-        context,
-        module,
-        builder,
-        &filename_str_ptr.unwrap(),
-        ptr,
-        access_size,
-    )?;
+    let src_loc_str_ptr = get_or_build_src_loc_str(module, builder, instr)?;
+    build_asan_mem_check(context, module, builder, &src_loc_str_ptr, ptr, access_size)?;
     Ok(())
 }
 
@@ -44,22 +47,19 @@ fn handle_load<'ctx>(
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-    filename_str_ptr: Option<GlobalValue<'ctx>>,
+    target_data: &TargetData,
     instr: &InstructionValue<'ctx>,
 ) -> Result<()> {
     let ptr = get_ptr_operand(&instr, 0);
-    if instr.get_type().is_int_type() {
-        let access_size = instr.get_type().into_int_type().size_of();
-        build_memcheck(
-            context,
-            module,
-            builder,
-            filename_str_ptr,
-            &instr,
-            ptr,
-            access_size,
-        )?;
+    let loaded_typ = instr.get_type();
+    if !loaded_typ.is_sized() {
+        return Ok(());
     }
+    // any sized value (integer, float, pointer, vector, aggregate) can be loaded
+    let access_size = context
+        .i64_type()
+        .const_int(target_data.get_store_size(&loaded_typ), false);
+    build_memcheck(context, module, builder, &instr, ptr, access_size)?;
     Ok(())
 }
 
@@ -67,179 +67,247 @@ fn handle_store<'ctx>(
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-    filename_str_ptr: Option<GlobalValue<'ctx>>,
+    target_data: &TargetData,
     instr: &InstructionValue<'ctx>,
 ) -> Result<()> {
-    let offset = instr.get_operand(0).unwrap().left().unwrap();
+    let value = instr.get_operand(0).unwrap().left().unwrap();
     let ptr = get_ptr_operand(&instr, 1);
 
-    // Do not consider pointer arithmetic (e.g., arr[idx] = value, where
-    // value is pointer)
-    if offset.is_pointer_value() {
+    // pointer value is used as LHS in `store` instruction. (e.g., arr[idx] = value)
+    // any type of access size is valid (i.e., `= value`), including pointer values
+    let access_size = context
+        .i64_type()
+        .const_int(target_data.get_store_size(&value.get_type()), false);
+    build_memcheck(context, module, builder, &instr, ptr, access_size)?;
+    Ok(())
+}
+
+/// Zero-extends the length operand of a memory intrinsic to the access size type
+fn get_len_operand<'ctx>(
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+    instr: &InstructionValue<'ctx>,
+    idx: u32,
+) -> Result<IntValue<'ctx>> {
+    let len = instr
+        .get_operand(idx)
+        .unwrap()
+        .left()
+        .unwrap()
+        .into_int_value();
+    builder.position_before(instr);
+    Ok(builder.build_int_z_extend_or_bit_cast(len, context.i64_type(), "")?)
+}
+
+fn handle_call<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    instr: &InstructionValue<'ctx>,
+) -> Result<()> {
+    let Some(callee) = get_callee_name(instr) else {
         return Ok(());
+    };
+    // memory intrinsics are lowered by the compiler rather than called through libc,
+    // so both ranges are checked in place instead of intercepting them at runtime
+    if callee.starts_with("llvm.memcpy.") || callee.starts_with("llvm.memmove.") {
+        // (dest, src, len, isvolatile)
+        let len = get_len_operand(context, builder, instr, 2)?;
+        for idx in [1, 0] {
+            let ptr = get_ptr_operand(instr, idx);
+            build_memcheck(context, module, builder, instr, ptr, len)?;
+        }
+    } else if callee.starts_with("llvm.memset.") {
+        // (dest, val, len, isvolatile)
+        let len = get_len_operand(context, builder, instr, 2)?;
+        let ptr = get_ptr_operand(instr, 0);
+        build_memcheck(context, module, builder, instr, ptr, len)?;
+    } else if callee == "sprintf" || callee == "vsprintf" {
+        // (dest, fmt, ...) the output is formatted beforehand to learn its length, so that
+        // the destination is checked before it is overflowed. Otherwise (e.g., `%n` would be
+        // run twice), the destination is checked once written.
+        let ptr = get_ptr_operand(instr, 0);
+        builder.position_before(instr);
+        let src_loc_str_ptr = get_or_build_src_loc_str(module, builder, instr)?;
+        let len = if can_preformat_sprintf(instr) {
+            build_sprintf_len(context, module, builder, instr)?
+        } else {
+            builder.position_before(&instr.get_next_instruction().unwrap());
+            build_sprintf_done_len(context, builder, instr)?
+        };
+        let check = build_asan_mem_check(context, module, builder, &src_loc_str_ptr, ptr, len)?;
+        copy_debug_loc(instr, &check);
     }
-
-    // pointer value is used as LHS in `store` instruction. (e.g., arr[idx] = value)
-    // any type of access size is valid (i.e., `= value`)
-    let access_size = offset.get_type().size_of().unwrap();
-    build_memcheck(
-        context,
-        module,
-        builder,
-        filename_str_ptr,
-        &instr,
-        ptr,
-        access_size,
-    )?;
     Ok(())
 }
 
+/// `llvm.lifetime.start/end` markers of each stack object paired with whether they start the scope
+type LifetimeMarkers<'ctx> = HashMap<LLVMValueRef, Vec<(InstructionValue<'ctx>, bool)>>;
+
+/// What the stack objects of a function need to know about it, collected once per function
+/// rather than rescanning the function for every object
+struct StackFrame<'ctx> {
+    entry_blk: Option<BasicBlock<'ctx>>,
+    lifetime_markers: LifetimeMarkers<'ctx>,
+    returns: Vec<InstructionValue<'ctx>>,
+}
+
+impl<'ctx> StackFrame<'ctx> {
+    fn new(func: &FunctionValue<'ctx>) -> Self {
+        let mut lifetime_markers = LifetimeMarkers::new();
+        let mut returns = vec![];
+        for basic_blk in func.get_basic_blocks() {
+            if let Some(terminator) = basic_blk.get_terminator() {
+                if terminator.get_opcode() == InstructionOpcode::Return {
+                    returns.push(terminator);
+                }
+            }
+            for instr in basic_blk.get_instructions() {
+                let Some(callee) = get_callee_name(&instr) else {
+                    continue;
+                };
+                let in_scope = if callee.starts_with("llvm.lifetime.start") {
+                    true
+                } else if callee.starts_with("llvm.lifetime.end") {
+                    false
+                } else {
+                    continue;
+                };
+                // (size, ptr)
+                let ptr = get_ptr_operand(&instr, 1);
+                lifetime_markers
+                    .entry(ptr.as_value_ref())
+                    .or_default()
+                    .push((instr, in_scope));
+            }
+        }
+        Self {
+            entry_blk: func.get_first_basic_block(),
+            lifetime_markers,
+            returns,
+        }
+    }
+}
+
 fn handle_alloca<'ctx>(
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-    replaced_alloca: &mut HashMap<PointerValue<'ctx>, (PointerValue<'ctx>, IntType<'ctx>)>,
+    target_data: &TargetData,
+    stack_frame: &mut StackFrame<'ctx>,
     instr: &InstructionValue<'ctx>,
 ) -> Result<()> {
-    // 1. allocate [ redzone | usable | redzone ]
-    let static_arr_kind = instr.get_allocated_type().unwrap();
-    if !static_arr_kind.is_array_type() {
+    let allocated_typ = instr.get_allocated_type().unwrap();
+    if !allocated_typ.is_sized() || !is_static_alloca(instr) {
+        return Ok(());
+    }
+    let usable_size = target_data.get_abi_size(&allocated_typ);
+    let align = instr.get_alignment().unwrap();
+    // the usable region starts right after the left redzone, so an object requiring
+    // a stricter alignment than the redzone size would be misaligned
+    if usable_size == 0 || align > REDZONE_SIZE {
         return Ok(());
     }
-    let static_arr = static_arr_kind.into_array_type();
-    let arr_len = static_arr.len();
-    let arr_typ = static_arr.get_element_type();
-    let elem_typ = arr_typ.into_int_type();
-    let elem_typ_byte = elem_typ.get_bit_width() / 8;
-    let rz_size = REDZONE_SIZE / elem_typ_byte;
 
+    // 1. allocate [ redzone | usable | redzone ] as a byte array
+    let total_size = REDZONE_SIZE as u64 + usable_size + REDZONE_SIZE as u64;
     builder.position_before(&instr);
-    let new_alloca = builder.build_alloca(elem_typ.array_type(rz_size + arr_len + rz_size), "")?;
+    let new_alloca =
+        builder.build_alloca(context.i8_type().array_type(total_size.try_into()?), "")?;
     let new_alloca_instr = new_alloca.as_instruction().unwrap();
+    // shadow memory tracks 8-byte granules from the start of the redzone
     new_alloca_instr
-        .set_alignment(instr.get_alignment().unwrap())
+        .set_alignment(align.max(SHADOW_GRANULE))
         .unwrap();
-    instr.replace_all_uses_with(&new_alloca_instr);
-    instr.erase_from_basic_block();
 
-    let next_instr_of_new_alloc = new_alloca_instr.get_next_instruction().unwrap();
+    let next_instr_of_new_alloc = instr.get_next_instruction().unwrap();
     builder.position_before(&next_instr_of_new_alloc);
 
-    // 2. mark redzones and set shadow memory
-    build_asan_init_redzone(
-        context,
-        module,
-        builder,
-        new_alloca,
-        static_arr.size_of().unwrap(),
-    )?;
-
-    // 3. add redzone size to allocated pointer to correctly set the usable pointer
-    let new_alloca_ptr = builder.build_alloca(context.ptr_type(AddressSpace::default()), "")?;
-    let zero_offset = context.i64_type().const_int(0, false);
-    let new_alloca_start_ptr = unsafe {
-        builder.build_in_bounds_gep(
-            elem_typ.array_type(rz_size + arr_len + rz_size),
-            new_alloca,
-            &[zero_offset, zero_offset],
-            "",
-        )?
+    // 2. objects of the entry block live until the function returns, thus they can be served
+    // from the fake stack (if enabled by the runtime) to detect use-after-return
+    let total_size = context.i64_type().const_int(total_size, false);
+    let usable_size = context.i64_type().const_int(usable_size, false);
+    let is_entry_alloca = instr.get_parent() == stack_frame.entry_blk;
+    let fake_frame = if is_entry_alloca {
+        Some(build_asan_stack_malloc(
+            context, module, builder, total_size,
+        )?)
+    } else {
+        None
     };
-    let rz_offset = context
-        .i64_type()
-        .const_int((REDZONE_SIZE / elem_typ_byte).into(), false);
+    let frame = match fake_frame {
+        Some(fake_frame) => {
+            let is_fake = builder.build_is_not_null(fake_frame, "")?;
+            builder
+                .build_select(is_fake, fake_frame, new_alloca, "")?
+                .into_pointer_value()
+        }
+        None => new_alloca,
+    };
+
+    // 3. mark redzones and set shadow memory
+    build_asan_init_redzone(context, module, builder, frame, usable_size)?;
+
+    // 4. add redzone size to allocated pointer to correctly set the usable pointer, and
+    // replace all uses of origin static object with it
+    let rz_offset = context.i64_type().const_int(REDZONE_SIZE.into(), false);
     let usable_ptr =
-        unsafe { builder.build_gep(elem_typ, new_alloca_start_ptr, &[rz_offset], "")? };
-    builder.build_store(new_alloca_ptr, usable_ptr)?;
+        unsafe { builder.build_in_bounds_gep(context.i8_type(), frame, &[rz_offset], "")? };
 
-    replaced_alloca.insert(new_alloca, (new_alloca_ptr, elem_typ));
-    Ok(())
-}
+    // 5. poison the object out of its scope
+    let markers = stack_frame
+        .lifetime_markers
+        .remove(&instr.as_value_ref())
+        .unwrap_or_default();
+    for (lifetime_marker, in_scope) in markers {
+        builder.position_before(&lifetime_marker);
+        build_asan_stack_scope(context, module, builder, usable_ptr, usable_size, in_scope)?;
+    }
 
-fn handle_call<'ctx>(
-    context: &'ctx Context,
-    builder: &Builder<'ctx>,
-    replaced_alloca: &HashMap<PointerValue<'ctx>, (PointerValue<'ctx>, IntType<'ctx>)>,
-    instr: &InstructionValue<'ctx>,
-) -> Result<()> {
-    // Inkwell seems to hold a subtle bug, where metadata instruction crashes to get operand
-    if !instr
-        .print_to_string()
-        .to_str()
-        .unwrap()
-        .contains("llvm.dbg.declare")
-    {
-        for (idx, operand) in instr.get_operands().into_iter().enumerate() {
-            if operand.is_none() {
-                return Ok(());
-            }
-            let operand = operand.unwrap();
-            if operand.left().is_none() {
-                return Ok(());
-            }
-            let operand = operand.left().unwrap();
-            if operand.is_pointer_value() {
-                if let Some((replaced_alloca_ptr, _)) =
-                    replaced_alloca.get(&operand.into_pointer_value())
-                {
-                    builder.position_before(&instr);
-                    let loaded_ptr = builder.build_load(
-                        context.ptr_type(AddressSpace::default()),
-                        *replaced_alloca_ptr,
-                        "",
-                    )?;
-                    instr.set_operand(idx.try_into().unwrap(), loaded_ptr);
-                }
-            }
+    // 6. release the frame at every return, so that no stale redzone is left on the stack
+    if let Some(fake_frame) = fake_frame {
+        for ret in &stack_frame.returns {
+            builder.position_before(ret);
+            build_asan_stack_free(context, module, builder, fake_frame, new_alloca, total_size)?;
         }
     }
+    instr.replace_all_uses_with(&usable_ptr.as_instruction().unwrap());
+    instr.erase_from_basic_block();
     Ok(())
 }
 
-fn handle_gep<'ctx>(
+/// Checks a loop-invariant access once at the end of the loop preheader
+fn build_hoisted_memcheck<'ctx>(
     context: &'ctx Context,
+    module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-    replaced_alloca: &HashMap<PointerValue<'ctx>, (PointerValue<'ctx>, IntType<'ctx>)>,
+    target_data: &TargetData,
     instr: &InstructionValue<'ctx>,
+    preheader: &BasicBlock<'ctx>,
 ) -> Result<()> {
-    let target_ptr = instr
-        .get_operand(0)
-        .unwrap()
-        .left()
-        .unwrap()
-        .into_pointer_value();
-    if let Some((replaced_alloca_ptr, replaced_alloca_typ)) = replaced_alloca.get(&target_ptr) {
-        builder.position_before(&instr);
-        // 1. add `load` instruction for pointer
-        let loaded_ptr = builder.build_load(
-            context.ptr_type(AddressSpace::default()),
-            *replaced_alloca_ptr,
-            "",
-        )?;
-        // 2. install `gep` instruction and remove older one
-        let idx = instr
-            .get_operand(2)
-            .unwrap()
-            .left()
-            .unwrap()
-            .into_int_value();
-        let gep = unsafe {
-            builder.build_gep(
-                // replaced_alloca_typ.unwrap(),
-                *replaced_alloca_typ,
-                loaded_ptr.into_pointer_value(),
-                &[idx],
-                "",
-            )?
-        };
-        instr.replace_all_uses_with(&gep.as_instruction().unwrap());
-        instr.erase_from_basic_block();
-    }
+    let Some((ptr, access_size)) = get_access(target_data, instr) else {
+        return Ok(());
+    };
+    builder.position_before(&preheader.get_terminator().unwrap());
+    let src_loc_str_ptr = get_or_build_src_loc_str(module, builder, instr)?;
+    let access_size = context.i64_type().const_int(access_size, false);
+    let call = build_asan_mem_check(context, module, builder, &src_loc_str_ptr, ptr, access_size)?;
+    // the check belongs to the access rather than to the preheader
+    copy_debug_loc(instr, &call);
     Ok(())
 }
 
-#[derive(Default)]
-pub struct ASANModule {}
+pub struct ASANModule {
+    optimize_checks: bool,
+}
+
+impl Default for ASANModule {
+    fn default() -> Self {
+        let optimize_checks =
+            !matches!(std::env::var(ASAN_DISABLE_CHECK_OPT), Ok(val) if val == "1");
+        Self { optimize_checks }
+    }
+}
 
 impl InstrumentModule for ASANModule {
     fn instrument<'ctx>(
@@ -248,15 +316,19 @@ impl InstrumentModule for ASANModule {
         module: &Module<'ctx>,
         builder: &Builder<'ctx>,
     ) -> Result<()> {
-        let mut filename_str_ptr = None;
+        let target_data = get_target_data(module)?;
         let funcs: Vec<_> = module.get_functions().collect();
         for func in funcs {
             // Skip funcs without bodies or those we've added
             if can_skip_instrument(&func) {
                 continue;
             }
-            set_filename(module, builder, &mut filename_str_ptr, &func)?;
-            let mut replaced_alloca = HashMap::new();
+            let plan = if self.optimize_checks {
+                plan_checks(&func, &target_data)
+            } else {
+                CheckPlan::default()
+            };
+            let mut stack_frame = StackFrame::new(&func);
             let mut instrumented_blks = HashSet::new();
             for basic_blk in func.get_basic_blocks() {
                 if instrumented_blks.contains(&basic_blk) {
@@ -265,27 +337,34 @@ impl InstrumentModule for ASANModule {
                 for instr in basic_blk.get_instructions() {
                     match instr.get_opcode() {
                         // install asan check
-                        InstructionOpcode::Load => {
-                            handle_load(context, module, builder, filename_str_ptr, &instr)?;
+                        InstructionOpcode::Load if !plan.is_elided(&instr) => {
+                            handle_load(context, module, builder, &target_data, &instr)?;
                         }
-                        InstructionOpcode::Store => {
-                            handle_store(context, module, builder, filename_str_ptr, &instr)?;
+                        InstructionOpcode::Store if !plan.is_elided(&instr) => {
+                            handle_store(context, module, builder, &target_data, &instr)?;
                         }
-                        InstructionOpcode::Alloca => {
-                            handle_alloca(context, module, builder, &mut replaced_alloca, &instr)?;
-                        }
-                        // Replace all uses of origin static object with newly allocated object's pointer
                         InstructionOpcode::Call => {
-                            handle_call(context, builder, &replaced_alloca, &instr)?;
+                            handle_call(context, module, builder, &instr)?;
                         }
-                        InstructionOpcode::GetElementPtr => {
-                            handle_gep(context, builder, &replaced_alloca, &instr)?;
+                        // Surround static objects with redzones
+                        InstructionOpcode::Alloca => {
+                            handle_alloca(
+                                context,
+                                module,
+                                builder,
+                                &target_data,
+                                &mut stack_frame,
+                                &instr,
+                            )?;
                         }
                         _ => {}
                     }
                 }
                 instrumented_blks.insert(basic_blk);
             }
+            for (instr, preheader) in &plan.hoisted {
+                build_hoisted_memcheck(context, module, builder, &target_data, instr, preheader)?;
+            }
         }
         // Verify instrumented IRs
         module_verify(module)
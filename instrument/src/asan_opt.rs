@@ -0,0 +1,397 @@
+use crate::inkwell_intrinsic::{get_callee_name, get_ptr_operand};
+use inkwell::{
+    basic_block::BasicBlock,
+    llvm_sys::{self},
+    targets::TargetData,
+    types::AnyType,
+    values::{AsValueRef, FunctionValue, InstructionOpcode, InstructionValue, PointerValue},
+};
+use llvm_sys::{
+    core::{
+        LLVMConstIntGetSExtValue, LLVMGetAllocatedType, LLVMGetConstOpcode, LLVMGetElementType,
+        LLVMGetGEPSourceElementType, LLVMGetNumOperands, LLVMGetOperand, LLVMGetTypeKind,
+        LLVMGlobalGetValueType, LLVMIsAAllocaInst, LLVMIsAConstantExpr, LLVMIsAConstantInt,
+        LLVMIsAGetElementPtrInst, LLVMIsAGlobalVariable, LLVMIsAInstruction,
+        LLVMStructGetTypeAtIndex, LLVMTypeIsSized,
+    },
+    prelude::{LLVMTypeRef, LLVMValueRef},
+    target::{LLVMABISizeOfType, LLVMOffsetOfElement, LLVMTargetDataRef},
+    LLVMOpcode, LLVMTypeKind,
+};
+use petgraph::{algo::dominators::simple_fast, prelude::DiGraphMap};
+use std::collections::{HashMap, HashSet};
+
+// dominance and reachability are kept as dense matrices, so larger functions are only
+// optimized within basic blocks
+const MAX_OPT_BLOCKS: usize = 1024;
+
+/// A load or store checked by `__asan_mem_check`
+#[derive(Clone, Copy)]
+struct Access<'ctx> {
+    instr: InstructionValue<'ctx>,
+    ptr: LLVMValueRef,
+    size: u64,
+    blk: usize,
+    pos: usize,
+}
+
+/// Which checks of a function can be dropped or moved out of loops
+#[derive(Default)]
+pub struct CheckPlan<'ctx> {
+    elided: HashSet<LLVMValueRef>,
+    /// accesses whose check is done once in the preheader of a loop instead
+    pub hoisted: Vec<(InstructionValue<'ctx>, BasicBlock<'ctx>)>,
+}
+
+impl<'ctx> CheckPlan<'ctx> {
+    /// Whether no check is needed right before `instr`
+    pub fn is_elided(&self, instr: &InstructionValue<'ctx>) -> bool {
+        self.elided.contains(&instr.as_value_ref())
+    }
+}
+
+/// Returns the checked pointer and access size of a load or store
+pub fn get_access<'ctx>(
+    target_data: &TargetData,
+    instr: &InstructionValue<'ctx>,
+) -> Option<(PointerValue<'ctx>, u64)> {
+    let (ptr, typ) = match instr.get_opcode() {
+        InstructionOpcode::Load => (get_ptr_operand(instr, 0), instr.get_type()),
+        InstructionOpcode::Store => (
+            get_ptr_operand(instr, 1),
+            instr.get_operand(0)?.left()?.get_type().as_any_type_enum(),
+        ),
+        _ => return None,
+    };
+    if !typ.is_sized() {
+        return None;
+    }
+    Some((ptr, target_data.get_store_size(&typ)))
+}
+
+/// Instructions which may change the shadow memory of checked pointers (e.g., `free` or the
+/// end of a scope), so that an earlier check does not hold after them
+fn is_barrier(instr: &InstructionValue) -> bool {
+    match instr.get_opcode() {
+        InstructionOpcode::Call => {
+            !get_callee_name(instr).is_some_and(|callee| callee.starts_with("llvm.dbg."))
+        }
+        InstructionOpcode::Invoke | InstructionOpcode::CallBr => true,
+        _ => false,
+    }
+}
+
+unsafe fn get_sized_alloc_size(target_data: LLVMTargetDataRef, typ: LLVMTypeRef) -> Option<u64> {
+    (LLVMTypeIsSized(typ) != 0).then(|| LLVMABISizeOfType(target_data, typ))
+}
+
+/// Returns the size of a static stack object or a global variable
+unsafe fn get_object_size(target_data: LLVMTargetDataRef, obj: LLVMValueRef) -> Option<u64> {
+    if !LLVMIsAAllocaInst(obj).is_null() {
+        // `alloca <ty>, i64 %n` (e.g., VLA) has no static size
+        let count = LLVMGetOperand(obj, 0);
+        if LLVMIsAConstantInt(count).is_null() || LLVMConstIntGetSExtValue(count) != 1 {
+            return None;
+        }
+        return get_sized_alloc_size(target_data, LLVMGetAllocatedType(obj));
+    }
+    if !LLVMIsAGlobalVariable(obj).is_null() {
+        return get_sized_alloc_size(target_data, LLVMGlobalGetValueType(obj));
+    }
+    None
+}
+
+unsafe fn is_gep(val: LLVMValueRef) -> bool {
+    !LLVMIsAGetElementPtrInst(val).is_null()
+        || (!LLVMIsAConstantExpr(val).is_null()
+            && LLVMGetConstOpcode(val) == LLVMOpcode::LLVMGetElementPtr)
+}
+
+/// Resolves a chain of constant-index GEPs (instructions or constant expressions) into the
+/// base object and the byte offset from it
+unsafe fn get_const_offset(
+    target_data: LLVMTargetDataRef,
+    mut ptr: LLVMValueRef,
+) -> Option<(LLVMValueRef, i64)> {
+    let mut offset = 0i64;
+    while is_gep(ptr) {
+        let mut typ = LLVMGetGEPSourceElementType(ptr);
+        for idx in 1..LLVMGetNumOperands(ptr) as u32 {
+            let operand = LLVMGetOperand(ptr, idx);
+            if LLVMIsAConstantInt(operand).is_null() {
+                return None;
+            }
+            let val = LLVMConstIntGetSExtValue(operand);
+            // the first index steps over the whole source element type
+            if idx == 1 {
+                let elem_size = get_sized_alloc_size(target_data, typ)? as i64;
+                offset = offset.checked_add(val.checked_mul(elem_size)?)?;
+                continue;
+            }
+            match LLVMGetTypeKind(typ) {
+                LLVMTypeKind::LLVMStructTypeKind => {
+                    let elem_offset = LLVMOffsetOfElement(target_data, typ, val as u32) as i64;
+                    offset = offset.checked_add(elem_offset)?;
+                    typ = LLVMStructGetTypeAtIndex(typ, val as u32);
+                }
+                LLVMTypeKind::LLVMArrayTypeKind | LLVMTypeKind::LLVMVectorTypeKind => {
+                    typ = LLVMGetElementType(typ);
+                    let elem_size = get_sized_alloc_size(target_data, typ)? as i64;
+                    offset = offset.checked_add(val.checked_mul(elem_size)?)?;
+                }
+                _ => return None,
+            }
+        }
+        ptr = LLVMGetOperand(ptr, 0);
+    }
+    Some((ptr, offset))
+}
+
+/// Whether the access stays within a stack object or a global variable of known size
+fn is_in_bounds(target_data: &TargetData, access: &Access) -> bool {
+    unsafe {
+        let target_data = target_data.as_mut_ptr();
+        let Some((obj, offset)) = get_const_offset(target_data, access.ptr) else {
+            return false;
+        };
+        let Some(obj_size) = get_object_size(target_data, obj) else {
+            return false;
+        };
+        offset >= 0 && (offset as u64).saturating_add(access.size) <= obj_size
+    }
+}
+
+/// Control flow of a single function. Dominance and reachability are only computed for
+/// functions up to `MAX_OPT_BLOCKS` blocks.
+struct FuncCfg<'ctx> {
+    blks: Vec<BasicBlock<'ctx>>,
+    blk_idx: HashMap<BasicBlock<'ctx>, usize>,
+    succs: Vec<Vec<usize>>,
+    preds: Vec<Vec<usize>>,
+    // positions of the barriers within each block, in ascending order
+    barriers: Vec<Vec<usize>>,
+    // `dominates[a][b]`: every path from the entry to `b` goes through `a`
+    dominates: Vec<Vec<bool>>,
+    // `reaches[a][b]`: a path of at least one edge leads from `a` to `b`
+    reaches: Vec<Vec<bool>>,
+}
+
+impl<'ctx> FuncCfg<'ctx> {
+    fn new(func: &FunctionValue<'ctx>) -> Option<Self> {
+        let blks = func.get_basic_blocks();
+        if blks.is_empty() || blks.len() > MAX_OPT_BLOCKS {
+            return None;
+        }
+        let blk_idx: HashMap<_, _> = blks.iter().enumerate().map(|(i, b)| (*b, i)).collect();
+        let mut succs = vec![Vec::new(); blks.len()];
+        let mut preds = vec![Vec::new(); blks.len()];
+        let mut graph = DiGraphMap::<usize, ()>::new();
+        for (idx, blk) in blks.iter().enumerate() {
+            graph.add_node(idx);
+            let Some(term) = blk.get_terminator() else {
+                continue;
+            };
+            for succ in term.get_operands().filter_map(|op| op?.right()) {
+                let succ = blk_idx[&succ];
+                if !succs[idx].contains(&succ) {
+                    succs[idx].push(succ);
+                    preds[succ].push(idx);
+                    graph.add_edge(idx, succ, ());
+                }
+            }
+        }
+        let barriers = blks
+            .iter()
+            .map(|blk| {
+                blk.get_instructions()
+                    .enumerate()
+                    .filter(|(_, instr)| is_barrier(instr))
+                    .map(|(pos, _)| pos)
+                    .collect()
+            })
+            .collect();
+
+        let doms = simple_fast(&graph, 0);
+        let mut dominates = vec![vec![false; blks.len()]; blks.len()];
+        for blk in 0..blks.len() {
+            // unreachable blocks have no dominators and are never optimized
+            for dom in doms.dominators(blk).into_iter().flatten() {
+                dominates[dom][blk] = true;
+            }
+        }
+        let mut reaches = vec![vec![false; blks.len()]; blks.len()];
+        for (from, reach) in reaches.iter_mut().enumerate() {
+            let mut worklist = succs[from].clone();
+            while let Some(blk) = worklist.pop() {
+                if !reach[blk] {
+                    reach[blk] = true;
+                    worklist.extend(&succs[blk]);
+                }
+            }
+        }
+        Some(FuncCfg {
+            blks,
+            blk_idx,
+            succs,
+            preds,
+            barriers,
+            dominates,
+            reaches,
+        })
+    }
+
+    /// Whether no barrier can be executed after `from` and before `to`, given that `from`
+    /// dominates `to`
+    fn is_barrier_free(&self, from: &Access, to: &Access) -> bool {
+        let barriers_of = |blk: usize| self.barriers[blk].iter();
+        if from.blk == to.blk {
+            // leaving the block and coming back executes `from` again
+            return !barriers_of(from.blk).any(|pos| *pos > from.pos && *pos < to.pos);
+        }
+        let tail_free = !barriers_of(from.blk).any(|pos| *pos > from.pos);
+        let head_free = !barriers_of(to.blk).any(|pos| *pos < to.pos);
+        // conservatively, every block on some path between both blocks
+        tail_free
+            && head_free
+            && (0..self.blks.len()).all(|blk| {
+                !(self.reaches[from.blk][blk] && self.reaches[blk][to.blk])
+                    || !self.has_barrier(blk)
+            })
+    }
+
+    fn has_barrier(&self, blk: usize) -> bool {
+        !self.barriers[blk].is_empty()
+    }
+
+    /// Natural loops as (header, body), where loops sharing a header are merged
+    fn get_loops(&self) -> Vec<(usize, HashSet<usize>)> {
+        let mut loops = HashMap::<usize, HashSet<usize>>::new();
+        for latch in 0..self.blks.len() {
+            for &header in &self.succs[latch] {
+                if !self.dominates[header][latch] {
+                    continue;
+                }
+                let body = loops
+                    .entry(header)
+                    .or_insert_with(|| HashSet::from([header]));
+                let mut worklist = vec![latch];
+                while let Some(blk) = worklist.pop() {
+                    if body.insert(blk) {
+                        worklist.extend(&self.preds[blk]);
+                    }
+                }
+            }
+        }
+        loops.into_iter().collect()
+    }
+
+    /// The only block entering the loop, which branches to the header unconditionally
+    fn get_preheader(&self, header: usize, body: &HashSet<usize>) -> Option<usize> {
+        let mut outside = self.preds[header].iter().filter(|p| !body.contains(p));
+        let preheader = *outside.next()?;
+        (outside.next().is_none() && self.succs[preheader] == [header]).then_some(preheader)
+    }
+}
+
+/// Whether `ptr` is defined out of the loop, so that its value is the same in every iteration
+fn is_loop_invariant(cfg: &FuncCfg, body: &HashSet<usize>, ptr: LLVMValueRef) -> bool {
+    unsafe {
+        // stack objects are replaced while instrumenting, thus their checks are not moved
+        if !LLVMIsAAllocaInst(ptr).is_null() {
+            return false;
+        }
+    }
+    // arguments and globals are defined out of every loop
+    let def_blk = unsafe {
+        if LLVMIsAInstruction(ptr).is_null() {
+            return true;
+        }
+        InstructionValue::new(ptr).get_parent()
+    };
+    def_blk
+        .and_then(|blk| cfg.blk_idx.get(&blk))
+        .is_none_or(|blk| !body.contains(blk))
+}
+
+/// Finds checks which can be skipped:
+/// 1. accesses to a stack object or a global variable at a constant in-bounds offset
+/// 2. accesses to the same pointer as a dominating check, without any barrier in between
+/// 3. loop-invariant accesses executed in every iteration of a loop without barriers,
+///    which are checked once before the loop
+pub fn plan_checks<'ctx>(func: &FunctionValue<'ctx>, target_data: &TargetData) -> CheckPlan<'ctx> {
+    let mut plan = CheckPlan::default();
+    let mut accesses = Vec::new();
+    for (blk, basic_blk) in func.get_basic_blocks().iter().enumerate() {
+        for (pos, instr) in basic_blk.get_instructions().enumerate() {
+            if let Some((ptr, size)) = get_access(target_data, &instr) {
+                accesses.push(Access {
+                    instr,
+                    ptr: ptr.as_value_ref(),
+                    size,
+                    blk,
+                    pos,
+                });
+            }
+        }
+    }
+
+    // 1. provably in-bounds accesses
+    accesses.retain(|access| {
+        let in_bounds = is_in_bounds(target_data, access);
+        if in_bounds {
+            plan.elided.insert(access.instr.as_value_ref());
+        }
+        !in_bounds
+    });
+
+    let Some(cfg) = FuncCfg::new(func) else {
+        return plan;
+    };
+
+    // 2. checks dominated by an equivalent check
+    let mut checked: Vec<Access> = Vec::new();
+    for access in &accesses {
+        let is_redundant = checked.iter().any(|prev| {
+            prev.ptr == access.ptr
+                && prev.size >= access.size
+                && if prev.blk == access.blk {
+                    prev.pos < access.pos
+                } else {
+                    cfg.dominates[prev.blk][access.blk]
+                }
+                && cfg.is_barrier_free(prev, access)
+        });
+        if is_redundant {
+            plan.elided.insert(access.instr.as_value_ref());
+        } else {
+            checked.push(*access);
+        }
+    }
+
+    // 3. loop-invariant checks, moved out of the outermost loop possible
+    let mut loops = cfg.get_loops();
+    loops.sort_by_key(|(_, body)| std::cmp::Reverse(body.len()));
+    for access in &checked {
+        let target = loops.iter().find_map(|(header, body)| {
+            if !body.contains(&access.blk) || body.iter().any(|blk| cfg.has_barrier(*blk)) {
+                return None;
+            }
+            let exiting = body
+                .iter()
+                .filter(|blk| cfg.succs[**blk].iter().any(|succ| !body.contains(succ)))
+                .collect::<Vec<_>>();
+            // the access must be executed whenever the loop is left
+            let is_executed =
+                !exiting.is_empty() && exiting.iter().all(|blk| cfg.dominates[access.blk][**blk]);
+            if !is_executed || !is_loop_invariant(&cfg, body, access.ptr) {
+                return None;
+            }
+            cfg.get_preheader(*header, body)
+        });
+        if let Some(preheader) = target {
+            plan.elided.insert(access.instr.as_value_ref());
+            plan.hoisted.push((access.instr, cfg.blks[preheader]));
+        }
+    }
+    plan
+}
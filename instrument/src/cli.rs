@@ -5,6 +5,7 @@ use clap::{Arg, Command};
 pub enum ModuleTyp {
     Coverage,
     Asan,
+    Msan,
     Fuzz,
     Symbolic,
     Race,
@@ -16,6 +17,7 @@ impl ModuleTyp {
         match self {
             Self::Coverage => "coverage",
             Self::Asan => "asan",
+            Self::Msan => "msan",
             Self::Fuzz => "fuzz",
             Self::Race => "race",
             Self::Symbolic => "symbolic",
@@ -27,6 +29,7 @@ impl ModuleTyp {
         [
             Self::Coverage,
             Self::Asan,
+            Self::Msan,
             Self::Fuzz,
             Self::Race,
             Self::Symbolic,
@@ -61,7 +64,7 @@ pub fn get_args() -> Result<(String, String, ModuleTyp)> {
                 .short('m')
                 .long("module")
                 .value_name("string")
-                .help("coverage || asan || fuzz || all")
+                .help("coverage || asan || msan || fuzz || all")
                 .required(true),
         )
         .get_matches();
@@ -87,7 +90,7 @@ pub fn get_args() -> Result<(String, String, ModuleTyp)> {
     let module_typ = ModuleTyp::from_str(&module_typ_str);
     if module_typ.is_none() {
         return Err(anyhow::anyhow!(
-            "Invalid module type. Only <coaverage || asan || fuzz || all> available "
+            "Invalid module type. Only <coaverage || asan || msan || fuzz || all> available "
         ));
     }
     Ok((input_filename, output_filename, module_typ.unwrap()))
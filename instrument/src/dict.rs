@@ -0,0 +1,96 @@
+use crate::inkwell_intrinsic::can_skip_instrument;
+use anyhow::Result;
+use inkwell::{
+    module::Module,
+    values::{BasicValueEnum, InstructionOpcode, InstructionValue, IntValue},
+};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+// longer strings are unlikely to be compared as a whole against the input
+const MAX_TOKEN_LEN: usize = 32;
+
+/// Collects the tokens a fuzzer can hardly make by byte-level mutations: constant strings
+/// and the immediates compared against (`icmp` and `switch` cases)
+pub fn extract_tokens(module: &Module) -> BTreeSet<Vec<u8>> {
+    let mut tokens = BTreeSet::new();
+    for global in module.get_globals() {
+        if !global.is_constant() {
+            continue;
+        }
+        let Some(BasicValueEnum::ArrayValue(array)) = global.get_initializer() else {
+            continue;
+        };
+        if !array.is_const_string() {
+            continue;
+        }
+        if let Some(string) = array.get_string_constant() {
+            let token = string.to_bytes();
+            if !token.is_empty() && token.len() <= MAX_TOKEN_LEN {
+                tokens.insert(token.to_vec());
+            }
+        }
+    }
+
+    for func in module.get_functions() {
+        if can_skip_instrument(&func) {
+            continue;
+        }
+        for basic_blk in func.get_basic_blocks() {
+            for instr in basic_blk.get_instructions() {
+                let operand_idxs: Vec<u32> = match instr.get_opcode() {
+                    InstructionOpcode::ICmp => vec![0, 1],
+                    // (condition, default label, [case value, case label]*)
+                    InstructionOpcode::Switch => (2..instr.get_num_operands()).step_by(2).collect(),
+                    _ => continue,
+                };
+                for idx in operand_idxs {
+                    if let Some(token) = get_const_int(&instr, idx).and_then(int_to_token) {
+                        tokens.insert(token);
+                    }
+                }
+            }
+        }
+    }
+    tokens
+}
+
+/// Writes tokens as an AFL-style dictionary that the fuzzer reads with `-x`
+pub fn write_dict(tokens: &BTreeSet<Vec<u8>>, path: impl AsRef<Path>) -> Result<()> {
+    let mut dict = String::new();
+    for (idx, token) in tokens.iter().enumerate() {
+        write!(dict, "token_{}=\"", idx)?;
+        for byte in token {
+            write!(dict, "\\x{:02x}", byte)?;
+        }
+        writeln!(dict, "\"")?;
+    }
+    fs::write(path, dict)?;
+    Ok(())
+}
+
+fn get_const_int<'ctx>(instr: &InstructionValue<'ctx>, idx: u32) -> Option<IntValue<'ctx>> {
+    match instr.get_operand(idx)?.left()? {
+        BasicValueEnum::IntValue(value) if value.is_const() => Some(value),
+        _ => None,
+    }
+}
+
+// the value is laid out in the width of its type (little endian), as it is read from the input
+fn int_to_token(value: IntValue) -> Option<Vec<u8>> {
+    let constant = value.get_sign_extended_constant()?;
+    // trivial values are made by byte-level mutations anyway
+    if matches!(constant, -1..=1) {
+        return None;
+    }
+    let width = match value.get_type().get_bit_width() {
+        0..=8 => 1,
+        9..=16 => 2,
+        17..=32 => 4,
+        33..=64 => 8,
+        _ => return None,
+    };
+    Some(constant.to_le_bytes()[..width].to_vec())
+}
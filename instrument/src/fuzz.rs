@@ -1,12 +1,17 @@
 use crate::{
     inkwell_intrinsic::{
-        build_ctros, build_fuzzer_init, build_trace_edge, can_skip_instrument, module_verify,
+        build_ctros, build_fuzzer_init, build_test_one_input_main, build_trace_cmp,
+        build_trace_edge, build_trace_mem_cmp, can_skip_instrument, get_callee_name,
+        get_ptr_operand, module_verify,
     },
     llvm_intrinsic::{cstr_to_str, get_instr_filename},
     module::InstrumentModule,
+    names::{FUZZER_TRACE_MEMCMP, FUZZER_TRACE_STRCMP},
 };
 use anyhow::Result;
-use inkwell::values::{InstructionOpcode, IntValue};
+use inkwell::values::{
+    BasicValueEnum, FunctionValue, InstructionOpcode, InstructionValue, IntValue,
+};
 use inkwell::{builder::Builder, context::Context, module::Module};
 use std::collections::HashSet;
 use uuid::Uuid;
@@ -17,6 +22,95 @@ fn get_rand_value<'ctx>(context: &'ctx Context) -> IntValue<'ctx> {
     i64_typ.const_int(lower, false)
 }
 
+fn get_int_operand<'ctx>(instr: &InstructionValue<'ctx>, idx: u32) -> Option<IntValue<'ctx>> {
+    match instr.get_operand(idx)?.left()? {
+        BasicValueEnum::IntValue(value) => Some(value),
+        _ => None,
+    }
+}
+
+// booleans are solved by flipping bits, and wider integers are not logged
+fn is_loggable_int(value: &IntValue) -> bool {
+    matches!(value.get_type().get_bit_width(), 8..=64)
+}
+
+/// Logs the operands of comparisons (`icmp`, `switch` and calls to `memcmp`-like functions),
+/// which the fuzzer finds in the input and replaces with the other side (input-to-state)
+fn instrument_cmps<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    func: &FunctionValue<'ctx>,
+) -> Result<()> {
+    let instrs: Vec<_> = func
+        .get_basic_blocks()
+        .iter()
+        .flat_map(|basic_blk| basic_blk.get_instructions())
+        .collect();
+    for instr in instrs {
+        match instr.get_opcode() {
+            InstructionOpcode::ICmp => {
+                let (Some(lhs), Some(rhs)) =
+                    (get_int_operand(&instr, 0), get_int_operand(&instr, 1))
+                else {
+                    continue;
+                };
+                if !is_loggable_int(&lhs) || (lhs.is_const() && rhs.is_const()) {
+                    continue;
+                }
+                builder.position_before(&instr);
+                build_trace_cmp(context, module, builder, lhs, rhs)?;
+            }
+            InstructionOpcode::Switch => {
+                // (condition, default label, [case value, case label]*)
+                let Some(cond) = get_int_operand(&instr, 0) else {
+                    continue;
+                };
+                if !is_loggable_int(&cond) || cond.is_const() {
+                    continue;
+                }
+                builder.position_before(&instr);
+                for idx in (2..instr.get_num_operands()).step_by(2) {
+                    if let Some(case) = get_int_operand(&instr, idx) {
+                        build_trace_cmp(context, module, builder, cond, case)?;
+                    }
+                }
+            }
+            InstructionOpcode::Call => {
+                let Some(callee) = get_callee_name(&instr) else {
+                    continue;
+                };
+                // (lhs, rhs[, len])
+                let (trace_func_name, len) = match callee.as_str() {
+                    "memcmp" | "bcmp" => (FUZZER_TRACE_MEMCMP, get_int_operand(&instr, 2)),
+                    "strncmp" | "strncasecmp" => (FUZZER_TRACE_STRCMP, get_int_operand(&instr, 2)),
+                    // bounded by the runtime
+                    "strcmp" | "strcasecmp" => (
+                        FUZZER_TRACE_STRCMP,
+                        Some(context.i64_type().const_all_ones()),
+                    ),
+                    _ => continue,
+                };
+                let Some(len) = len else {
+                    continue;
+                };
+                builder.position_before(&instr);
+                build_trace_mem_cmp(
+                    context,
+                    module,
+                    builder,
+                    trace_func_name,
+                    get_ptr_operand(&instr, 0),
+                    get_ptr_operand(&instr, 1),
+                    len,
+                )?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
 #[derive(Default)]
 pub struct FuzzModule {}
 
@@ -62,7 +156,10 @@ impl InstrumentModule for FuzzModule {
                 }
                 instrumented_blks.insert(basic_blk);
             }
+            instrument_cmps(context, module, builder, &func)?;
         }
+        // added after the edges, so that the loop of persistent mode is not traced
+        build_test_one_input_main(context, module, builder)?;
         // Verify instrumented IRs
         module_verify(module)
     }
@@ -1,22 +1,26 @@
 use crate::{
-    llvm_intrinsic::{cstr_to_str, get_instr_filename, get_instr_loc},
+    llvm_intrinsic::{copy_debug_loc, cstr_to_str, get_instr_filename, get_instr_loc},
     names::*,
     race::{AccessOperation, Lock},
     symbolic::ConstraintSerialized,
 };
 use anyhow::Result;
 use inkwell::{
+    attributes::{Attribute, AttributeLoc},
     builder::Builder,
     context::Context,
     llvm_sys::{self},
-    module::Module,
+    module::{Linkage, Module},
+    targets::TargetData,
+    types::FunctionType,
     values::{
-        AsValueRef, BasicValueEnum::ArrayValue, CallSiteValue, FunctionValue, GlobalValue,
-        InstructionValue, IntValue, PointerValue, StructValue,
+        AsValueRef, BasicMetadataValueEnum, BasicValueEnum::ArrayValue, CallSiteValue,
+        FunctionValue, GlobalValue, InstructionOpcode, InstructionValue, IntValue, PointerValue,
+        StructValue,
     },
-    AddressSpace,
+    AddressSpace, IntPredicate,
 };
-use llvm_sys::core::{LLVMGetAggregateElement, LLVMGetNumOperands};
+use llvm_sys::core::{LLVMGetAggregateElement, LLVMGetNumOperands, LLVMIsAGlobalVariable};
 use std::collections::{BTreeSet, HashMap};
 
 pub fn get_func<'ctx>(module: &Module<'ctx>, func_name: &str) -> Option<FunctionValue<'ctx>> {
@@ -34,6 +38,46 @@ pub fn get_or_build_global_string_ptr<'ctx>(
     }
 }
 
+/// Returns the global string describing where `instr` is in the source (i.e.,
+/// `file:line:col in func`), which the runtime prints when the access is invalid
+pub fn get_or_build_src_loc_str<'ctx>(
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    instr: &InstructionValue<'ctx>,
+) -> Result<GlobalValue<'ctx>> {
+    let filename = match get_instr_filename(instr) {
+        Some(filename) => filename.to_string(),
+        None => cstr_to_str(module.get_source_file_name()),
+    };
+    let func_name = instr
+        .get_parent()
+        .and_then(|basic_blk| basic_blk.get_parent())
+        .map(|func| cstr_to_str(func.get_name()))
+        .unwrap_or_default();
+    let src_loc = match get_instr_loc(instr) {
+        // no debug location is attached
+        (0, _) => format!("{} in {}", filename, func_name),
+        (line, col) => format!("{}:{}:{} in {}", filename, line, col, func_name),
+    };
+    get_or_build_global_string_ptr(module, builder, &src_loc)
+}
+
+pub fn get_target_data(module: &Module) -> Result<TargetData> {
+    let data_layout = module.get_data_layout();
+    Ok(TargetData::create(data_layout.as_str().to_str()?))
+}
+
+/// Whether `alloca` allocates a single object of a type known at compile time
+pub fn is_static_alloca(instr: &InstructionValue) -> bool {
+    // `alloca <ty>, i64 %n` (e.g., VLA) cannot be resized at compile time
+    instr
+        .get_operand(0)
+        .and_then(|operand| operand.left())
+        .filter(|operand| operand.is_int_value())
+        .and_then(|operand| operand.into_int_value().get_zero_extended_constant())
+        == Some(1)
+}
+
 pub fn get_ptr_operand<'ctx>(instr: &InstructionValue<'ctx>, idx: u32) -> PointerValue<'ctx> {
     instr
         .get_operand(idx)
@@ -43,6 +87,16 @@ pub fn get_ptr_operand<'ctx>(instr: &InstructionValue<'ctx>, idx: u32) -> Pointe
         .into_pointer_value()
 }
 
+/// Returns the name of the directly called function of `call` instruction
+pub fn get_callee_name(instr: &InstructionValue) -> Option<String> {
+    if instr.get_opcode() != InstructionOpcode::Call {
+        return None;
+    }
+    // callee is always the last operand
+    let callee = instr.get_operand(instr.get_num_operands() - 1)?.left()?;
+    Some(cstr_to_str(callee.get_name()))
+}
+
 pub fn set_filename<'ctx>(
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
@@ -196,6 +250,60 @@ fn get_trace_edge<'ctx>(context: &'ctx Context, module: &Module<'ctx>) -> Functi
     }
 }
 
+fn get_trace_cmp<'ctx>(context: &'ctx Context, module: &Module<'ctx>) -> FunctionValue<'ctx> {
+    match get_func(module, FUZZER_TRACE_CMP) {
+        Some(func) => func,
+        None => {
+            let trace_cmp = context.void_type().fn_type(
+                &[
+                    context.i64_type().into(),
+                    context.i64_type().into(),
+                    context.i8_type().into(),
+                ],
+                false,
+            );
+            module.add_function(FUZZER_TRACE_CMP, trace_cmp, None)
+        }
+    }
+}
+
+/// `__fuzzer_trace_memcmp` and `__fuzzer_trace_strcmp` share the type (lhs, rhs, len)
+fn get_trace_mem_cmp<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    func_name: &str,
+) -> FunctionValue<'ctx> {
+    match get_func(module, func_name) {
+        Some(func) => func,
+        None => {
+            let trace_mem_cmp = context.void_type().fn_type(
+                &[
+                    context.ptr_type(AddressSpace::default()).into(),
+                    context.ptr_type(AddressSpace::default()).into(),
+                    context.i64_type().into(),
+                ],
+                false,
+            );
+            module.add_function(func_name, trace_mem_cmp, None)
+        }
+    }
+}
+
+fn get_test_one_input_main<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+) -> FunctionValue<'ctx> {
+    match get_func(module, FUZZER_TEST_ONE_INPUT_MAIN) {
+        Some(func) => func,
+        None => {
+            let test_one_input_main = context
+                .i32_type()
+                .fn_type(&[context.ptr_type(AddressSpace::default()).into()], false);
+            module.add_function(FUZZER_TEST_ONE_INPUT_MAIN, test_one_input_main, None)
+        }
+    }
+}
+
 fn get_pthread_self<'ctx>(context: &'ctx Context, module: &Module<'ctx>) -> FunctionValue<'ctx> {
     match get_func(module, PTHREAD_SELF) {
         Some(func) => func,
@@ -335,25 +443,244 @@ pub fn build_cov_hit_batch<'ctx>(
     Ok(())
 }
 
+// must be kept in sync with `asan_runtime`
+const ASAN_SHADOW_SCALE: u64 = 3;
+const ASAN_SHADOW_OFFSET: u64 = 0x7fff8000;
+// an access up to a granule spans at most two granules, which are checked inline
+const ASAN_MAX_INLINE_ACCESS_SIZE: u64 = 8;
+
+fn build_load_shadow<'ctx>(
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+    addr: IntValue<'ctx>,
+) -> Result<IntValue<'ctx>> {
+    let i64_typ = context.i64_type();
+    let shadow_idx =
+        builder.build_right_shift(addr, i64_typ.const_int(ASAN_SHADOW_SCALE, false), false, "")?;
+    let shadow_addr =
+        builder.build_int_add(shadow_idx, i64_typ.const_int(ASAN_SHADOW_OFFSET, false), "")?;
+    let shadow_ptr =
+        builder.build_int_to_ptr(shadow_addr, context.ptr_type(AddressSpace::default()), "")?;
+    Ok(builder
+        .build_load(context.i8_type(), shadow_ptr, "")?
+        .into_int_value())
+}
+
+/// Builds `__asan_mem_check_fast(src_loc, addr, size)`, which reads the shadow bytes of the
+/// first and the last accessed byte of a non-empty access and calls into the runtime only if
+/// either is poisoned.
+/// It is always inlined into the instrumented code.
+fn get_asan_mem_check_fast_func<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+) -> Result<FunctionValue<'ctx>> {
+    if let Some(func) = get_func(module, ASAN_MEM_CHECK_FAST) {
+        return Ok(func);
+    }
+    let asan_mem_check = get_asan_mem_check_func(context, module);
+    let func = module.add_function(
+        ASAN_MEM_CHECK_FAST,
+        asan_mem_check.get_type(),
+        Some(Linkage::Internal),
+    );
+    let always_inline = Attribute::get_named_enum_kind_id("alwaysinline");
+    func.add_attribute(
+        AttributeLoc::Function,
+        context.create_enum_attribute(always_inline, 0),
+    );
+
+    let i64_typ = context.i64_type();
+    let src_loc_ptr = func.get_nth_param(0).unwrap();
+    let ptr = func.get_nth_param(1).unwrap().into_pointer_value();
+    let access_size = func.get_nth_param(2).unwrap().into_int_value();
+    let entry = context.append_basic_block(func, "entry");
+    let check = context.append_basic_block(func, "check");
+    let slow_path = context.append_basic_block(func, "slow_path");
+    let done = context.append_basic_block(func, "done");
+
+    // do not disturb the insertion point of the caller's builder
+    let builder = context.create_builder();
+    builder.position_at_end(entry);
+    // an empty access (e.g., `memcpy(NULL, NULL, 0)`) touches no byte, not even its shadow
+    let is_empty =
+        builder.build_int_compare(IntPredicate::EQ, access_size, i64_typ.const_zero(), "")?;
+    builder.build_conditional_branch(is_empty, done, check)?;
+
+    builder.position_at_end(check);
+    let first_addr = builder.build_ptr_to_int(ptr, i64_typ, "")?;
+    let end_addr = builder.build_int_add(first_addr, access_size, "")?;
+    let last_addr = builder.build_int_sub(end_addr, i64_typ.const_int(1, false), "")?;
+    let first_shadow = build_load_shadow(context, &builder, first_addr)?;
+    let last_shadow = build_load_shadow(context, &builder, last_addr)?;
+    let shadow = builder.build_or(first_shadow, last_shadow, "")?;
+    let is_poisoned =
+        builder.build_int_compare(IntPredicate::NE, shadow, context.i8_type().const_zero(), "")?;
+    // larger ranges may hide a poisoned granule in the middle
+    let is_large = builder.build_int_compare(
+        IntPredicate::UGT,
+        access_size,
+        i64_typ.const_int(ASAN_MAX_INLINE_ACCESS_SIZE, false),
+        "",
+    )?;
+    let need_slow_path = builder.build_or(is_poisoned, is_large, "")?;
+    builder.build_conditional_branch(need_slow_path, slow_path, done)?;
+
+    // the runtime precisely checks partially addressable granules and reports
+    builder.position_at_end(slow_path);
+    builder.build_call(
+        asan_mem_check,
+        &[src_loc_ptr.into(), ptr.into(), access_size.into()],
+        "",
+    )?;
+    builder.build_unconditional_branch(done)?;
+
+    builder.position_at_end(done);
+    builder.build_return(None)?;
+    Ok(func)
+}
+
 pub fn build_asan_mem_check<'ctx>(
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-    filename_str_ptr: &GlobalValue,
+    src_loc_str_ptr: &GlobalValue,
     ptr: PointerValue,
     access_size: IntValue<'ctx>,
-) -> Result<()> {
-    let asan_mem_check = get_asan_mem_check_func(context, module);
-    builder.build_call(
-        asan_mem_check,
+) -> Result<InstructionValue<'ctx>> {
+    let asan_mem_check_fast = get_asan_mem_check_fast_func(context, module)?;
+    let call = builder.build_call(
+        asan_mem_check_fast,
         &[
-            filename_str_ptr.as_pointer_value().into(),
+            src_loc_str_ptr.as_pointer_value().into(),
             ptr.into(),
             access_size.into(),
         ],
         "",
     )?;
-    Ok(())
+    // a call to an inlinable function must carry a debug location. The checked instruction
+    // follows the call, so its location is reused.
+    let call_instr = call.try_as_basic_value().right().unwrap();
+    if let Some(checked_instr) = call_instr.get_next_instruction() {
+        copy_debug_loc(&checked_instr, &call_instr);
+    }
+    Ok(call_instr)
+}
+
+fn get_snprintf<'ctx>(context: &'ctx Context, module: &Module<'ctx>) -> FunctionValue<'ctx> {
+    match get_func(module, SNPRINTF) {
+        Some(func) => func,
+        None => {
+            let ptr_typ = context.ptr_type(AddressSpace::default());
+            let snprintf = context.i32_type().fn_type(
+                &[ptr_typ.into(), context.i64_type().into(), ptr_typ.into()],
+                true,
+            );
+            module.add_function(SNPRINTF, snprintf, None)
+        }
+    }
+}
+
+/// Whether a format has a `%n` conversion, which writes the count through its argument
+fn has_n_conversion(fmt: &[u8]) -> bool {
+    let mut bytes = fmt.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte != b'%' {
+            continue;
+        }
+        // flags, width, precision and length modifiers precede the conversion
+        let conversion = bytes.find(|c| !b"-+ #0123456789.*'$hlLqjzt".contains(c));
+        if conversion == Some(&b'n') {
+            return true;
+        }
+    }
+    false
+}
+
+/// Whether the output of a `sprintf` call can be formatted once more beforehand, which is
+/// the case for a constant format without `%n`. A `va_list` of `vsprintf` is consumed by
+/// formatting it.
+pub fn can_preformat_sprintf(instr: &InstructionValue) -> bool {
+    if get_callee_name(instr).as_deref() != Some(SPRINTF) {
+        return false;
+    }
+    // (dest, fmt, args...)
+    let Some(fmt) = instr.get_operand(1).and_then(|operand| operand.left()) else {
+        return false;
+    };
+    unsafe {
+        if LLVMIsAGlobalVariable(fmt.as_value_ref()).is_null() {
+            return false;
+        }
+        let global = GlobalValue::new(fmt.as_value_ref());
+        if !global.is_constant() {
+            return false;
+        }
+        match global.get_initializer() {
+            Some(ArrayValue(array)) => array
+                .get_string_constant()
+                .is_some_and(|fmt| !has_n_conversion(fmt.to_bytes())),
+            _ => false,
+        }
+    }
+}
+
+/// Bytes written by a `sprintf`-like call returning `written`, including the terminator.
+/// Zero is returned on a formatting error, which writes nothing.
+fn build_sprintf_written_len<'ctx>(
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+    written: IntValue<'ctx>,
+) -> Result<IntValue<'ctx>> {
+    let i64_typ = context.i64_type();
+    let written = builder.build_int_s_extend(written, i64_typ, "")?;
+    let len = builder.build_int_add(written, i64_typ.const_int(1, false), "")?;
+    let is_err = builder.build_int_compare(IntPredicate::SLT, written, i64_typ.const_zero(), "")?;
+    Ok(builder
+        .build_select(is_err, i64_typ.const_zero(), len, "")?
+        .into_int_value())
+}
+
+/// Returns the number of bytes a `sprintf` call writes, formatted beforehand by
+/// `snprintf(NULL, 0, ...)` so that the destination can be checked before it is written.
+/// Only for calls passing `can_preformat_sprintf`.
+pub fn build_sprintf_len<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    instr: &InstructionValue<'ctx>,
+) -> Result<IntValue<'ctx>> {
+    let null = context.ptr_type(AddressSpace::default()).const_null();
+    let mut snprintf_args: Vec<BasicMetadataValueEnum> =
+        vec![null.into(), context.i64_type().const_zero().into()];
+    // (dest, fmt, args...) where the callee is the last operand
+    snprintf_args.extend(
+        (1..instr.get_num_operands() - 1).map(|idx| {
+            BasicMetadataValueEnum::from(instr.get_operand(idx).unwrap().left().unwrap())
+        }),
+    );
+    let written = builder
+        .build_call(get_snprintf(context, module), &snprintf_args, "")?
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_int_value();
+    build_sprintf_written_len(context, builder, written)
+}
+
+/// Returns the number of bytes a `sprintf`-like call has written, from its return value. The
+/// builder must be positioned after the call.
+pub fn build_sprintf_done_len<'ctx>(
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+    instr: &InstructionValue<'ctx>,
+) -> Result<IntValue<'ctx>> {
+    let written = CallSiteValue::try_from(*instr)
+        .unwrap()
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_int_value();
+    build_sprintf_written_len(context, builder, written)
 }
 
 pub fn build_asan_init_redzone<'ctx>(
@@ -374,6 +701,278 @@ pub fn build_asan_init_redzone<'ctx>(
     Ok(())
 }
 
+fn get_or_add_runtime_func<'ctx>(
+    module: &Module<'ctx>,
+    func_name: &str,
+    fn_typ: FunctionType<'ctx>,
+) -> FunctionValue<'ctx> {
+    get_func(module, func_name).unwrap_or_else(|| module.add_function(func_name, fn_typ, None))
+}
+
+/// Builds `__asan_stack_malloc(size)`, returning a fake frame or null
+pub fn build_asan_stack_malloc<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    size: IntValue<'ctx>,
+) -> Result<PointerValue<'ctx>> {
+    let ptr_typ = context.ptr_type(AddressSpace::default());
+    let fn_typ = ptr_typ.fn_type(&[context.i64_type().into()], false);
+    let stack_malloc = get_or_add_runtime_func(module, ASAN_STACK_MALLOC, fn_typ);
+    let fake_frame = builder.build_call(stack_malloc, &[size.into()], "")?;
+    Ok(fake_frame
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_pointer_value())
+}
+
+/// Builds `__asan_stack_free(fake_frame, real_frame, size)`
+pub fn build_asan_stack_free<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    fake_frame: PointerValue<'ctx>,
+    real_frame: PointerValue<'ctx>,
+    size: IntValue<'ctx>,
+) -> Result<()> {
+    let ptr_typ = context.ptr_type(AddressSpace::default());
+    let fn_typ = context.void_type().fn_type(
+        &[ptr_typ.into(), ptr_typ.into(), context.i64_type().into()],
+        false,
+    );
+    let stack_free = get_or_add_runtime_func(module, ASAN_STACK_FREE, fn_typ);
+    builder.build_call(
+        stack_free,
+        &[fake_frame.into(), real_frame.into(), size.into()],
+        "",
+    )?;
+    Ok(())
+}
+
+/// Builds `__asan_poison_stack_scope(ptr, size)` or `__asan_unpoison_stack_scope(ptr, size)`
+pub fn build_asan_stack_scope<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    usable_ptr: PointerValue<'ctx>,
+    usable_size: IntValue<'ctx>,
+    in_scope: bool,
+) -> Result<()> {
+    let fn_typ = context.void_type().fn_type(
+        &[
+            context.ptr_type(AddressSpace::default()).into(),
+            context.i64_type().into(),
+        ],
+        false,
+    );
+    let func_name = if in_scope {
+        ASAN_UNPOISON_STACK_SCOPE
+    } else {
+        ASAN_POISON_STACK_SCOPE
+    };
+    let stack_scope = get_or_add_runtime_func(module, func_name, fn_typ);
+    builder.build_call(stack_scope, &[usable_ptr.into(), usable_size.into()], "")?;
+    Ok(())
+}
+
+/// Builds `__msan_poison(ptr, size)` or `__msan_unpoison(ptr, size)`
+pub fn build_msan_poison<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    ptr: PointerValue<'ctx>,
+    size: IntValue<'ctx>,
+    poison: bool,
+) -> Result<()> {
+    let fn_typ = context.void_type().fn_type(
+        &[
+            context.ptr_type(AddressSpace::default()).into(),
+            context.i64_type().into(),
+        ],
+        false,
+    );
+    let func_name = if poison { MSAN_POISON } else { MSAN_UNPOISON };
+    let msan_poison = get_or_add_runtime_func(module, func_name, fn_typ);
+    builder.build_call(msan_poison, &[ptr.into(), size.into()], "")?;
+    Ok(())
+}
+
+/// Builds `__msan_unpoison_str(ptr)`
+pub fn build_msan_unpoison_str<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    ptr: PointerValue<'ctx>,
+) -> Result<()> {
+    let fn_typ = context
+        .void_type()
+        .fn_type(&[context.ptr_type(AddressSpace::default()).into()], false);
+    let unpoison_str = get_or_add_runtime_func(module, MSAN_UNPOISON_STR, fn_typ);
+    builder.build_call(unpoison_str, &[ptr.into()], "")?;
+    Ok(())
+}
+
+/// Builds `__msan_load_shadow(ptr, size)`, returning the shadow of the loaded value as `i64`
+pub fn build_msan_load_shadow<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    ptr: PointerValue<'ctx>,
+    size: IntValue<'ctx>,
+) -> Result<IntValue<'ctx>> {
+    let i64_typ = context.i64_type();
+    let fn_typ = i64_typ.fn_type(
+        &[
+            context.ptr_type(AddressSpace::default()).into(),
+            i64_typ.into(),
+        ],
+        false,
+    );
+    let load_shadow = get_or_add_runtime_func(module, MSAN_LOAD_SHADOW, fn_typ);
+    let shadow = builder.build_call(load_shadow, &[ptr.into(), size.into()], "")?;
+    Ok(shadow.try_as_basic_value().left().unwrap().into_int_value())
+}
+
+/// Builds `__msan_store_shadow(ptr, size, shadow)`
+pub fn build_msan_store_shadow<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    ptr: PointerValue<'ctx>,
+    size: IntValue<'ctx>,
+    shadow: IntValue<'ctx>,
+) -> Result<()> {
+    let i64_typ = context.i64_type();
+    let fn_typ = context.void_type().fn_type(
+        &[
+            context.ptr_type(AddressSpace::default()).into(),
+            i64_typ.into(),
+            i64_typ.into(),
+        ],
+        false,
+    );
+    let store_shadow = get_or_add_runtime_func(module, MSAN_STORE_SHADOW, fn_typ);
+    builder.build_call(store_shadow, &[ptr.into(), size.into(), shadow.into()], "")?;
+    Ok(())
+}
+
+/// Builds `__msan_copy_shadow(dst, src, size)`
+pub fn build_msan_copy_shadow<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    dst: PointerValue<'ctx>,
+    src: PointerValue<'ctx>,
+    size: IntValue<'ctx>,
+) -> Result<()> {
+    let ptr_typ = context.ptr_type(AddressSpace::default());
+    let fn_typ = context.void_type().fn_type(
+        &[ptr_typ.into(), ptr_typ.into(), context.i64_type().into()],
+        false,
+    );
+    let copy_shadow = get_or_add_runtime_func(module, MSAN_COPY_SHADOW, fn_typ);
+    builder.build_call(copy_shadow, &[dst.into(), src.into(), size.into()], "")?;
+    Ok(())
+}
+
+/// Builds `__msan_set_param_shadow(idx, shadow)` passing the shadow of an argument
+pub fn build_msan_set_param_shadow<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    idx: u32,
+    shadow: IntValue<'ctx>,
+) -> Result<()> {
+    let i32_typ = context.i32_type();
+    let fn_typ = context
+        .void_type()
+        .fn_type(&[i32_typ.into(), context.i64_type().into()], false);
+    let set_param_shadow = get_or_add_runtime_func(module, MSAN_SET_PARAM_SHADOW, fn_typ);
+    builder.build_call(
+        set_param_shadow,
+        &[i32_typ.const_int(idx as u64, false).into(), shadow.into()],
+        "",
+    )?;
+    Ok(())
+}
+
+/// Builds `__msan_get_param_shadow(idx)`, returning the shadow of a parameter as `i64`
+pub fn build_msan_get_param_shadow<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    idx: u32,
+) -> Result<IntValue<'ctx>> {
+    let i32_typ = context.i32_type();
+    let fn_typ = context.i64_type().fn_type(&[i32_typ.into()], false);
+    let get_param_shadow = get_or_add_runtime_func(module, MSAN_GET_PARAM_SHADOW, fn_typ);
+    let shadow = builder.build_call(
+        get_param_shadow,
+        &[i32_typ.const_int(idx as u64, false).into()],
+        "",
+    )?;
+    Ok(shadow.try_as_basic_value().left().unwrap().into_int_value())
+}
+
+/// Builds `__msan_set_retval_shadow(shadow)`
+pub fn build_msan_set_retval_shadow<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    shadow: IntValue<'ctx>,
+) -> Result<()> {
+    let fn_typ = context
+        .void_type()
+        .fn_type(&[context.i64_type().into()], false);
+    let set_retval_shadow = get_or_add_runtime_func(module, MSAN_SET_RETVAL_SHADOW, fn_typ);
+    builder.build_call(set_retval_shadow, &[shadow.into()], "")?;
+    Ok(())
+}
+
+/// Builds `__msan_get_retval_shadow()`, returning the shadow of the returned value as `i64`
+pub fn build_msan_get_retval_shadow<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+) -> Result<IntValue<'ctx>> {
+    let fn_typ = context.i64_type().fn_type(&[], false);
+    let get_retval_shadow = get_or_add_runtime_func(module, MSAN_GET_RETVAL_SHADOW, fn_typ);
+    let shadow = builder.build_call(get_retval_shadow, &[], "")?;
+    Ok(shadow.try_as_basic_value().left().unwrap().into_int_value())
+}
+
+/// Builds `__msan_check(shadow, src_loc, kind)`, which reports if `shadow` is not zero
+pub fn build_msan_check<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    src_loc_str_ptr: &GlobalValue,
+    shadow: IntValue<'ctx>,
+    kind: u8,
+) -> Result<()> {
+    let i8_typ = context.i8_type();
+    let fn_typ = context.void_type().fn_type(
+        &[
+            context.i64_type().into(),
+            context.ptr_type(AddressSpace::default()).into(),
+            i8_typ.into(),
+        ],
+        false,
+    );
+    let msan_check = get_or_add_runtime_func(module, MSAN_CHECK, fn_typ);
+    builder.build_call(
+        msan_check,
+        &[
+            shadow.into(),
+            src_loc_str_ptr.as_pointer_value().into(),
+            i8_typ.const_int(kind as u64, false).into(),
+        ],
+        "",
+    )?;
+    Ok(())
+}
+
 pub fn build_trace_edge<'ctx>(
     context: &'ctx Context,
     module: &Module<'ctx>,
@@ -385,6 +984,47 @@ pub fn build_trace_edge<'ctx>(
     Ok(())
 }
 
+pub fn build_trace_cmp<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    lhs: IntValue<'ctx>,
+    rhs: IntValue<'ctx>,
+) -> Result<()> {
+    // __fuzzer_trace_cmp(i64, i64, i8) - operands are zero-extended, and their size is in bytes
+    let trace_cmp = get_trace_cmp(context, module);
+    let size = lhs.get_type().get_bit_width().div_ceil(8);
+    let lhs = builder.build_int_z_extend_or_bit_cast(lhs, context.i64_type(), "")?;
+    let rhs = builder.build_int_z_extend_or_bit_cast(rhs, context.i64_type(), "")?;
+    builder.build_call(
+        trace_cmp,
+        &[
+            lhs.into(),
+            rhs.into(),
+            context.i8_type().const_int(size as u64, false).into(),
+        ],
+        "",
+    )?;
+    Ok(())
+}
+
+/// Logs the operands of a `memcmp`-like (`FUZZER_TRACE_MEMCMP`) or of a `strcmp`-like
+/// (`FUZZER_TRACE_STRCMP`) call comparing up to `len` bytes
+pub fn build_trace_mem_cmp<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    func_name: &str,
+    lhs: PointerValue<'ctx>,
+    rhs: PointerValue<'ctx>,
+    len: IntValue<'ctx>,
+) -> Result<()> {
+    let trace_mem_cmp = get_trace_mem_cmp(context, module, func_name);
+    let len = builder.build_int_z_extend_or_bit_cast(len, context.i64_type(), "")?;
+    builder.build_call(trace_mem_cmp, &[lhs.into(), rhs.into(), len.into()], "")?;
+    Ok(())
+}
+
 pub fn build_pthread_self<'ctx>(
     context: &'ctx Context,
     module: &Module<'ctx>,
@@ -509,6 +1149,37 @@ pub fn build_fuzzer_init<'ctx>(
     Ok(constructor)
 }
 
+/// Defines `main` of a module defining `LLVMFuzzerTestOneInput` but not `main`, which runs
+/// `LLVMFuzzerTestOneInput` in persistent mode
+pub fn build_test_one_input_main<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+) -> Result<()> {
+    let Some(test_one_input) = get_func(module, LLVM_FUZZER_TEST_ONE_INPUT) else {
+        return Ok(());
+    };
+    if test_one_input.count_basic_blocks() == 0 || get_func(module, MAIN).is_some() {
+        return Ok(());
+    }
+    // main() { return __fuzzer_test_one_input_main(LLVMFuzzerTestOneInput); }
+    let test_one_input_main = get_test_one_input_main(context, module);
+    let main = module.add_function(MAIN, context.i32_type().fn_type(&[], false), None);
+    let entry = context.append_basic_block(main, FUZZER_MAIN_ENTRY);
+    builder.position_at_end(entry);
+    let ret = builder
+        .build_call(
+            test_one_input_main,
+            &[test_one_input.as_global_value().as_pointer_value().into()],
+            "",
+        )?
+        .try_as_basic_value()
+        .left()
+        .unwrap();
+    builder.build_return(Some(&ret))?;
+    Ok(())
+}
+
 pub fn build_symbolic_init<'ctx>(
     context: &'ctx Context,
     module: &Module<'ctx>,
@@ -757,5 +1428,6 @@ pub fn can_skip_instrument<'ctx>(func: &FunctionValue<'ctx>) -> bool {
         || func.get_name().to_string_lossy().starts_with("__asan_")
         || func.get_name().to_string_lossy().starts_with("__fuzzer_")
         || func.get_name().to_string_lossy().starts_with("__symbolic_")
-        || func.get_name().to_string_lossy().starts_with("__race_");
+        || func.get_name().to_string_lossy().starts_with("__race_")
+        || func.get_name().to_string_lossy().starts_with("__msan_");
 }
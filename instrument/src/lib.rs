@@ -1,11 +1,14 @@
 pub mod asan;
+pub mod asan_opt;
 pub mod cfg;
 pub mod cli;
 pub mod coverage;
+pub mod dict;
 pub mod fuzz;
 pub mod inkwell_intrinsic;
 pub mod llvm_intrinsic;
 pub mod module;
+pub mod msan;
 pub mod names;
 pub mod race;
 pub mod symbolic;
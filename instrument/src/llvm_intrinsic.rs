@@ -3,7 +3,10 @@ use inkwell::{
     memory_buffer::MemoryBuffer,
     values::{AnyValue, InstructionOpcode, InstructionValue},
 };
-use llvm_sys::core::{LLVMGetDebugLocColumn, LLVMGetDebugLocFilename, LLVMGetDebugLocLine};
+use llvm_sys::{
+    core::{LLVMGetDebugLocColumn, LLVMGetDebugLocFilename, LLVMGetDebugLocLine},
+    debuginfo::{LLVMInstructionGetDebugLoc, LLVMInstructionSetDebugLoc},
+};
 use std::ffi::CStr;
 use std::path::Path;
 
@@ -38,6 +41,16 @@ pub fn cstr_to_str(cstr: &CStr) -> String {
     cstr.to_string_lossy().into_owned()
 }
 
+/// Attaches the debug location of `from` to `to`, if any
+pub fn copy_debug_loc(from: &InstructionValue, to: &InstructionValue) {
+    unsafe {
+        let loc = LLVMInstructionGetDebugLoc(from.as_value_ref());
+        if !loc.is_null() {
+            LLVMInstructionSetDebugLoc(to.as_value_ref(), loc);
+        }
+    }
+}
+
 fn get_br_loc(instr: &mut InstructionValue) -> u32 {
     loop {
         let loc = get_instr_loc(instr).0;
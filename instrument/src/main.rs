@@ -4,13 +4,23 @@ use instrument::{
     asan::ASANModule,
     cli::{get_args, ModuleTyp},
     coverage::CoverageModule,
+    dict::{extract_tokens, write_dict},
     fuzz::FuzzModule,
     llvm_intrinsic::read_ll,
     module::{instrument, instrument_all},
+    msan::MSANModule,
     race::RaceModule,
     symbolic::SymbolicModule,
 };
 
+// dictionary written next to the instrumented file (e.g., `out.ll` -> `out.dict`)
+fn dict_filename(output_filename: &str) -> String {
+    let stem = output_filename
+        .strip_suffix(".ll")
+        .unwrap_or(output_filename);
+    format!("{}.dict", stem)
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let (input_filename, output_filename, module_typ) = get_args()?;
     let mem_buf = read_ll(&input_filename)?;
@@ -27,7 +37,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let asan_module = ASANModule::default();
             instrument(&asan_module, &context, &module, &builder)?;
         }
+        ModuleTyp::Msan => {
+            let msan_module = MSANModule::default();
+            instrument(&msan_module, &context, &module, &builder)?;
+        }
         ModuleTyp::Fuzz => {
+            // tokens are extracted before instrumentation to exclude the constants it adds
+            write_dict(&extract_tokens(&module), dict_filename(&output_filename))?;
             let fuzz_module = FuzzModule::default();
             instrument(&fuzz_module, &context, &module, &builder)?;
         }
@@ -40,7 +56,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             instrument(&race_module, &context, &module, &builder)?;
         }
         ModuleTyp::All => {
-            instrument_all(&context, &module, &builder)?;
+            write_dict(&extract_tokens(&module), dict_filename(&output_filename))?;
+            instrument_all(&context, &module, &builder, false)?;
         }
     }
 
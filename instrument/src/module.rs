@@ -2,8 +2,8 @@ use anyhow::Result;
 use inkwell::{builder::Builder, context::Context, module::Module};
 
 use crate::{
-    asan::ASANModule, coverage::CoverageModule, fuzz::FuzzModule, race::RaceModule,
-    symbolic::SymbolicModule,
+    asan::ASANModule, coverage::CoverageModule, fuzz::FuzzModule, msan::MSANModule,
+    race::RaceModule, symbolic::SymbolicModule,
 };
 
 pub trait InstrumentModule {
@@ -27,12 +27,14 @@ where
     m.instrument(context, module, builder)
 }
 
+/// Instruments with every module, checking memory with MSAN instead of ASAN if `msan` is set
+/// (both runtimes interpose the allocator, so only one of them can be linked)
 pub fn instrument_all<'ctx>(
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
+    msan: bool,
 ) -> Result<()> {
-    let asan_module = ASANModule::default();
     let fuzz_module = FuzzModule::default();
     let cov_module = CoverageModule::default();
     let symbolic_module = SymbolicModule::default();
@@ -40,7 +42,11 @@ pub fn instrument_all<'ctx>(
     instrument(&race_module, &context, &module, &builder)?;
     instrument(&symbolic_module, &context, &module, &builder)?;
     instrument(&fuzz_module, &context, &module, &builder)?;
-    instrument(&asan_module, &context, &module, &builder)?;
+    if msan {
+        instrument(&MSANModule::default(), &context, &module, &builder)?;
+    } else {
+        instrument(&ASANModule::default(), &context, &module, &builder)?;
+    }
     instrument(&cov_module, &context, &module, &builder)?;
     Ok(())
 }
@@ -0,0 +1,760 @@
+use crate::{
+    inkwell_intrinsic::{
+        build_msan_check, build_msan_copy_shadow, build_msan_get_param_shadow,
+        build_msan_get_retval_shadow, build_msan_load_shadow, build_msan_poison,
+        build_msan_set_param_shadow, build_msan_set_retval_shadow, build_msan_store_shadow,
+        build_msan_unpoison_str, can_skip_instrument, get_callee_name, get_or_build_src_loc_str,
+        get_ptr_operand, get_target_data, is_static_alloca, module_verify,
+    },
+    module::InstrumentModule,
+};
+use anyhow::Result;
+use inkwell::{
+    basic_block::BasicBlock,
+    builder::Builder,
+    context::Context,
+    llvm_sys::prelude::LLVMValueRef,
+    module::Module,
+    targets::TargetData,
+    types::{BasicTypeEnum, IntType},
+    values::{
+        AsValueRef, BasicValueEnum, FunctionValue, InstructionOpcode, InstructionValue, IntValue,
+        PhiValue, PointerValue,
+    },
+    IntPredicate,
+};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+// kinds of `__msan_check`, which must be kept in sync with `msan_runtime`
+const CHECK_BRANCH: u8 = 0;
+const CHECK_POINTER: u8 = 1;
+const CHECK_LIBCALL: u8 = 2;
+
+// library functions writing a null-terminated string to their first argument
+const STRING_WRITERS: [&str; 4] = ["sprintf", "snprintf", "vsprintf", "vsnprintf"];
+
+/// Arguments through which a library function writes
+enum OutParams {
+    Args(&'static [u32]),
+    /// every argument following the format string at the index (e.g., `scanf`)
+    AfterFormat(u32),
+}
+
+/// Library functions writing to memory passed by pointer, which the runtime does not intercept
+/// (e.g., variadic ones). Stack objects passed as their out-parameters are initialized by the
+/// call, while other arguments keep their shadow.
+const OUT_PARAM_WRITERS: [(&str, OutParams); 36] = [
+    ("scanf", OutParams::AfterFormat(0)),
+    ("__isoc99_scanf", OutParams::AfterFormat(0)),
+    ("__isoc23_scanf", OutParams::AfterFormat(0)),
+    ("sscanf", OutParams::AfterFormat(1)),
+    ("__isoc99_sscanf", OutParams::AfterFormat(1)),
+    ("__isoc23_sscanf", OutParams::AfterFormat(1)),
+    ("fscanf", OutParams::AfterFormat(1)),
+    ("__isoc99_fscanf", OutParams::AfterFormat(1)),
+    ("__isoc23_fscanf", OutParams::AfterFormat(1)),
+    ("pread", OutParams::Args(&[1])),
+    ("recv", OutParams::Args(&[1])),
+    ("recvfrom", OutParams::Args(&[1, 4, 5])),
+    ("accept", OutParams::Args(&[1, 2])),
+    ("getsockname", OutParams::Args(&[1, 2])),
+    ("getpeername", OutParams::Args(&[1, 2])),
+    ("getsockopt", OutParams::Args(&[3, 4])),
+    ("socketpair", OutParams::Args(&[3])),
+    ("pipe", OutParams::Args(&[0])),
+    ("stat", OutParams::Args(&[1])),
+    ("lstat", OutParams::Args(&[1])),
+    ("fstat", OutParams::Args(&[1])),
+    ("getline", OutParams::Args(&[0, 1])),
+    ("getdelim", OutParams::Args(&[0, 1])),
+    ("getcwd", OutParams::Args(&[0])),
+    ("time", OutParams::Args(&[0])),
+    ("gettimeofday", OutParams::Args(&[0, 1])),
+    ("clock_gettime", OutParams::Args(&[1])),
+    ("localtime_r", OutParams::Args(&[1])),
+    ("gmtime_r", OutParams::Args(&[1])),
+    ("wait", OutParams::Args(&[0])),
+    ("waitpid", OutParams::Args(&[1])),
+    ("getrlimit", OutParams::Args(&[1])),
+    ("pthread_create", OutParams::Args(&[0])),
+    ("strtol", OutParams::Args(&[1])),
+    ("strtoul", OutParams::Args(&[1])),
+    ("strtod", OutParams::Args(&[1])),
+];
+
+fn is_out_param(callee_name: &str, idx: u32) -> bool {
+    OUT_PARAM_WRITERS
+        .iter()
+        .find(|(name, _)| *name == callee_name)
+        .is_some_and(|(_, out_params)| match out_params {
+            OutParams::Args(indices) => indices.contains(&idx),
+            OutParams::AfterFormat(format_idx) => idx > *format_idx,
+        })
+}
+
+fn is_clean(shadow: IntValue) -> bool {
+    shadow.get_zero_extended_constant() == Some(0)
+}
+
+fn get_successors<'ctx>(basic_blk: &BasicBlock<'ctx>) -> Vec<BasicBlock<'ctx>> {
+    let Some(terminator) = basic_blk.get_terminator() else {
+        return vec![];
+    };
+    (0..terminator.get_num_operands())
+        .filter_map(|idx| terminator.get_operand(idx)?.right())
+        .collect()
+}
+
+/// Returns the blocks reachable from the entry in reverse post-order, so that the definition
+/// of a value is visited before its uses (except for phis)
+fn get_rpo_blocks<'ctx>(func: &FunctionValue<'ctx>) -> Vec<BasicBlock<'ctx>> {
+    let Some(entry) = func.get_first_basic_block() else {
+        return vec![];
+    };
+    let mut visited = HashSet::from([entry]);
+    let mut post_order = vec![];
+    // (block, successors, index of the next successor to visit)
+    let mut stack = vec![(entry, get_successors(&entry), 0)];
+    while let Some((basic_blk, succs, idx)) = stack.last_mut() {
+        match succs.get(*idx).copied() {
+            Some(succ) => {
+                *idx += 1;
+                if visited.insert(succ) {
+                    stack.push((succ, get_successors(&succ), 0));
+                }
+            }
+            None => {
+                post_order.push(*basic_blk);
+                stack.pop();
+            }
+        }
+    }
+    post_order.reverse();
+    post_order
+}
+
+/// Propagates shadows (i.e., which bits of a value are uninitialized) through a function.
+/// Integers are shadowed by an integer of the same width and pointers by `i64`, while the
+/// other values (e.g., floats, vectors, aggregates) are treated as initialized.
+struct ShadowPropagator<'a, 'ctx> {
+    context: &'ctx Context,
+    module: &'a Module<'ctx>,
+    builder: &'a Builder<'ctx>,
+    target_data: &'a TargetData,
+    shadows: HashMap<LLVMValueRef, IntValue<'ctx>>,
+    // shadow phis are filled once the shadows of every incoming value are known
+    phis: Vec<(PhiValue<'ctx>, PhiValue<'ctx>)>,
+}
+
+impl<'a, 'ctx> ShadowPropagator<'a, 'ctx> {
+    fn new(
+        context: &'ctx Context,
+        module: &'a Module<'ctx>,
+        builder: &'a Builder<'ctx>,
+        target_data: &'a TargetData,
+    ) -> Self {
+        ShadowPropagator {
+            context,
+            module,
+            builder,
+            target_data,
+            shadows: HashMap::new(),
+            phis: vec![],
+        }
+    }
+
+    fn get_shadow_typ(&self, typ: BasicTypeEnum<'ctx>) -> Option<IntType<'ctx>> {
+        match typ {
+            BasicTypeEnum::IntType(int_typ) if int_typ.get_bit_width() <= 64 => Some(int_typ),
+            BasicTypeEnum::PointerType(_) => Some(self.context.i64_type()),
+            _ => None,
+        }
+    }
+
+    fn get_result_shadow_typ(&self, instr: &InstructionValue<'ctx>) -> Option<IntType<'ctx>> {
+        BasicTypeEnum::try_from(instr.get_type())
+            .ok()
+            .and_then(|typ| self.get_shadow_typ(typ))
+    }
+
+    /// Values without a propagated shadow (e.g., constants, globals) are initialized
+    fn get_shadow(&self, val: BasicValueEnum<'ctx>) -> Option<IntValue<'ctx>> {
+        let shadow_typ = self.get_shadow_typ(val.get_type())?;
+        Some(
+            self.shadows
+                .get(&val.as_value_ref())
+                .copied()
+                .unwrap_or_else(|| shadow_typ.const_zero()),
+        )
+    }
+
+    fn get_operand_shadow(
+        &self,
+        instr: &InstructionValue<'ctx>,
+        idx: u32,
+    ) -> Option<IntValue<'ctx>> {
+        self.get_shadow(instr.get_operand(idx)?.left()?)
+    }
+
+    fn set_shadow(&mut self, instr: &InstructionValue<'ctx>, shadow: IntValue<'ctx>) {
+        self.shadows.insert(instr.as_value_ref(), shadow);
+    }
+
+    fn position_after(&self, instr: &InstructionValue<'ctx>) {
+        self.builder
+            .position_before(&instr.get_next_instruction().unwrap());
+    }
+
+    fn cast_shadow(&self, shadow: IntValue<'ctx>, typ: IntType<'ctx>) -> Result<IntValue<'ctx>> {
+        let width = shadow.get_type().get_bit_width();
+        Ok(match width.cmp(&typ.get_bit_width()) {
+            Ordering::Less => self.builder.build_int_z_extend(shadow, typ, "")?,
+            Ordering::Greater => self.builder.build_int_truncate(shadow, typ, "")?,
+            Ordering::Equal => shadow,
+        })
+    }
+
+    /// Poisons every bit of `typ` if any bit of `shadow` is poisoned
+    fn spread_shadow(&self, shadow: IntValue<'ctx>, typ: IntType<'ctx>) -> Result<IntValue<'ctx>> {
+        let is_poisoned = self.builder.build_int_compare(
+            IntPredicate::NE,
+            shadow,
+            shadow.get_type().const_zero(),
+            "",
+        )?;
+        Ok(self
+            .builder
+            .build_int_s_extend_or_bit_cast(is_poisoned, typ, "")?)
+    }
+
+    fn build_check(
+        &self,
+        instr: &InstructionValue<'ctx>,
+        shadow: IntValue<'ctx>,
+        kind: u8,
+    ) -> Result<()> {
+        if is_clean(shadow) {
+            return Ok(());
+        }
+        self.builder.position_before(instr);
+        let shadow = self.cast_shadow(shadow, self.context.i64_type())?;
+        let src_loc_str_ptr = get_or_build_src_loc_str(self.module, self.builder, instr)?;
+        build_msan_check(
+            self.context,
+            self.module,
+            self.builder,
+            &src_loc_str_ptr,
+            shadow,
+            kind,
+        )
+    }
+
+    fn build_check_ptr(
+        &self,
+        instr: &InstructionValue<'ctx>,
+        ptr: PointerValue<'ctx>,
+    ) -> Result<()> {
+        match self.get_shadow(ptr.into()) {
+            Some(shadow) => self.build_check(instr, shadow, CHECK_POINTER),
+            None => Ok(()),
+        }
+    }
+
+    fn get_len_operand(&self, instr: &InstructionValue<'ctx>, idx: u32) -> Result<IntValue<'ctx>> {
+        let len = instr
+            .get_operand(idx)
+            .unwrap()
+            .left()
+            .unwrap()
+            .into_int_value();
+        self.builder.position_before(instr);
+        Ok(self
+            .builder
+            .build_int_z_extend_or_bit_cast(len, self.context.i64_type(), "")?)
+    }
+
+    /// Returns the static stack object `ptr` points into along with its size
+    fn get_stack_object(
+        &self,
+        ptr: PointerValue<'ctx>,
+    ) -> Option<(PointerValue<'ctx>, IntValue<'ctx>)> {
+        let mut ptr = ptr;
+        loop {
+            let instr = ptr.as_instruction()?;
+            match instr.get_opcode() {
+                InstructionOpcode::GetElementPtr
+                | InstructionOpcode::BitCast
+                | InstructionOpcode::AddrSpaceCast => ptr = get_ptr_operand(&instr, 0),
+                InstructionOpcode::Alloca if is_static_alloca(&instr) => {
+                    let allocated_typ = instr.get_allocated_type().ok()?;
+                    if !allocated_typ.is_sized() {
+                        return None;
+                    }
+                    let size = self.target_data.get_abi_size(&allocated_typ);
+                    return Some((ptr, self.context.i64_type().const_int(size, false)));
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    /// Shadows of the parameters are passed by the caller through the runtime
+    fn instrument_params(&mut self, func: &FunctionValue<'ctx>) -> Result<()> {
+        let Some(first_instr) = func
+            .get_first_basic_block()
+            .and_then(|basic_blk| basic_blk.get_first_instruction())
+        else {
+            return Ok(());
+        };
+        self.builder.position_before(&first_instr);
+        for (idx, param) in func.get_param_iter().enumerate() {
+            let Some(shadow_typ) = self.get_shadow_typ(param.get_type()) else {
+                continue;
+            };
+            let shadow =
+                build_msan_get_param_shadow(self.context, self.module, self.builder, idx as u32)?;
+            let shadow = self.cast_shadow(shadow, shadow_typ)?;
+            self.shadows.insert(param.as_value_ref(), shadow);
+        }
+        Ok(())
+    }
+
+    fn instrument_func(&mut self, func: &FunctionValue<'ctx>) -> Result<()> {
+        // instructions are collected before any shadow computation is inserted
+        let instrs: Vec<_> = get_rpo_blocks(func)
+            .iter()
+            .flat_map(|basic_blk| basic_blk.get_instructions())
+            .collect();
+        self.instrument_params(func)?;
+        for instr in instrs {
+            self.visit(&instr)?;
+        }
+        self.fill_shadow_phis();
+        Ok(())
+    }
+
+    fn visit(&mut self, instr: &InstructionValue<'ctx>) -> Result<()> {
+        match instr.get_opcode() {
+            InstructionOpcode::Alloca => self.handle_alloca(instr),
+            InstructionOpcode::Load => self.handle_load(instr),
+            InstructionOpcode::Store => self.handle_store(instr),
+            InstructionOpcode::Add
+            | InstructionOpcode::Sub
+            | InstructionOpcode::Mul
+            | InstructionOpcode::UDiv
+            | InstructionOpcode::SDiv
+            | InstructionOpcode::URem
+            | InstructionOpcode::SRem
+            | InstructionOpcode::Xor => self.handle_arith(instr),
+            InstructionOpcode::And | InstructionOpcode::Or => self.handle_bitwise(instr),
+            InstructionOpcode::Shl | InstructionOpcode::LShr | InstructionOpcode::AShr => {
+                self.handle_shift(instr)
+            }
+            InstructionOpcode::ICmp => self.handle_icmp(instr),
+            InstructionOpcode::Trunc
+            | InstructionOpcode::ZExt
+            | InstructionOpcode::SExt
+            | InstructionOpcode::PtrToInt
+            | InstructionOpcode::IntToPtr
+            | InstructionOpcode::BitCast
+            | InstructionOpcode::AddrSpaceCast => self.handle_cast(instr),
+            InstructionOpcode::Select => self.handle_select(instr),
+            InstructionOpcode::GetElementPtr => self.handle_gep(instr),
+            InstructionOpcode::Phi => self.handle_phi(instr),
+            InstructionOpcode::Call => self.handle_call(instr),
+            InstructionOpcode::Br | InstructionOpcode::Switch => self.handle_branch(instr),
+            InstructionOpcode::Return => self.handle_return(instr),
+            _ => Ok(()),
+        }
+    }
+
+    /// Stack objects are uninitialized whenever the function is entered
+    fn handle_alloca(&mut self, instr: &InstructionValue<'ctx>) -> Result<()> {
+        let allocated_typ = instr.get_allocated_type().unwrap();
+        if !allocated_typ.is_sized() || !is_static_alloca(instr) {
+            return Ok(());
+        }
+        let size = self.target_data.get_abi_size(&allocated_typ);
+        let size = self.context.i64_type().const_int(size, false);
+        let ptr = unsafe { PointerValue::new(instr.as_value_ref()) };
+        self.position_after(instr);
+        build_msan_poison(self.context, self.module, self.builder, ptr, size, true)
+    }
+
+    fn handle_load(&mut self, instr: &InstructionValue<'ctx>) -> Result<()> {
+        let ptr = get_ptr_operand(instr, 0);
+        self.build_check_ptr(instr, ptr)?;
+        let Some(shadow_typ) = self.get_result_shadow_typ(instr) else {
+            return Ok(());
+        };
+        let size = self.target_data.get_store_size(&instr.get_type());
+        let size = self.context.i64_type().const_int(size, false);
+        self.position_after(instr);
+        let shadow = build_msan_load_shadow(self.context, self.module, self.builder, ptr, size)?;
+        let shadow = self.cast_shadow(shadow, shadow_typ)?;
+        self.set_shadow(instr, shadow);
+        Ok(())
+    }
+
+    fn handle_store(&mut self, instr: &InstructionValue<'ctx>) -> Result<()> {
+        let val = instr.get_operand(0).unwrap().left().unwrap();
+        let ptr = get_ptr_operand(instr, 1);
+        self.build_check_ptr(instr, ptr)?;
+        let size = self.target_data.get_store_size(&val.get_type());
+        let size = self.context.i64_type().const_int(size, false);
+        self.builder.position_before(instr);
+        match self.get_shadow(val) {
+            Some(shadow) => {
+                let shadow = self.cast_shadow(shadow, self.context.i64_type())?;
+                build_msan_store_shadow(self.context, self.module, self.builder, ptr, size, shadow)
+            }
+            // values without a shadow are initialized as a whole
+            None => build_msan_poison(self.context, self.module, self.builder, ptr, size, false),
+        }
+    }
+
+    /// A result bit may depend on any operand bit, so any poisoned bit poisons the result
+    /// (approximately, as in MSan)
+    fn handle_arith(&mut self, instr: &InstructionValue<'ctx>) -> Result<()> {
+        let (Some(lhs_shadow), Some(rhs_shadow)) = (
+            self.get_operand_shadow(instr, 0),
+            self.get_operand_shadow(instr, 1),
+        ) else {
+            return Ok(());
+        };
+        if is_clean(lhs_shadow) && is_clean(rhs_shadow) {
+            return Ok(());
+        }
+        self.position_after(instr);
+        let shadow = self.builder.build_or(lhs_shadow, rhs_shadow, "")?;
+        self.set_shadow(instr, shadow);
+        Ok(())
+    }
+
+    /// An initialized `0` operand of `and` (or `1` of `or`) determines the result bit on its
+    /// own, thus `S = (S1 & S2) | (V1 & S2) | (S1 & V2)` with `V` inverted for `or`
+    fn handle_bitwise(&mut self, instr: &InstructionValue<'ctx>) -> Result<()> {
+        let (Some(lhs_shadow), Some(rhs_shadow)) = (
+            self.get_operand_shadow(instr, 0),
+            self.get_operand_shadow(instr, 1),
+        ) else {
+            return Ok(());
+        };
+        if is_clean(lhs_shadow) && is_clean(rhs_shadow) {
+            return Ok(());
+        }
+        let lhs = instr
+            .get_operand(0)
+            .unwrap()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let rhs = instr
+            .get_operand(1)
+            .unwrap()
+            .left()
+            .unwrap()
+            .into_int_value();
+        self.position_after(instr);
+        let (lhs, rhs) = if instr.get_opcode() == InstructionOpcode::Or {
+            (
+                self.builder.build_not(lhs, "")?,
+                self.builder.build_not(rhs, "")?,
+            )
+        } else {
+            (lhs, rhs)
+        };
+        let both = self.builder.build_and(lhs_shadow, rhs_shadow, "")?;
+        let lhs_only = self.builder.build_and(lhs_shadow, rhs, "")?;
+        let rhs_only = self.builder.build_and(lhs, rhs_shadow, "")?;
+        let shadow = self.builder.build_or(both, lhs_only, "")?;
+        let shadow = self.builder.build_or(shadow, rhs_only, "")?;
+        self.set_shadow(instr, shadow);
+        Ok(())
+    }
+
+    /// The shadow is shifted along with the value, while a poisoned shift amount poisons all
+    fn handle_shift(&mut self, instr: &InstructionValue<'ctx>) -> Result<()> {
+        let (Some(lhs_shadow), Some(rhs_shadow)) = (
+            self.get_operand_shadow(instr, 0),
+            self.get_operand_shadow(instr, 1),
+        ) else {
+            return Ok(());
+        };
+        if is_clean(lhs_shadow) && is_clean(rhs_shadow) {
+            return Ok(());
+        }
+        let rhs = instr
+            .get_operand(1)
+            .unwrap()
+            .left()
+            .unwrap()
+            .into_int_value();
+        self.position_after(instr);
+        let shifted = match instr.get_opcode() {
+            InstructionOpcode::Shl => self.builder.build_left_shift(lhs_shadow, rhs, "")?,
+            InstructionOpcode::AShr => self.builder.build_right_shift(lhs_shadow, rhs, true, "")?,
+            _ => self.builder.build_right_shift(lhs_shadow, rhs, false, "")?,
+        };
+        let spread = self.spread_shadow(rhs_shadow, lhs_shadow.get_type())?;
+        let shadow = self.builder.build_or(shifted, spread, "")?;
+        self.set_shadow(instr, shadow);
+        Ok(())
+    }
+
+    fn handle_icmp(&mut self, instr: &InstructionValue<'ctx>) -> Result<()> {
+        let (Some(lhs_shadow), Some(rhs_shadow)) = (
+            self.get_operand_shadow(instr, 0),
+            self.get_operand_shadow(instr, 1),
+        ) else {
+            return Ok(());
+        };
+        // comparing vectors results in a vector
+        let Some(shadow_typ) = self.get_result_shadow_typ(instr) else {
+            return Ok(());
+        };
+        if is_clean(lhs_shadow) && is_clean(rhs_shadow) {
+            return Ok(());
+        }
+        self.position_after(instr);
+        let shadow = self.builder.build_or(lhs_shadow, rhs_shadow, "")?;
+        let shadow = self.spread_shadow(shadow, shadow_typ)?;
+        self.set_shadow(instr, shadow);
+        Ok(())
+    }
+
+    fn handle_cast(&mut self, instr: &InstructionValue<'ctx>) -> Result<()> {
+        let (Some(shadow_typ), Some(src_shadow)) = (
+            self.get_result_shadow_typ(instr),
+            self.get_operand_shadow(instr, 0),
+        ) else {
+            return Ok(());
+        };
+        if is_clean(src_shadow) {
+            return Ok(());
+        }
+        self.position_after(instr);
+        let shadow = match instr.get_opcode() {
+            // the shadow of the sign bit is extended as well
+            InstructionOpcode::SExt => self
+                .builder
+                .build_int_s_extend(src_shadow, shadow_typ, "")?,
+            _ => self.cast_shadow(src_shadow, shadow_typ)?,
+        };
+        self.set_shadow(instr, shadow);
+        Ok(())
+    }
+
+    fn handle_select(&mut self, instr: &InstructionValue<'ctx>) -> Result<()> {
+        let Some(shadow_typ) = self.get_result_shadow_typ(instr) else {
+            return Ok(());
+        };
+        let cond = instr
+            .get_operand(0)
+            .unwrap()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let (Some(cond_shadow), Some(true_shadow), Some(false_shadow)) = (
+            self.get_shadow(cond.into()),
+            self.get_operand_shadow(instr, 1),
+            self.get_operand_shadow(instr, 2),
+        ) else {
+            return Ok(());
+        };
+        if is_clean(cond_shadow) && is_clean(true_shadow) && is_clean(false_shadow) {
+            return Ok(());
+        }
+        self.position_after(instr);
+        let selected = self
+            .builder
+            .build_select(cond, true_shadow, false_shadow, "")?
+            .into_int_value();
+        // either value may be chosen by a poisoned condition
+        let spread = self.spread_shadow(cond_shadow, shadow_typ)?;
+        let shadow = self.builder.build_or(selected, spread, "")?;
+        self.set_shadow(instr, shadow);
+        Ok(())
+    }
+
+    fn handle_gep(&mut self, instr: &InstructionValue<'ctx>) -> Result<()> {
+        let Some(shadow_typ) = self.get_result_shadow_typ(instr) else {
+            return Ok(());
+        };
+        // the base pointer and every index
+        let operand_shadows: Vec<_> = (0..instr.get_num_operands())
+            .filter_map(|idx| self.get_operand_shadow(instr, idx))
+            .filter(|shadow| !is_clean(*shadow))
+            .collect();
+        if operand_shadows.is_empty() {
+            return Ok(());
+        }
+        self.position_after(instr);
+        let mut shadow = shadow_typ.const_zero();
+        for operand_shadow in operand_shadows {
+            let operand_shadow = self.cast_shadow(operand_shadow, shadow_typ)?;
+            shadow = self.builder.build_or(shadow, operand_shadow, "")?;
+        }
+        self.set_shadow(instr, shadow);
+        Ok(())
+    }
+
+    fn handle_phi(&mut self, instr: &InstructionValue<'ctx>) -> Result<()> {
+        let Some(shadow_typ) = self.get_result_shadow_typ(instr) else {
+            return Ok(());
+        };
+        self.builder.position_before(instr);
+        let shadow_phi = self.builder.build_phi(shadow_typ, "")?;
+        self.set_shadow(instr, shadow_phi.as_basic_value().into_int_value());
+        self.phis
+            .push((PhiValue::try_from(*instr).unwrap(), shadow_phi));
+        Ok(())
+    }
+
+    fn fill_shadow_phis(&mut self) {
+        for (phi, shadow_phi) in &self.phis {
+            let shadow_typ = shadow_phi.as_basic_value().get_type().into_int_type();
+            for idx in 0..phi.count_incoming() {
+                let (val, basic_blk) = phi.get_incoming(idx).unwrap();
+                let shadow = self
+                    .get_shadow(val)
+                    .unwrap_or_else(|| shadow_typ.const_zero());
+                shadow_phi.add_incoming(&[(&shadow, basic_blk)]);
+            }
+        }
+    }
+
+    fn handle_call(&mut self, instr: &InstructionValue<'ctx>) -> Result<()> {
+        let callee_name = get_callee_name(instr).unwrap_or_default();
+        if callee_name.starts_with("llvm.memcpy.") || callee_name.starts_with("llvm.memmove.") {
+            // (dest, src, len, isvolatile)
+            let len = self.get_len_operand(instr, 2)?;
+            let dst = get_ptr_operand(instr, 0);
+            let src = get_ptr_operand(instr, 1);
+            return build_msan_copy_shadow(self.context, self.module, self.builder, dst, src, len);
+        }
+        if callee_name.starts_with("llvm.memset.") {
+            // (dest, val, len, isvolatile)
+            let len = self.get_len_operand(instr, 2)?;
+            let dst = get_ptr_operand(instr, 0);
+            return build_msan_poison(self.context, self.module, self.builder, dst, len, false);
+        }
+        if callee_name.starts_with("llvm.") {
+            return Ok(());
+        }
+        match self.module.get_function(&callee_name) {
+            Some(callee) if !can_skip_instrument(&callee) => {
+                self.handle_instrumented_call(instr, callee.count_params())
+            }
+            Some(_) => self.handle_lib_call(instr, &callee_name),
+            // results of indirect calls are treated as initialized
+            None => Ok(()),
+        }
+    }
+
+    fn handle_instrumented_call(
+        &mut self,
+        instr: &InstructionValue<'ctx>,
+        num_params: u32,
+    ) -> Result<()> {
+        for idx in 0..num_params {
+            let Some(shadow) = self.get_operand_shadow(instr, idx) else {
+                continue;
+            };
+            // the callee has taken the shadows of the previous call
+            if is_clean(shadow) {
+                continue;
+            }
+            self.builder.position_before(instr);
+            let shadow = self.cast_shadow(shadow, self.context.i64_type())?;
+            build_msan_set_param_shadow(self.context, self.module, self.builder, idx, shadow)?;
+        }
+        let Some(shadow_typ) = self.get_result_shadow_typ(instr) else {
+            return Ok(());
+        };
+        self.position_after(instr);
+        let shadow = build_msan_get_retval_shadow(self.context, self.module, self.builder)?;
+        let shadow = self.cast_shadow(shadow, shadow_typ)?;
+        self.set_shadow(instr, shadow);
+        Ok(())
+    }
+
+    /// Uninstrumented functions (e.g., libc) may branch on any of their arguments, thus they
+    /// are checked at the call. Their results are treated as initialized.
+    fn handle_lib_call(&mut self, instr: &InstructionValue<'ctx>, callee_name: &str) -> Result<()> {
+        // callee is the last operand
+        let num_args = instr.get_num_operands() - 1;
+        for idx in 0..num_args {
+            if let Some(shadow) = self.get_operand_shadow(instr, idx) {
+                self.build_check(instr, shadow, CHECK_LIBCALL)?;
+            }
+        }
+        self.position_after(instr);
+        if STRING_WRITERS.contains(&callee_name) {
+            let dst = get_ptr_operand(instr, 0);
+            build_msan_unpoison_str(self.context, self.module, self.builder, dst)?;
+        }
+        for idx in (0..num_args).filter(|idx| is_out_param(callee_name, *idx)) {
+            let Some(arg) = instr.get_operand(idx).and_then(|operand| operand.left()) else {
+                continue;
+            };
+            if !arg.is_pointer_value() {
+                continue;
+            }
+            if let Some((ptr, size)) = self.get_stack_object(arg.into_pointer_value()) {
+                build_msan_poison(self.context, self.module, self.builder, ptr, size, false)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_branch(&mut self, instr: &InstructionValue<'ctx>) -> Result<()> {
+        // unconditional branch has the destination as its only operand
+        if instr.get_opcode() == InstructionOpcode::Br && instr.get_num_operands() != 3 {
+            return Ok(());
+        }
+        match self.get_operand_shadow(instr, 0) {
+            Some(shadow) => self.build_check(instr, shadow, CHECK_BRANCH),
+            None => Ok(()),
+        }
+    }
+
+    /// The shadow of the returned value is always set, as the caller takes it after the call
+    fn handle_return(&mut self, instr: &InstructionValue<'ctx>) -> Result<()> {
+        let Some(shadow) = self.get_operand_shadow(instr, 0) else {
+            return Ok(());
+        };
+        self.builder.position_before(instr);
+        let shadow = self.cast_shadow(shadow, self.context.i64_type())?;
+        build_msan_set_retval_shadow(self.context, self.module, self.builder, shadow)
+    }
+}
+
+/// Tracks uninitialized memory as MSan does. Loads and stores move shadows between memory
+/// (kept by `msan_runtime`) and values, and a use of a poisoned value in a branch, as a
+/// pointer or as an argument of a library call is reported. It intercepts `malloc` on its
+/// own, thus it is not combined with `ASANModule`.
+#[derive(Default)]
+pub struct MSANModule {}
+
+impl InstrumentModule for MSANModule {
+    fn instrument<'ctx>(
+        &self,
+        context: &'ctx Context,
+        module: &Module<'ctx>,
+        builder: &Builder<'ctx>,
+    ) -> Result<()> {
+        let target_data = get_target_data(module)?;
+        let funcs: Vec<_> = module.get_functions().collect();
+        for func in funcs {
+            // Skip funcs without bodies or those we've added
+            if can_skip_instrument(&func) {
+                continue;
+            }
+            ShadowPropagator::new(context, module, builder, &target_data).instrument_func(&func)?;
+        }
+        // Verify instrumented IRs
+        module_verify(module)
+    }
+}
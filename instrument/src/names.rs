@@ -18,12 +18,38 @@ pub const COV_SRC_MAPPING_LINES_LINES: &str = "__cov_src_mapping_lines_lines";
 pub const COV_SRC_MAPPING_LINES_LINES_PTR: &str = "__cov_src_mapping_lines_lines_ptr";
 
 pub const ASAN_MEM_CHECK: &str = "__asan_mem_check";
+pub const ASAN_MEM_CHECK_FAST: &str = "__asan_mem_check_fast";
 pub const ASAN_MEM_INIT_REDZONE: &str = "__asan_init_redzone";
+pub const ASAN_STACK_MALLOC: &str = "__asan_stack_malloc";
+pub const ASAN_STACK_FREE: &str = "__asan_stack_free";
+pub const ASAN_POISON_STACK_SCOPE: &str = "__asan_poison_stack_scope";
+pub const ASAN_UNPOISON_STACK_SCOPE: &str = "__asan_unpoison_stack_scope";
+pub const SNPRINTF: &str = "snprintf";
+pub const SPRINTF: &str = "sprintf";
+
+pub const MSAN_POISON: &str = "__msan_poison";
+pub const MSAN_UNPOISON: &str = "__msan_unpoison";
+pub const MSAN_UNPOISON_STR: &str = "__msan_unpoison_str";
+pub const MSAN_LOAD_SHADOW: &str = "__msan_load_shadow";
+pub const MSAN_STORE_SHADOW: &str = "__msan_store_shadow";
+pub const MSAN_COPY_SHADOW: &str = "__msan_copy_shadow";
+pub const MSAN_SET_PARAM_SHADOW: &str = "__msan_set_param_shadow";
+pub const MSAN_GET_PARAM_SHADOW: &str = "__msan_get_param_shadow";
+pub const MSAN_SET_RETVAL_SHADOW: &str = "__msan_set_retval_shadow";
+pub const MSAN_GET_RETVAL_SHADOW: &str = "__msan_get_retval_shadow";
+pub const MSAN_CHECK: &str = "__msan_check";
 
 pub const FUZZER_MODULE_INIT: &str = "__fuzzer_module_init";
 pub const FUZZER_INIT_ENTRY: &str = "__fuzzer_init_entry";
 pub const FUZZER_FORKSERVER_INIT: &str = "__fuzzer_forkserver_init";
 pub const FUZZER_TRACE_EDGE: &str = "__fuzzer_trace_edge";
+pub const FUZZER_TRACE_CMP: &str = "__fuzzer_trace_cmp";
+pub const FUZZER_TRACE_MEMCMP: &str = "__fuzzer_trace_memcmp";
+pub const FUZZER_TRACE_STRCMP: &str = "__fuzzer_trace_strcmp";
+pub const FUZZER_TEST_ONE_INPUT_MAIN: &str = "__fuzzer_test_one_input_main";
+pub const FUZZER_MAIN_ENTRY: &str = "__fuzzer_main_entry";
+pub const LLVM_FUZZER_TEST_ONE_INPUT: &str = "LLVMFuzzerTestOneInput";
+pub const MAIN: &str = "main";
 
 pub const SYMBOLIC_MAKE_VAR: &str = "__make_symbolic";
 pub const SYMBOLIC_MODULE_ADD_SYM: &str = "__symbolic_module_add_sym";
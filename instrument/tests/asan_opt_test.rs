@@ -0,0 +1,166 @@
+use inkwell::values::InstructionOpcode::{Call, Load, Store};
+use inkwell::{
+    context::Context, memory_buffer::MemoryBuffer, module::Module, values::CallSiteValue,
+};
+use instrument::{
+    asan::ASANModule, asan_opt::plan_checks, inkwell_intrinsic::get_target_data,
+    llvm_intrinsic::cstr_to_str, module::instrument, names::ASAN_MEM_CHECK_FAST,
+};
+
+const IR: &str = r#"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-i128:128-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+@global = global [4 x i32] zeroinitializer
+
+declare void @opaque()
+
+define i32 @const_in_bounds(i64 %idx) {
+entry:
+  %arr = alloca [4 x i32], align 16
+  %in = getelementptr inbounds [4 x i32], ptr %arr, i64 0, i64 3
+  store i32 1, ptr %in
+  %out = getelementptr inbounds [4 x i32], ptr %arr, i64 0, i64 4
+  store i32 2, ptr %out
+  %var = getelementptr inbounds [4 x i32], ptr %arr, i64 0, i64 %idx
+  store i32 3, ptr %var
+  %val = load i32, ptr getelementptr inbounds ([4 x i32], ptr @global, i64 0, i64 1)
+  ret i32 %val
+}
+
+define i64 @dominated(ptr %ptr) {
+entry:
+  %a = load i32, ptr %ptr
+  %b = load i32, ptr %ptr
+  %c = load i64, ptr %ptr
+  call void @opaque()
+  %d = load i32, ptr %ptr
+  br label %next
+
+next:
+  %e = load i32, ptr %ptr
+  ret i64 %c
+}
+
+define void @invariant(ptr %ptr, i32 %n) {
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %inc, %loop ]
+  store i32 %i, ptr %ptr
+  %inc = add i32 %i, 1
+  %cond = icmp slt i32 %inc, %n
+  br i1 %cond, label %loop, label %exit
+
+exit:
+  ret void
+}
+
+define void @invariant_with_call(ptr %ptr, i32 %n) {
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %inc, %loop ]
+  store i32 %i, ptr %ptr
+  call void @opaque()
+  %inc = add i32 %i, 1
+  %cond = icmp slt i32 %inc, %n
+  br i1 %cond, label %loop, label %exit
+
+exit:
+  ret void
+}
+"#;
+
+fn load_module(context: &Context) -> Module<'_> {
+    let mem_buf = MemoryBuffer::create_from_memory_range_copy(IR.as_bytes(), "asan_opt");
+    context.create_module_from_ir(mem_buf).unwrap()
+}
+
+/// Returns whether the check of each load and store of `func_name` is dropped, in order
+fn get_elided(module: &Module, func_name: &str) -> Vec<bool> {
+    let func = module.get_function(func_name).unwrap();
+    let plan = plan_checks(&func, &get_target_data(module).unwrap());
+    func.get_basic_blocks()
+        .iter()
+        .flat_map(|basic_blk| basic_blk.get_instructions())
+        .filter(|instr| matches!(instr.get_opcode(), Load | Store))
+        .map(|instr| plan.is_elided(&instr))
+        .collect()
+}
+
+fn count_calls(module: &Module, func_name: &str, callee: &str) -> usize {
+    let func = module.get_function(func_name).unwrap();
+    func.get_basic_blocks()
+        .iter()
+        .flat_map(|basic_blk| basic_blk.get_instructions())
+        .filter(|instr| instr.get_opcode() == Call)
+        .filter_map(|instr| TryInto::<CallSiteValue>::try_into(instr).ok())
+        .filter(|callsite| cstr_to_str(callsite.get_called_fn_value().get_name()) == callee)
+        .count()
+}
+
+#[test]
+fn test_const_in_bounds() {
+    let context = Context::create();
+    let module = load_module(&context);
+    // in bounds, one past the end, a variable index, and in bounds of a global
+    assert_eq!(
+        get_elided(&module, "const_in_bounds"),
+        [true, false, false, true]
+    );
+}
+
+#[test]
+fn test_dominated() {
+    let context = Context::create();
+    let module = load_module(&context);
+    // a wider access is checked again, and so is an access after a call which may free
+    // the pointer. The access in the next block is covered by the one after the call.
+    assert_eq!(
+        get_elided(&module, "dominated"),
+        [false, true, false, false, true]
+    );
+}
+
+#[test]
+fn test_loop_invariant() {
+    let context = Context::create();
+    let module = load_module(&context);
+    let func = module.get_function("invariant").unwrap();
+    let plan = plan_checks(&func, &get_target_data(&module).unwrap());
+    assert_eq!(plan.hoisted.len(), 1);
+    let (instr, preheader) = plan.hoisted[0];
+    assert_eq!(instr.get_opcode(), Store);
+    assert_eq!(cstr_to_str(preheader.get_name()), "entry");
+    assert_eq!(get_elided(&module, "invariant"), [true]);
+
+    // a call in the loop may free the pointer in any iteration
+    let func = module.get_function("invariant_with_call").unwrap();
+    let plan = plan_checks(&func, &get_target_data(&module).unwrap());
+    assert!(plan.hoisted.is_empty());
+    assert_eq!(get_elided(&module, "invariant_with_call"), [false]);
+}
+
+#[test]
+fn test_disable_check_opt() {
+    let count_checks = |asan_module: &ASANModule| {
+        let context = Context::create();
+        let module = load_module(&context);
+        let builder = context.create_builder();
+        instrument(asan_module, &context, &module, &builder).unwrap();
+        ["const_in_bounds", "dominated", "invariant"]
+            .iter()
+            .map(|func_name| count_calls(&module, func_name, ASAN_MEM_CHECK_FAST))
+            .collect::<Vec<_>>()
+    };
+    let optimized = count_checks(&ASANModule::default());
+    std::env::set_var("ASAN_DISABLE_CHECK_OPT", "1");
+    let unoptimized = count_checks(&ASANModule::default());
+    std::env::remove_var("ASAN_DISABLE_CHECK_OPT");
+    // every load and store is checked in place once disabled
+    assert_eq!(optimized, [2, 3, 1]);
+    assert_eq!(unoptimized, [4, 5, 1]);
+}
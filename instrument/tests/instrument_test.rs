@@ -1,9 +1,14 @@
 use inkwell::values::InstructionOpcode::Call;
 use inkwell::{context::Context, values::CallSiteValue};
 use instrument::{
+    asan::ASANModule,
     coverage::CoverageModule,
     module::instrument,
-    names::{COV_HIT_BATCH, COV_MAPPING_SRC},
+    msan::MSANModule,
+    names::{
+        ASAN_MEM_CHECK_FAST, COV_HIT_BATCH, COV_MAPPING_SRC, MSAN_CHECK, MSAN_GET_PARAM_SHADOW,
+        MSAN_SET_PARAM_SHADOW, SNPRINTF,
+    },
 };
 use instrument::{llvm_intrinsic::cstr_to_str, names::COV_INIT_ENTRY};
 mod util;
@@ -56,3 +61,106 @@ fn test_instrument_coverage() {
     // source mapping : one instrumentation should exist per single file
     assert_eq!(src_mapping_call_found, 1);
 }
+
+#[test]
+fn test_instrument_msan() {
+    let src = r#"
+    int add(int a, int b) {
+        return a + b;
+    }
+
+    int main() {
+        int a;
+        int b = 2;
+        if (add(a, b) > 0) {
+            return 1;
+        }
+        return 0;
+    }
+"#;
+
+    let mem_buf = util::load_ir(&src);
+    let context = Context::create();
+    let module = context.create_module_from_ir(mem_buf).unwrap();
+    let builder = context.create_builder();
+    let msan_module = MSANModule::default();
+    instrument(&msan_module, &context, &module, &builder).unwrap();
+
+    let count_calls = |func_name: &str, runtime_func: &str| {
+        let func = module.get_function(func_name).unwrap();
+        func.get_basic_blocks()
+            .iter()
+            .flat_map(|basic_blk| basic_blk.get_instructions())
+            .filter(|instr| instr.get_opcode() == Call)
+            .filter_map(|instr| TryInto::<CallSiteValue>::try_into(instr).ok())
+            .filter(|callsite| {
+                cstr_to_str(callsite.get_called_fn_value().get_name()) == runtime_func
+            })
+            .count()
+    };
+    // the shadow of `a` flows into `add` and back through its return value to the branch
+    assert_eq!(count_calls("add", MSAN_GET_PARAM_SHADOW), 2);
+    assert!(count_calls("main", MSAN_SET_PARAM_SHADOW) >= 1);
+    assert!(count_calls("main", MSAN_CHECK) >= 1);
+}
+
+#[test]
+fn test_instrument_asan_sprintf() {
+    let src = r#"
+    #include <stdarg.h>
+    #include <stdio.h>
+
+    void format_const(char *buf, int x) {
+        sprintf(buf, "%d", x);
+    }
+
+    void format_count(char *buf, int *count) {
+        sprintf(buf, "ab%n", count);
+    }
+
+    void format_va(char *buf, const char *fmt, va_list args) {
+        vsprintf(buf, fmt, args);
+    }
+"#;
+
+    let mem_buf = util::load_ir(&src);
+    let context = Context::create();
+    let module = context.create_module_from_ir(mem_buf).unwrap();
+    let builder = context.create_builder();
+    let asan_module = ASANModule::default();
+    instrument(&asan_module, &context, &module, &builder).unwrap();
+
+    let callees = |func_name: &str| -> Vec<String> {
+        let func = module.get_function(func_name).unwrap();
+        func.get_basic_blocks()
+            .iter()
+            .flat_map(|basic_blk| basic_blk.get_instructions())
+            .filter(|instr| instr.get_opcode() == Call)
+            .filter_map(|instr| TryInto::<CallSiteValue>::try_into(instr).ok())
+            .map(|callsite| cstr_to_str(callsite.get_called_fn_value().get_name()))
+            .collect()
+    };
+    let position =
+        |callees: &[String], name: &str| callees.iter().position(|callee| callee == name);
+    let last_check = |callees: &[String]| {
+        callees
+            .iter()
+            .rposition(|callee| callee == ASAN_MEM_CHECK_FAST)
+    };
+
+    // a constant format is formatted beforehand, and the destination is checked before the call
+    let format_const = callees("format_const");
+    let sprintf = position(&format_const, "sprintf").unwrap();
+    assert!(position(&format_const, SNPRINTF).unwrap() < sprintf);
+    assert!(last_check(&format_const).unwrap() < sprintf);
+
+    // `%n` is not run twice, and a `va_list` is not consumed, so these are checked after the call
+    let format_count = callees("format_count");
+    assert_eq!(position(&format_count, SNPRINTF), None);
+    assert!(last_check(&format_count).unwrap() > position(&format_count, "sprintf").unwrap());
+
+    let format_va = callees("format_va");
+    assert_eq!(position(&format_va, SNPRINTF), None);
+    assert_eq!(position(&format_va, "vsnprintf"), None);
+    assert!(last_check(&format_va).unwrap() > position(&format_va, "vsprintf").unwrap());
+}
@@ -0,0 +1,4 @@
+pub mod msan_hook;
+pub mod msan_intrinsic;
+pub mod msan_runtime;
+pub mod msan_shadow;
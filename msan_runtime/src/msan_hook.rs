@@ -0,0 +1,146 @@
+// interceptors are called by C code under the contracts of the libc functions they replace
+#![allow(clippy::missing_safety_doc)]
+
+use crate::{
+    msan_intrinsic::*,
+    msan_shadow::{copy_shadow, poison, unpoison},
+};
+use libc::{c_char, c_int, c_void, size_t, ssize_t, FILE};
+pub use sanitizer_common::reentrance::{is_reentered, with_reentered, MALLOC_REENTERED};
+use std::ptr;
+
+/// Heap memory starts out uninitialized
+unsafe fn poison_chunk(usable_ptr: *mut c_void, size: usize) -> *mut c_void {
+    if !usable_ptr.is_null() {
+        poison(usable_ptr as usize, size);
+    }
+    usable_ptr
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn malloc(size: size_t) -> *mut c_void {
+    if is_reentered() {
+        return get_cmalloc()(size);
+    }
+    let usable_ptr = with_reentered(|| get_cmalloc()(size));
+    poison_chunk(usable_ptr, size)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn calloc(nmemb: size_t, size: size_t) -> *mut c_void {
+    if is_reentered() {
+        return __libc_calloc(nmemb, size);
+    }
+    let usable_ptr = with_reentered(|| get_ccalloc()(nmemb, size));
+    if !usable_ptr.is_null() {
+        // the chunk may reuse memory poisoned by a previous allocation
+        unpoison(usable_ptr as usize, nmemb * size);
+    }
+    usable_ptr
+}
+
+/// Implemented as allocate-copy-release, so that the shadow of the old chunk is copied
+/// before another thread can reuse its memory
+#[no_mangle]
+pub unsafe extern "C" fn realloc(old_ptr: *mut c_void, size: size_t) -> *mut c_void {
+    if is_reentered() {
+        return __libc_realloc(old_ptr, size);
+    }
+    if old_ptr.is_null() {
+        return malloc(size);
+    }
+    let new_ptr = with_reentered(|| get_cmalloc()(size));
+    if new_ptr.is_null() {
+        return ptr::null_mut();
+    }
+    let copy_size = libc::malloc_usable_size(old_ptr).min(size);
+    libc::memcpy(new_ptr, old_ptr, copy_size);
+    copy_shadow(new_ptr as usize, old_ptr as usize, copy_size);
+    poison(new_ptr as usize + copy_size, size - copy_size);
+    with_reentered(|| get_cfree()(old_ptr));
+    new_ptr
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn posix_memalign(
+    memptr: *mut *mut c_void,
+    align: size_t,
+    size: size_t,
+) -> c_int {
+    if is_reentered() {
+        return get_cposix_memalign()(memptr, align, size);
+    }
+    let ret = with_reentered(|| get_cposix_memalign()(memptr, align, size));
+    if ret == 0 {
+        poison_chunk(*memptr, size);
+    }
+    ret
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn aligned_alloc(align: size_t, size: size_t) -> *mut c_void {
+    if is_reentered() {
+        return get_caligned_alloc()(align, size);
+    }
+    let usable_ptr = with_reentered(|| get_caligned_alloc()(align, size));
+    poison_chunk(usable_ptr, size)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn strcpy(dest: *mut c_char, src: *const c_char) -> *mut c_char {
+    let len = libc::strlen(src) + 1;
+    let ret = get_strcpy()(dest, src);
+    copy_shadow(dest as usize, src as usize, len);
+    ret
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn strncpy(dest: *mut c_char, src: *const c_char, n: size_t) -> *mut c_char {
+    let len = libc::strnlen(src, n);
+    let ret = get_strncpy()(dest, src, n);
+    copy_shadow(dest as usize, src as usize, len);
+    // the rest is padded with null bytes
+    unpoison(dest as usize + len, n - len);
+    ret
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn strcat(dest: *mut c_char, src: *const c_char) -> *mut c_char {
+    let dest_len = libc::strlen(dest);
+    let len = libc::strlen(src) + 1;
+    let ret = get_strcat()(dest, src);
+    copy_shadow(dest as usize + dest_len, src as usize, len);
+    ret
+}
+
+// functions filling a buffer from outside the program
+
+#[no_mangle]
+pub unsafe extern "C" fn read(fd: c_int, buf: *mut c_void, count: size_t) -> ssize_t {
+    let ret = get_read()(fd, buf, count);
+    if ret > 0 {
+        unpoison(buf as usize, ret as usize);
+    }
+    ret
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn fread(
+    buf: *mut c_void,
+    size: size_t,
+    n: size_t,
+    stream: *mut FILE,
+) -> size_t {
+    let ret = get_fread()(buf, size, n, stream);
+    unpoison(buf as usize, ret * size);
+    ret
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn fgets(buf: *mut c_char, n: c_int, stream: *mut FILE) -> *mut c_char {
+    let ret = get_fgets()(buf, n, stream);
+    if !ret.is_null() {
+        unpoison(buf as usize, libc::strlen(buf) + 1);
+    }
+    ret
+}
@@ -0,0 +1,95 @@
+use crate::msan_hook::with_reentered;
+use libc::{c_char, c_int, c_void, dlsym, size_t, ssize_t, FILE, RTLD_NEXT};
+use std::ffi::CStr;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+type MallocFn = unsafe extern "C" fn(size_t) -> *mut c_void;
+type CallocFn = unsafe extern "C" fn(nmemb: size_t, size: size_t) -> *mut c_void;
+type FreeFn = unsafe extern "C" fn(ptr: *mut c_void);
+type PosixMemalignFn =
+    unsafe extern "C" fn(memptr: *mut *mut c_void, align: size_t, size: size_t) -> c_int;
+type AlignedAllocFn = unsafe extern "C" fn(align: size_t, size: size_t) -> *mut c_void;
+type StrcpyFn = unsafe extern "C" fn(dest: *mut c_char, src: *const c_char) -> *mut c_char;
+type StrncpyFn =
+    unsafe extern "C" fn(dest: *mut c_char, src: *const c_char, n: size_t) -> *mut c_char;
+type StrcatFn = unsafe extern "C" fn(dest: *mut c_char, src: *const c_char) -> *mut c_char;
+type ReadFn = unsafe extern "C" fn(fd: c_int, buf: *mut c_void, count: size_t) -> ssize_t;
+type FreadFn =
+    unsafe extern "C" fn(buf: *mut c_void, size: size_t, n: size_t, stream: *mut FILE) -> size_t;
+type FgetsFn = unsafe extern "C" fn(buf: *mut c_char, n: c_int, stream: *mut FILE) -> *mut c_char;
+
+extern "C" {
+    // `dlsym` may call `calloc` before any real function has been resolved
+    pub fn __libc_calloc(nmemb: size_t, size: size_t) -> *mut c_void;
+    pub fn __libc_realloc(ptr: *mut c_void, size: size_t) -> *mut c_void;
+}
+
+// resolved functions are cached without a lock, as they are looked up on every allocation
+static MALLOC: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static CALLOC: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static FREE: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static POSIX_MEMALIGN: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static ALIGNED_ALLOC: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static STRCPY: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static STRNCPY: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static STRCAT: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static READ: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static FREAD: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static FGETS: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+/// Resolves the next definition of `name` (i.e., the libc one) and caches it. `dlsym` may
+/// allocate, which must not be intercepted.
+fn get_real_fn<F: Copy>(real_fn: &AtomicPtr<c_void>, name: &CStr) -> F {
+    let mut sym = real_fn.load(Ordering::Acquire);
+    if sym.is_null() {
+        sym = with_reentered(|| unsafe { dlsym(RTLD_NEXT, name.as_ptr()) });
+        assert!(!sym.is_null(), "failed to resolve {:?}", name);
+        real_fn.store(sym, Ordering::Release);
+    }
+    unsafe { std::mem::transmute_copy::<*mut c_void, F>(&sym) }
+}
+
+pub fn get_cmalloc() -> MallocFn {
+    get_real_fn(&MALLOC, c"malloc")
+}
+
+pub fn get_ccalloc() -> CallocFn {
+    get_real_fn(&CALLOC, c"calloc")
+}
+
+pub fn get_cfree() -> FreeFn {
+    get_real_fn(&FREE, c"free")
+}
+
+pub fn get_cposix_memalign() -> PosixMemalignFn {
+    get_real_fn(&POSIX_MEMALIGN, c"posix_memalign")
+}
+
+pub fn get_caligned_alloc() -> AlignedAllocFn {
+    get_real_fn(&ALIGNED_ALLOC, c"aligned_alloc")
+}
+
+pub fn get_strcpy() -> StrcpyFn {
+    get_real_fn(&STRCPY, c"strcpy")
+}
+
+pub fn get_strncpy() -> StrncpyFn {
+    get_real_fn(&STRNCPY, c"strncpy")
+}
+
+pub fn get_strcat() -> StrcatFn {
+    get_real_fn(&STRCAT, c"strcat")
+}
+
+pub fn get_read() -> ReadFn {
+    get_real_fn(&READ, c"read")
+}
+
+pub fn get_fread() -> FreadFn {
+    get_real_fn(&FREAD, c"fread")
+}
+
+pub fn get_fgets() -> FgetsFn {
+    get_real_fn(&FGETS, c"fgets")
+}
@@ -0,0 +1,175 @@
+use crate::{
+    msan_hook::{with_reentered, MALLOC_REENTERED},
+    msan_shadow::{copy_shadow, poison, read_shadow, unpoison, write_shadow},
+};
+use libc::{c_char, c_void, size_t};
+use sanitizer_common::options::{self, cstr_to_string};
+use std::backtrace::Backtrace;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+const MSAN_TEST_ENABLED: &str = "MSAN_UNIT_TEST_ENABLED";
+const MSAN_OPTIONS: &str = "MSAN_OPTIONS";
+pub const EXIT_CODE: i32 = 99;
+
+// shadows of arguments beyond this are dropped (i.e., treated as initialized)
+const MAX_PARAM_SHADOWS: usize = 64;
+
+/// Use of an uninitialized value checked by the instrumented code
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum CheckKind {
+    Branch = 0,
+    Pointer = 1,
+    LibCall = 2,
+}
+
+impl CheckKind {
+    fn from_u8(kind: u8) -> Self {
+        match kind {
+            0 => CheckKind::Branch,
+            1 => CheckKind::Pointer,
+            _ => CheckKind::LibCall,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckKind::Branch => "in a conditional branch",
+            CheckKind::Pointer => "as a pointer",
+            CheckKind::LibCall => "as an argument of a library call",
+        }
+    }
+}
+
+thread_local! {
+    // shadows of the arguments passed to the callee and of the value returned to the caller
+    static PARAM_SHADOWS: [Cell<u64>; MAX_PARAM_SHADOWS] =
+        const { [const { Cell::new(0) }; MAX_PARAM_SHADOWS] };
+    static RETVAL_SHADOW: Cell<u64> = const { Cell::new(0) };
+}
+
+lazy_static::lazy_static! {
+    static ref REPORTED: Mutex<HashSet<(CheckKind, String)>> = Mutex::new(HashSet::new());
+}
+
+pub fn is_test_enabled() -> bool {
+    options::is_enabled(MSAN_TEST_ENABLED)
+}
+
+/// Reads an option from `MSAN_OPTIONS` (e.g., `MSAN_OPTIONS=halt_on_error=0`)
+pub fn get_option(name: &str) -> Option<String> {
+    options::get_option(MSAN_OPTIONS, name)
+}
+
+fn halt_on_error() -> bool {
+    !is_test_enabled() && get_option("halt_on_error").is_none_or(|val| val != "0")
+}
+
+#[no_mangle]
+pub extern "C" fn __msan_poison(addr: *mut c_void, size: size_t) {
+    poison(addr as usize, size);
+}
+
+#[no_mangle]
+pub extern "C" fn __msan_unpoison(addr: *mut c_void, size: size_t) {
+    unpoison(addr as usize, size);
+}
+
+/// Marks a null-terminated string written by an uninstrumented function as initialized
+///
+/// # Safety
+/// `addr` is either null or points to a null-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn __msan_unpoison_str(addr: *const c_char) {
+    if !addr.is_null() {
+        unpoison(addr as usize, libc::strlen(addr) + 1);
+    }
+}
+
+/// Returns the shadow of a loaded value of `size` bytes
+#[no_mangle]
+pub extern "C" fn __msan_load_shadow(addr: *const c_void, size: size_t) -> u64 {
+    read_shadow(addr as usize, size)
+}
+
+#[no_mangle]
+pub extern "C" fn __msan_store_shadow(addr: *mut c_void, size: size_t, shadow: u64) {
+    write_shadow(addr as usize, size, shadow);
+}
+
+/// Propagates initializedness through `llvm.memcpy` and `llvm.memmove`
+#[no_mangle]
+pub extern "C" fn __msan_copy_shadow(dst: *mut c_void, src: *const c_void, size: size_t) {
+    copy_shadow(dst as usize, src as usize, size);
+}
+
+#[no_mangle]
+pub extern "C" fn __msan_set_param_shadow(idx: u32, shadow: u64) {
+    let _ = PARAM_SHADOWS.try_with(|shadows| {
+        if let Some(param) = shadows.get(idx as usize) {
+            param.set(shadow);
+        }
+    });
+}
+
+/// Takes the shadow of an argument on function entry. It is cleared, so that a later call
+/// from uninstrumented code does not see a stale shadow.
+#[no_mangle]
+pub extern "C" fn __msan_get_param_shadow(idx: u32) -> u64 {
+    PARAM_SHADOWS
+        .try_with(|shadows| shadows.get(idx as usize).map_or(0, |param| param.take()))
+        .unwrap_or(0)
+}
+
+#[no_mangle]
+pub extern "C" fn __msan_set_retval_shadow(shadow: u64) {
+    let _ = RETVAL_SHADOW.try_with(|retval| retval.set(shadow));
+}
+
+#[no_mangle]
+pub extern "C" fn __msan_get_retval_shadow() -> u64 {
+    RETVAL_SHADOW.try_with(|retval| retval.take()).unwrap_or(0)
+}
+
+/// Reports a use of an uninitialized value, i.e., a value whose shadow is not zero.
+/// `src_loc_ptr` is `file:line:col in func` of the using instruction.
+///
+/// # Safety
+///
+/// `src_loc_ptr` must point to a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn __msan_check(shadow: u64, src_loc_ptr: *const c_char, kind: u8) {
+    if shadow == 0 {
+        return;
+    }
+    let src_loc = cstr_to_string(src_loc_ptr);
+    report_uninit_use(&src_loc, CheckKind::from_u8(kind));
+}
+
+fn report_uninit_use(src_loc: &str, kind: CheckKind) {
+    // errors are reported once per (kind, location)
+    let is_first = with_reentered(|| REPORTED.lock().unwrap().insert((kind, src_loc.to_string())));
+    if !is_first {
+        return;
+    }
+    eprintln!(
+        "[MSAN] use of uninitialized value {} detected at {}",
+        kind.as_str(),
+        src_loc
+    );
+    // print backtrace
+    MALLOC_REENTERED.with(|re_enter| {
+        re_enter.set(true);
+        if !is_test_enabled() {
+            eprintln!("{}", Backtrace::force_capture());
+        }
+        re_enter.set(false);
+    });
+    if halt_on_error() {
+        unsafe {
+            libc::_exit(EXIT_CODE);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+// One shadow byte is kept per application byte, where a set bit marks the corresponding bit
+// as uninitialized. Shadow is mapped lazily by regions through a two-level table, and memory
+// without a shadow region (e.g., globals, mmap-ed memory) is initialized.
+const ADDR_BITS: usize = 47;
+const REGION_BITS: usize = 16;
+const L2_BITS: usize = 15;
+const L1_BITS: usize = ADDR_BITS - L2_BITS - REGION_BITS;
+const REGION_SIZE: usize = 1 << REGION_BITS;
+
+pub const POISONED_BYTE: u8 = 0xff;
+pub const CLEAN_BYTE: u8 = 0x00;
+
+type L2Table = [AtomicPtr<u8>; 1 << L2_BITS];
+
+static L1_TABLE: [AtomicPtr<L2Table>; 1 << L1_BITS] =
+    [const { AtomicPtr::new(ptr::null_mut()) }; 1 << L1_BITS];
+
+unsafe fn map_zeroed(size: usize) -> Option<*mut libc::c_void> {
+    let mem = libc::mmap(
+        ptr::null_mut(),
+        size,
+        libc::PROT_READ | libc::PROT_WRITE,
+        libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+        -1,
+        0,
+    );
+    (mem != libc::MAP_FAILED).then_some(mem)
+}
+
+/// Installs a zeroed table into `slot`. A thread losing the race drops its own mapping.
+unsafe fn install<T>(slot: &AtomicPtr<T>, size: usize) -> Option<*mut T> {
+    let new = map_zeroed(size)? as *mut T;
+    match slot.compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => Some(new),
+        Err(installed) => {
+            libc::munmap(new as *mut libc::c_void, size);
+            Some(installed)
+        }
+    }
+}
+
+/// Returns the shadow of `addr`, mapping its region if `create` is set
+fn get_shadow_ptr(addr: usize, create: bool) -> Option<*mut u8> {
+    if addr >> ADDR_BITS != 0 {
+        return None;
+    }
+    unsafe {
+        let l1_slot = &L1_TABLE[addr >> (L2_BITS + REGION_BITS)];
+        let mut l2 = l1_slot.load(Ordering::Acquire);
+        if l2.is_null() {
+            if !create {
+                return None;
+            }
+            l2 = install(l1_slot, size_of::<L2Table>())?;
+        }
+        let region_slot = &(*l2)[(addr >> REGION_BITS) & ((1 << L2_BITS) - 1)];
+        let mut region = region_slot.load(Ordering::Acquire);
+        if region.is_null() {
+            if !create {
+                return None;
+            }
+            region = install(region_slot, REGION_SIZE)?;
+        }
+        Some(region.add(addr & (REGION_SIZE - 1)))
+    }
+}
+
+/// Splits `[addr, addr + size)` into pieces not crossing a shadow region
+fn split_by_region(addr: usize, size: usize) -> impl Iterator<Item = (usize, usize)> {
+    let end = addr.saturating_add(size);
+    let mut cur = addr;
+    std::iter::from_fn(move || {
+        if cur >= end {
+            return None;
+        }
+        let piece_end = end.min((cur | (REGION_SIZE - 1)) + 1);
+        let piece = (cur, piece_end - cur);
+        cur = piece_end;
+        Some(piece)
+    })
+}
+
+/// Marks `[addr, addr + size)` as uninitialized
+pub fn poison(addr: usize, size: usize) {
+    for (start, len) in split_by_region(addr, size) {
+        if let Some(shadow) = get_shadow_ptr(start, true) {
+            unsafe { ptr::write_bytes(shadow, POISONED_BYTE, len) };
+        }
+    }
+}
+
+/// Marks `[addr, addr + size)` as initialized
+pub fn unpoison(addr: usize, size: usize) {
+    for (start, len) in split_by_region(addr, size) {
+        if let Some(shadow) = get_shadow_ptr(start, false) {
+            unsafe { ptr::write_bytes(shadow, CLEAN_BYTE, len) };
+        }
+    }
+}
+
+/// Reads the shadow of a little-endian value of `size` (at most 8) bytes
+pub fn read_shadow(addr: usize, size: usize) -> u64 {
+    (0..size.min(8)).fold(0, |shadow, idx| {
+        let byte = get_shadow_ptr(addr + idx, false).map_or(CLEAN_BYTE, |ptr| unsafe { *ptr });
+        shadow | ((byte as u64) << (idx * 8))
+    })
+}
+
+/// Writes the shadow of a little-endian value of `size` (at most 8) bytes
+pub fn write_shadow(addr: usize, size: usize, shadow: u64) {
+    if shadow == 0 {
+        unpoison(addr, size.min(8));
+        return;
+    }
+    for idx in 0..size.min(8) {
+        if let Some(ptr) = get_shadow_ptr(addr + idx, true) {
+            unsafe { *ptr = (shadow >> (idx * 8)) as u8 };
+        }
+    }
+}
+
+/// Copies the shadow of `[src, src + size)` to `dst`. Overlapping ranges are allowed as in
+/// `memmove`.
+pub fn copy_shadow(dst: usize, src: usize, size: usize) {
+    let mut pieces = Vec::new();
+    let mut offset = 0;
+    while offset < size {
+        // neither side of a piece crosses a shadow region
+        let src_left = REGION_SIZE - ((src + offset) & (REGION_SIZE - 1));
+        let dst_left = REGION_SIZE - ((dst + offset) & (REGION_SIZE - 1));
+        let len = (size - offset).min(src_left).min(dst_left);
+        pieces.push((offset, len));
+        offset += len;
+    }
+    // copy backward when the destination overlaps the tail of the source
+    if dst > src && dst < src + size {
+        pieces.reverse();
+    }
+    for (offset, len) in pieces {
+        match get_shadow_ptr(src + offset, false) {
+            Some(src_shadow) => {
+                if let Some(dst_shadow) = get_shadow_ptr(dst + offset, true) {
+                    unsafe { ptr::copy(src_shadow, dst_shadow, len) };
+                }
+            }
+            None => unpoison(dst + offset, len),
+        }
+    }
+}
+
+/// Returns the offset of the first uninitialized byte within `[addr, addr + size)`
+pub fn find_poisoned_byte(addr: usize, size: usize) -> Option<usize> {
+    (0..size).find(|&idx| {
+        get_shadow_ptr(addr + idx, false).is_some_and(|ptr| unsafe { *ptr } != CLEAN_BYTE)
+    })
+}
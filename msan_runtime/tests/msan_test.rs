@@ -0,0 +1,93 @@
+use msan_runtime::msan_hook::{calloc, malloc, realloc};
+use msan_runtime::msan_runtime::{
+    __msan_get_param_shadow, __msan_get_retval_shadow, __msan_set_param_shadow,
+    __msan_set_retval_shadow,
+};
+use msan_runtime::msan_shadow::{
+    copy_shadow, find_poisoned_byte, poison, read_shadow, unpoison, write_shadow,
+};
+
+fn is_initialized(ptr: *mut libc::c_void, size: usize) -> bool {
+    find_poisoned_byte(ptr as usize, size).is_none()
+}
+
+#[test]
+fn test_heap_initializedness() {
+    unsafe {
+        let ptr = malloc(64);
+        assert!(!is_initialized(ptr, 1));
+        let zeroed = calloc(4, 16);
+        assert!(is_initialized(zeroed, 64));
+
+        // only the initialized prefix is kept by realloc
+        unpoison(ptr as usize, 16);
+        let ptr = realloc(ptr, 128);
+        assert!(is_initialized(ptr, 16));
+        assert_eq!(find_poisoned_byte(ptr as usize, 128), Some(16));
+        libc::free(ptr);
+        libc::free(zeroed);
+    }
+}
+
+#[test]
+fn test_partial_store() {
+    let mut val = 0u64;
+    let addr = &mut val as *mut u64 as usize;
+    poison(addr, 8);
+    assert_eq!(read_shadow(addr, 8), u64::MAX);
+    // storing a single byte initializes only that byte
+    write_shadow(addr, 1, 0);
+    assert_eq!(read_shadow(addr, 8), !0xff);
+    write_shadow(addr + 4, 4, 0);
+    assert_eq!(read_shadow(addr, 4), 0xffff_ff00);
+    assert_eq!(read_shadow(addr + 4, 4), 0);
+}
+
+#[test]
+fn test_overlapping_copy() {
+    let mut buf = [0u8; 32];
+    let addr = buf.as_mut_ptr() as usize;
+    unpoison(addr, 32);
+    poison(addr, 8);
+    // `memmove`-like copy to the right keeps the source pattern
+    copy_shadow(addr + 4, addr, 16);
+    assert_eq!(find_poisoned_byte(addr + 12, 20), None);
+    assert_eq!(read_shadow(addr + 4, 8), u64::MAX);
+    copy_shadow(addr, addr + 12, 8);
+    assert_eq!(read_shadow(addr, 8), 0);
+}
+
+#[test]
+fn test_copy_across_regions() {
+    let size = 3 << 16;
+    let src = unsafe { malloc(size) } as usize;
+    let dst = unsafe { calloc(1, size) } as usize;
+    unpoison(src + 1000, 1 << 16);
+    copy_shadow(dst, src, size);
+    assert_eq!(find_poisoned_byte(dst, size), Some(0));
+    assert_eq!(find_poisoned_byte(dst + 1000, size - 1000), Some(1 << 16));
+    unsafe {
+        libc::free(src as *mut libc::c_void);
+        libc::free(dst as *mut libc::c_void);
+    }
+}
+
+#[test]
+fn test_param_and_retval_shadow() {
+    __msan_set_param_shadow(1, 0xff);
+    assert_eq!(__msan_get_param_shadow(0), 0);
+    assert_eq!(__msan_get_param_shadow(1), 0xff);
+    // shadows are consumed by the callee
+    assert_eq!(__msan_get_param_shadow(1), 0);
+
+    __msan_set_retval_shadow(0xf0);
+    assert_eq!(__msan_get_retval_shadow(), 0xf0);
+    assert_eq!(__msan_get_retval_shadow(), 0);
+
+    // shadows are per thread
+    __msan_set_param_shadow(0, 1);
+    std::thread::spawn(|| assert_eq!(__msan_get_param_shadow(0), 0))
+        .join()
+        .unwrap();
+    assert_eq!(__msan_get_param_shadow(0), 1);
+}
@@ -0,0 +1,29 @@
+use libc::c_char;
+use std::env;
+
+/// Whether `env_name` (e.g., `ASAN_UNIT_TEST_ENABLED`) is set to `1`
+pub fn is_enabled(env_name: &str) -> bool {
+    matches!(env::var(env_name), Ok(val) if val == "1")
+}
+
+/// Reads an option from `options_env` (e.g., `ASAN_OPTIONS=halt_on_error=0:detect_leaks=1`)
+pub fn get_option(options_env: &str, name: &str) -> Option<String> {
+    env::var(options_env)
+        .ok()?
+        .split([':', ','])
+        .filter_map(|opt| opt.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, val)| val.to_string())
+}
+
+/// Copies a C string, where a null pointer is read as an empty string
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a nul-terminated string.
+pub unsafe fn cstr_to_string(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return "".to_string();
+    }
+    std::ffi::CStr::from_ptr(ptr).to_string_lossy().into_owned()
+}
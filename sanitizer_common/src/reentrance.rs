@@ -0,0 +1,23 @@
+use std::cell::Cell;
+
+thread_local! {
+    // a plain flag of the current thread, so that checking it never blocks another thread
+    pub static MALLOC_REENTERED: Cell<bool> = const { Cell::new(false) }
+}
+
+/// Whether the current thread is inside the runtime. Allocations made by the runtime itself
+/// are passed to libc as is. A thread being torn down is treated as reentered.
+pub fn is_reentered() -> bool {
+    MALLOC_REENTERED
+        .try_with(|re_enter| re_enter.get())
+        .unwrap_or(true)
+}
+
+pub fn with_reentered<T>(f: impl FnOnce() -> T) -> T {
+    let prev = MALLOC_REENTERED
+        .try_with(|re_enter| re_enter.replace(true))
+        .unwrap_or(true);
+    let ret = f();
+    let _ = MALLOC_REENTERED.try_with(|re_enter| re_enter.set(prev));
+    ret
+}
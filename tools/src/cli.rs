@@ -1,5 +1,5 @@
 use anyhow::Result;
-use clap::{Arg, ArgMatches, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 
 #[derive(Debug)]
 enum Flag {
@@ -8,10 +8,14 @@ enum Flag {
     OutBinName,
     Compiler,
     OptLevel,
+    LifetimeMarkers,
     CoverageRuntimeLibPath,
     CoverageRuntimeLibName,
     AsanRuntimeLibPath,
     AsanRuntimeLibName,
+    Msan,
+    MsanRuntimeLibPath,
+    MsanRuntimeLibName,
     FuzzerRuntimeLibPath,
     FuzzerRuntimeLibName,
     SymbolicRuntimeLibPath,
@@ -28,10 +32,14 @@ impl Flag {
             Self::OutBinName => "output_bin_name",
             Self::Compiler => "compiler",
             Self::OptLevel => "opt_level",
+            Self::LifetimeMarkers => "lifetime_markers",
             Self::CoverageRuntimeLibPath => "coverage_runtime_lib_path",
             Self::CoverageRuntimeLibName => "coverage_runtime_lib_name",
             Self::AsanRuntimeLibPath => "asan_runtime_lib_path",
             Self::AsanRuntimeLibName => "asan_runtime_lib_name",
+            Self::Msan => "msan",
+            Self::MsanRuntimeLibPath => "msan_runtime_lib_path",
+            Self::MsanRuntimeLibName => "msan_runtime_lib_name",
             Self::FuzzerRuntimeLibPath => "fuzzer_runtime_lib_path",
             Self::FuzzerRuntimeLibName => "fuzzer_runtime_lib_name",
             Self::SymbolicRuntimeLibPath => "symbolic_runtime_lib_path",
@@ -89,6 +97,13 @@ impl CLI {
                     .help("Sepcify optimization level <O0 || O1 || O2 || O3>")
                     .default_value("O0"), // default is O0 (disable optimization)
             )
+            .arg(
+                Arg::new(Flag::LifetimeMarkers.as_str())
+                    .short('l')
+                    .long("lifetime_markers")
+                    .action(ArgAction::SetTrue)
+                    .help("Keep lifetime markers at O0 to detect stack-use-after-scope"),
+            )
             .arg(
                 Arg::new(Flag::CoverageRuntimeLibPath.as_str())
                     .short('q')
@@ -121,6 +136,27 @@ impl CLI {
                     .help("Asan Runtime library name (C runtime)")
                     .required(true),
             )
+            .arg(
+                Arg::new(Flag::Msan.as_str())
+                    .short('z')
+                    .long("msan")
+                    .action(ArgAction::SetTrue)
+                    .help("Detect uninitialized reads with Msan instead of Asan"),
+            )
+            .arg(
+                Arg::new(Flag::MsanRuntimeLibPath.as_str())
+                    .short('n')
+                    .long("msan_runtime_path")
+                    .value_name("string")
+                    .help("Msan Runtime library path (C runtime), required with --msan"),
+            )
+            .arg(
+                Arg::new(Flag::MsanRuntimeLibName.as_str())
+                    .short('u')
+                    .long("msan_runtime_name")
+                    .value_name("string")
+                    .help("Msan Runtime library name (C runtime), required with --msan"),
+            )
             .arg(
                 Arg::new(Flag::FuzzerRuntimeLibPath.as_str())
                     .short('f')
@@ -189,10 +225,12 @@ impl CLI {
         String,
         String,
         String,
+        bool,
         String,
         String,
         String,
         String,
+        Option<(String, String)>,
         String,
         String,
         String,
@@ -205,10 +243,20 @@ impl CLI {
         let out_bin = self.get_arg(Flag::OutBinName.as_str());
         let compiler = self.get_arg(Flag::Compiler.as_str());
         let opt_level = self.get_arg(Flag::OptLevel.as_str());
+        let lifetime_markers = self.matches.get_flag(Flag::LifetimeMarkers.as_str());
         let coverage_runtime_lib_path_name = self.get_arg(Flag::CoverageRuntimeLibPath.as_str());
         let coverage_runtime_lib_name = self.get_arg(Flag::CoverageRuntimeLibName.as_str());
         let asan_runtime_lib_path_name = self.get_arg(Flag::AsanRuntimeLibPath.as_str());
         let asan_runtime_lib_name = self.get_arg(Flag::AsanRuntimeLibName.as_str());
+        let msan = self.matches.get_flag(Flag::Msan.as_str());
+        let msan_runtime_lib_path_name = self
+            .matches
+            .get_one::<String>(Flag::MsanRuntimeLibPath.as_str())
+            .cloned();
+        let msan_runtime_lib_name = self
+            .matches
+            .get_one::<String>(Flag::MsanRuntimeLibName.as_str())
+            .cloned();
         let fuzzer_runtime_lib_path_name = self.get_arg(Flag::FuzzerRuntimeLibPath.as_str());
         let fuzzer_runtime_lib_name = self.get_arg(Flag::FuzzerRuntimeLibName.as_str());
         let symbolic_runtime_lib_path_name = self.get_arg(Flag::SymbolicRuntimeLibPath.as_str());
@@ -224,6 +272,16 @@ impl CLI {
                 ))
             }
         }
+        // the Msan runtime replaces the Asan one, so it is linked only in Msan mode
+        let msan_runtime = match (msan, msan_runtime_lib_path_name, msan_runtime_lib_name) {
+            (false, _, _) => None,
+            (true, Some(lib_path), Some(lib_name)) => Some((lib_path, lib_name)),
+            (true, _, _) => {
+                return Err(anyhow::anyhow!(
+                    "Msan runtime library path and name are required with --msan"
+                ))
+            }
+        };
         match compiler.as_str() {
             "clang" | "clang++" => {}
             _ => {
@@ -238,10 +296,12 @@ impl CLI {
             out_bin,
             compiler,
             opt_level,
+            lifetime_markers,
             coverage_runtime_lib_path_name,
             coverage_runtime_lib_name,
             asan_runtime_lib_path_name,
             asan_runtime_lib_name,
+            msan_runtime,
             fuzzer_runtime_lib_path_name,
             fuzzer_runtime_lib_name,
             symbolic_runtime_lib_path_name,
@@ -4,7 +4,11 @@ use crate::{
 };
 use anyhow::{Context as AnyhowContext, Result};
 use inkwell::context::Context;
-use instrument::{llvm_intrinsic::read_ll, module::instrument_all};
+use instrument::{
+    dict::{extract_tokens, write_dict},
+    llvm_intrinsic::read_ll,
+    module::instrument_all,
+};
 use std::process::Command;
 
 pub fn get_args() -> Result<(
@@ -13,10 +17,12 @@ pub fn get_args() -> Result<(
     String,
     String,
     String,
+    bool,
     String,
     String,
     String,
     String,
+    Option<(String, String)>,
     String,
     String,
     String,
@@ -28,11 +34,28 @@ pub fn get_args() -> Result<(
     cli.get_args()
 }
 
-fn compile_to_ir(file: &str, compiler: &str, opt_level: &str, out_dir: &str) -> Result<String> {
+fn compile_to_ir(
+    file: &str,
+    compiler: &str,
+    opt_level: &str,
+    lifetime_markers: bool,
+    out_dir: &str,
+) -> Result<String> {
     let output_file = format!("{}/{}.ll", out_dir, extract_filename(file));
+    // clang emits `llvm.lifetime.*` only when optimizing, so O0 is emulated by optimizing
+    // without running any pass
+    let opt_args = if lifetime_markers && opt_level == "O0" {
+        vec![
+            "-O1".to_string(),
+            "-Xclang".into(),
+            "-disable-llvm-passes".into(),
+        ]
+    } else {
+        vec![format!("-{}", opt_level)]
+    };
     let status = Command::new(compiler)
         .arg("-Wno-everything")
-        .arg(format!("-{}", opt_level))
+        .args(opt_args)
         .arg("-g")
         .arg("-S")
         .arg("-emit-llvm")
@@ -47,12 +70,22 @@ fn compile_to_ir(file: &str, compiler: &str, opt_level: &str, out_dir: &str) ->
     Ok(output_file)
 }
 
-fn instrument(ir_file: &str, out_dir: &str) -> Result<String, Box<dyn std::error::Error>> {
+fn instrument(
+    ir_file: &str,
+    out_dir: &str,
+    out_bin: &str,
+    msan: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
     let mem_buf = read_ll(&ir_file)?;
     let context = Context::create();
     let module = context.create_module_from_ir(mem_buf)?;
     let builder = context.create_builder();
-    instrument_all(&context, &module, &builder)?;
+    // picked up by the fuzzer as `<binary>.dict`
+    write_dict(
+        &extract_tokens(&module),
+        format!("{}/{}.dict", out_dir, out_bin),
+    )?;
+    instrument_all(&context, &module, &builder, msan)?;
     let output_file = format!("{}/instrumented_{}", out_dir, extract_filename(ir_file));
     module.print_to_file(&output_file)?;
     Ok(output_file)
@@ -63,8 +96,8 @@ fn compile_to_bin(
     compiler: &str,
     coverage_runtime_lib_path: &str,
     coverage_runtime_lib_name: &str,
-    asan_runtime_lib_path: &str,
-    asan_runtime_lib_name: &str,
+    mem_runtime_lib_path: &str,
+    mem_runtime_lib_name: &str,
     fuzzer_runtime_lib_path: &str,
     fuzzer_runtime_lib_name: &str,
     symbolic_runtime_lib_path: &str,
@@ -86,8 +119,8 @@ fn compile_to_bin(
         .arg(instrumented_file)
         .arg(format!("-L{}", coverage_runtime_lib_path))
         .arg(format!("-l{}", coverage_runtime_lib_name))
-        .arg(format!("-L{}", asan_runtime_lib_path))
-        .arg(format!("-l{}", asan_runtime_lib_name))
+        .arg(format!("-L{}", mem_runtime_lib_path))
+        .arg(format!("-l{}", mem_runtime_lib_name))
         .arg(format!("-L{}", fuzzer_runtime_lib_path))
         .arg(format!("-l{}", fuzzer_runtime_lib_name))
         .arg(format!("-L{}", symbolic_runtime_lib_path))
@@ -110,10 +143,12 @@ pub fn build(
     out_bin: &str,
     compiler: &str,
     opt_level: &str,
+    lifetime_markers: bool,
     coverage_runtime_lib_path: &str,
     coverage_runtime_lib_name: &str,
     asan_runtime_lib_path: &str,
     asan_runtime_lib_name: &str,
+    msan_runtime: Option<(&str, &str)>,
     fuzzer_runtime_lib_path: &str,
     fuzzer_runtime_lib_name: &str,
     symbolic_runtime_lib_path: &str,
@@ -122,17 +157,26 @@ pub fn build(
     race_runtime_lib_name: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
     create_dir(out_dir)?;
-    let ir_file = compile_to_ir(&input_file, &compiler, &opt_level, &out_dir)?;
+    let ir_file = compile_to_ir(
+        &input_file,
+        &compiler,
+        &opt_level,
+        lifetime_markers,
+        &out_dir,
+    )?;
     println!("[+] compiled to IR ({})", ir_file);
-    let instrumented_file = instrument(&ir_file, &out_dir)?;
+    let instrumented_file = instrument(&ir_file, &out_dir, &out_bin, msan_runtime.is_some())?;
     println!("[+] IR file instrumented ({})", instrumented_file);
+    // either memory checker, as both runtimes interpose the allocator
+    let (mem_runtime_lib_path, mem_runtime_lib_name) =
+        msan_runtime.unwrap_or((asan_runtime_lib_path, asan_runtime_lib_name));
     compile_to_bin(
         &instrumented_file,
         &compiler,
         &coverage_runtime_lib_path,
         &coverage_runtime_lib_name,
-        &asan_runtime_lib_path,
-        &asan_runtime_lib_name,
+        &mem_runtime_lib_path,
+        &mem_runtime_lib_name,
         &fuzzer_runtime_lib_path,
         &fuzzer_runtime_lib_name,
         &symbolic_runtime_lib_path,
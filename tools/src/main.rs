@@ -8,10 +8,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         out_bin,
         compiler,
         opt_level,
+        lifetime_markers,
         coverage_runtime_lib_path,
         coverage_runtime_lib_name,
         asan_runtime_lib_path,
         asan_runtime_lib_name,
+        msan_runtime,
         fuzzer_runtime_lib_path,
         fuzzer_runtime_lib_name,
         symbolic_runtime_lib_path,
@@ -25,10 +27,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         &out_bin,
         &compiler,
         &opt_level,
+        lifetime_markers,
         &coverage_runtime_lib_path,
         &coverage_runtime_lib_name,
         &asan_runtime_lib_path,
         &asan_runtime_lib_name,
+        msan_runtime
+            .as_ref()
+            .map(|(lib_path, lib_name)| (lib_path.as_str(), lib_name.as_str())),
         &fuzzer_runtime_lib_path,
         &fuzzer_runtime_lib_name,
         &symbolic_runtime_lib_path,
@@ -0,0 +1,44 @@
+#include <stdlib.h>
+// ASAN_OPTIONS: halt_on_error=0
+
+void write_at(int* arr, int idx) {
+    arr[idx] = 1; // OOB access if `idx` is out of bounds
+}
+
+int main() {
+    int* arr = (int*)malloc(sizeof(int) * 4);
+    write_at(arr, 4);
+    write_at(arr, 4); // the same bug at the same location is reported once
+    free(arr);
+    arr[0] = 2; // UAF
+}
+
+//_:_// expected stdout:
+//_:_// [+] compiled to IR (covout/halt-on-error.c.ll)
+//_:_// [+] IR file instrumented (covout/instrumented_halt-on-error.c.ll)
+//_:_// [+] Binary created (halt-on-error)
+//_:_// [+] You can run LD_LIBRARY_PATH=../bin/debug ./covout/halt-on-error 
+//_:_// +-----------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | File                              | % Funcs | Uncovered Funcs | % Branch | Uncovered Branches | % Lines | Uncovered lines |
+//_:_// +-----------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | tests/inputs/asan/halt-on-error.c | 100.00  |                 | NaN      |                    | 100.00  |                 |
+//_:_// +-----------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+
+//_:_// expected stderr:
+//_:_// [ASAN] invalid memory access detected at tests/inputs/asan/halt-on-error.c:5:14 in write_at
+//_:_//    5: write_at
+//_:_//              at ./tests/inputs/asan/halt-on-error.c:5:14
+//_:_//    6: main
+//_:_//              at ./tests/inputs/asan/halt-on-error.c:10:5
+//_:_//    7: __libc_start_call_main
+//_:_//    8: __libc_start_main_alias_2
+//_:_//    9: _start
+//_:_// [ASAN] invalid memory access detected at tests/inputs/asan/halt-on-error.c:13:12 in main
+//_:_//    5: main
+//_:_//              at ./tests/inputs/asan/halt-on-error.c:13:12
+//_:_//    6: __libc_start_call_main
+//_:_//    7: __libc_start_main_alias_2
+//_:_//    8: _start
+//_:_// [ASAN] SUMMARY: 2 unique error(s), 3 in total
+//_:_// [ASAN]   heap-buffer-overflow: 1 location(s), 2 hit(s)
+//_:_// [ASAN]   heap-use-after-free: 1 location(s), 1 hit(s)
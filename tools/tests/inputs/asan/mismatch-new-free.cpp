@@ -0,0 +1,27 @@
+#include <stdlib.h>
+
+int main() {
+    int* arr = new int[4];
+    free(arr); // must be released with `delete[]`
+}
+
+//_:_// expected stdout:
+//_:_// [+] compiled to IR (covout/mismatch-new-free.cpp.ll)
+//_:_// [+] IR file instrumented (covout/instrumented_mismatch-new-free.cpp.ll)
+//_:_// [+] Binary created (mismatch-new-free.cpp)
+//_:_// [+] You can run LD_LIBRARY_PATH=../bin/debug ./covout/mismatch-new-free.cpp 
+//_:_// +-----------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | File                                    | % Funcs | Uncovered Funcs | % Branch | Uncovered Branches | % Lines | Uncovered lines |
+//_:_// +-----------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | tests/inputs/asan/mismatch-new-free.cpp | 100.00  |                 | NaN      |                    | 100.00  |                 |
+//_:_// +-----------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+
+//_:_// expected stderr:
+//_:_// [ASAN] alloc-dealloc mismatch (operator new [] vs free) detected
+//_:_//    4: free
+//_:_//    5: main
+//_:_//              at ./tests/inputs/asan/mismatch-new-free.cpp:5:5
+//_:_//    6: __libc_start_call_main
+//_:_//    7: __libc_start_main_alias_2
+//_:_//    8: _start
+
@@ -25,7 +25,7 @@ int main() {
 //_:_// +--------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
 
 //_:_// expected stderr:
-//_:_// [ASAN] invalid memory access detected at tests/inputs/asan/oob-malloc.c
+//_:_// [ASAN] invalid memory access detected at tests/inputs/asan/oob-malloc.c:9:13 in myfunc
 //_:_//    5: myfunc
 //_:_//              at ./tests/inputs/asan/oob-malloc.c:9:13
 //_:_//    6: main
@@ -22,7 +22,7 @@ int main() {
 //_:_// +---------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
 
 //_:_// expected stderr:
-//_:_// [ASAN] invalid memory access detected at tests/inputs/asan/oob-malloc2.c
+//_:_// [ASAN] invalid memory access detected at tests/inputs/asan/oob-malloc2.c:7:16 in main
 //_:_//    5: main
 //_:_//              at ./tests/inputs/asan/oob-malloc2.c:7:16
 //_:_//    6: __libc_start_call_main
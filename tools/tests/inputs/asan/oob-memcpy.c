@@ -0,0 +1,34 @@
+#include <stdlib.h>
+#include <string.h>
+
+void myfunc() {
+    char src[16] = "aaaaaaaaaaaaaaa";
+    char* dest = (char*)malloc(8);
+    memcpy(dest, src, sizeof(src)); // OOB write
+}
+
+int main() {
+    myfunc();
+}
+
+//_:_// expected stdout:
+//_:_// [+] compiled to IR (covout/oob-memcpy.c.ll)
+//_:_// [+] IR file instrumented (covout/instrumented_oob-memcpy.c.ll)
+//_:_// [+] Binary created (oob-memcpy)
+//_:_// [+] You can run LD_LIBRARY_PATH=../bin/debug ./covout/oob-memcpy 
+//_:_// +--------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | File                           | % Funcs | Uncovered Funcs | % Branch | Uncovered Branches | % Lines | Uncovered lines |
+//_:_// +--------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | tests/inputs/asan/oob-memcpy.c | 100.00  |                 | NaN      |                    | 100.00  |                 |
+//_:_// +--------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+
+//_:_// expected stderr:
+//_:_// [ASAN] invalid memory access detected at tests/inputs/asan/oob-memcpy.c:7:5 in myfunc
+//_:_//    5: myfunc
+//_:_//              at ./tests/inputs/asan/oob-memcpy.c:7:5
+//_:_//    6: main
+//_:_//              at ./tests/inputs/asan/oob-memcpy.c:11:5
+//_:_//    7: __libc_start_call_main
+//_:_//    8: __libc_start_main_alias_2
+//_:_//    9: _start
+
@@ -0,0 +1,32 @@
+#include <stdio.h>
+#include <stdlib.h>
+
+void myfunc(int row) {
+    int grid[2][3];
+    grid[row][1] = 7; // OOB
+}
+
+int main() {
+    myfunc(2);
+}
+
+//_:_// expected stdout:
+//_:_// [+] compiled to IR (covout/oob-stack-2d.c.ll)
+//_:_// [+] IR file instrumented (covout/instrumented_oob-stack-2d.c.ll)
+//_:_// [+] Binary created (oob-stack-2d)
+//_:_// [+] You can run LD_LIBRARY_PATH=../bin/debug ./covout/oob-stack-2d 
+//_:_// +----------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | File                             | % Funcs | Uncovered Funcs | % Branch | Uncovered Branches | % Lines | Uncovered lines |
+//_:_// +----------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | tests/inputs/asan/oob-stack-2d.c | 100.00  |                 | NaN      |                    | 100.00  |                 |
+//_:_// +----------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+
+//_:_// expected stderr:
+//_:_// [ASAN] invalid memory access detected at tests/inputs/asan/oob-stack-2d.c:6:18 in myfunc
+//_:_//    5: myfunc
+//_:_//              at ./tests/inputs/asan/oob-stack-2d.c:6:18
+//_:_//    6: main
+//_:_//              at ./tests/inputs/asan/oob-stack-2d.c:10:5
+//_:_//    7: __libc_start_call_main
+//_:_//    8: __libc_start_main_alias_2
+//_:_//    9: _start
@@ -0,0 +1,37 @@
+#include <stdio.h>
+#include <stdlib.h>
+
+struct pair {
+    int first;
+    int second;
+};
+
+void myfunc(int idx) {
+    struct pair p[2];
+    p[idx].second = 7; // OOB
+}
+
+int main() {
+    myfunc(2);
+}
+
+//_:_// expected stdout:
+//_:_// [+] compiled to IR (covout/oob-stack-struct.c.ll)
+//_:_// [+] IR file instrumented (covout/instrumented_oob-stack-struct.c.ll)
+//_:_// [+] Binary created (oob-stack-struct)
+//_:_// [+] You can run LD_LIBRARY_PATH=../bin/debug ./covout/oob-stack-struct 
+//_:_// +--------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | File                                 | % Funcs | Uncovered Funcs | % Branch | Uncovered Branches | % Lines | Uncovered lines |
+//_:_// +--------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | tests/inputs/asan/oob-stack-struct.c | 100.00  |                 | NaN      |                    | 100.00  |                 |
+//_:_// +--------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+
+//_:_// expected stderr:
+//_:_// [ASAN] invalid memory access detected at tests/inputs/asan/oob-stack-struct.c:11:19 in myfunc
+//_:_//    5: myfunc
+//_:_//              at ./tests/inputs/asan/oob-stack-struct.c:11:19
+//_:_//    6: main
+//_:_//              at ./tests/inputs/asan/oob-stack-struct.c:15:5
+//_:_//    7: __libc_start_call_main
+//_:_//    8: __libc_start_main_alias_2
+//_:_//    9: _start
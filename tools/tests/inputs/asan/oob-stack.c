@@ -23,7 +23,7 @@ int main() {
 //_:_// +-------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
 
 //_:_// expected stderr:
-//_:_// [ASAN] invalid memory access detected at tests/inputs/asan/oob-stack.c
+//_:_// [ASAN] invalid memory access detected at tests/inputs/asan/oob-stack.c:7:13 in myfunc
 //_:_//    5: myfunc
 //_:_//              at ./tests/inputs/asan/oob-stack.c:7:13
 //_:_//    6: main
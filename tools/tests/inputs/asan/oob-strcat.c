@@ -0,0 +1,33 @@
+#include <string.h>
+
+void myfunc() {
+    char buffer[8] = "abcd";
+    strcat(buffer, "efgh");
+}
+
+int main() {
+   myfunc();
+}
+
+//_:_// expected stdout:
+//_:_// [+] compiled to IR (covout/oob-strcat.c.ll)
+//_:_// [+] IR file instrumented (covout/instrumented_oob-strcat.c.ll)
+//_:_// [+] Binary created (oob-strcat)
+//_:_// [+] You can run LD_LIBRARY_PATH=../bin/debug ./covout/oob-strcat 
+//_:_// +--------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | File                           | % Funcs | Uncovered Funcs | % Branch | Uncovered Branches | % Lines | Uncovered lines |
+//_:_// +--------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | tests/inputs/asan/oob-strcat.c | 100.00  |                 | NaN      |                    | 100.00  |                 |
+//_:_// +--------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+
+//_:_// expected stderr:
+//_:_// [ASAN] invalid memory access detected at libc::strcat
+//_:_//    5: strcat
+//_:_//    6: myfunc
+//_:_//              at ./tests/inputs/asan/oob-strcat.c:5:5
+//_:_//    7: main
+//_:_//              at ./tests/inputs/asan/oob-strcat.c:9:4
+//_:_//    8: __libc_start_call_main
+//_:_//    9: __libc_start_main_alias_2
+//_:_//   10: _start
+
@@ -0,0 +1,30 @@
+#include <stdio.h>
+#include <stdlib.h>
+
+#define NUM_ALLOCS 100000
+
+int main() {
+    char** ptrs = (char**)malloc(sizeof(char*) * NUM_ALLOCS);
+    // keep more chunks alive at once than a fixed-size allocation table could hold
+    for (int i = 0; i < NUM_ALLOCS; i++) {
+        ptrs[i] = (char*)malloc(i % 64 + 1);
+        ptrs[i][i % 64] = 'a';
+    }
+    for (int i = 0; i < NUM_ALLOCS; i++) {
+        free(ptrs[i]);
+    }
+    free(ptrs);
+    printf("%d\n", NUM_ALLOCS);
+}
+
+//_:_// expected stdout:
+//_:_// [+] compiled to IR (covout/safe-many-malloc.c.ll)
+//_:_// [+] IR file instrumented (covout/instrumented_safe-many-malloc.c.ll)
+//_:_// [+] Binary created (safe-many-malloc)
+//_:_// [+] You can run LD_LIBRARY_PATH=../bin/debug ./covout/safe-many-malloc 
+//_:_// +--------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | File                                 | % Funcs | Uncovered Funcs | % Branch | Uncovered Branches | % Lines | Uncovered lines |
+//_:_// +--------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | tests/inputs/asan/safe-many-malloc.c | 100.00  |                 | 100.00   |                    | 100.00  |                 |
+//_:_// +--------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// 100000
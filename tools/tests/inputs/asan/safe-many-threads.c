@@ -0,0 +1,46 @@
+#include <pthread.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+#define NUM_THREADS 16
+#define NUM_ALLOCS 10000
+
+void* worker(void* arg) {
+    long sum = 0;
+    // every thread allocates, resizes and releases chunks at the same time
+    for (int i = 0; i < NUM_ALLOCS; i++) {
+        char* p = (char*)malloc(i % 64 + 1);
+        p[0] = 1;
+        p[i % 64] = 1;
+        char* q = (char*)realloc(p, i % 128 + 1);
+        sum += q[0];
+        free(q);
+    }
+    return (void*)sum;
+}
+
+int main() {
+    pthread_t threads[NUM_THREADS];
+    for (int i = 0; i < NUM_THREADS; i++) {
+        pthread_create(&threads[i], NULL, worker, NULL);
+    }
+    long total = 0;
+    for (int i = 0; i < NUM_THREADS; i++) {
+        void* sum;
+        pthread_join(threads[i], &sum);
+        total += (long)sum;
+    }
+    printf("%ld\n", total);
+}
+
+//_:_// expected stdout:
+//_:_// [+] compiled to IR (covout/safe-many-threads.c.ll)
+//_:_// [+] IR file instrumented (covout/instrumented_safe-many-threads.c.ll)
+//_:_// [+] Binary created (safe-many-threads)
+//_:_// [+] You can run LD_LIBRARY_PATH=../bin/debug ./covout/safe-many-threads 
+//_:_// +---------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | File                                  | % Funcs | Uncovered Funcs | % Branch | Uncovered Branches | % Lines | Uncovered lines |
+//_:_// +---------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | tests/inputs/asan/safe-many-threads.c | 100.00  |                 | 100.00   |                    | 100.00  |                 |
+//_:_// +---------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// 160000
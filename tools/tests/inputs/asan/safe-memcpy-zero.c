@@ -0,0 +1,24 @@
+#include <stdio.h>
+#include <string.h>
+
+// an empty access is never checked, even at NULL
+void myfunc(size_t len) {
+    memcpy(NULL, NULL, len);
+    printf("%zu\n", len);
+}
+
+int main(int argc, char **argv) {
+    myfunc(argc - 1);
+}
+
+//_:_// expected stdout:
+//_:_// [+] compiled to IR (covout/safe-memcpy-zero.c.ll)
+//_:_// [+] IR file instrumented (covout/instrumented_safe-memcpy-zero.c.ll)
+//_:_// [+] Binary created (safe-memcpy-zero)
+//_:_// [+] You can run LD_LIBRARY_PATH=../bin/debug ./covout/safe-memcpy-zero 
+//_:_// +--------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | File                                 | % Funcs | Uncovered Funcs | % Branch | Uncovered Branches | % Lines | Uncovered lines |
+//_:_// +--------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | tests/inputs/asan/safe-memcpy-zero.c | 100.00  |                 | NaN      |                    | 100.00  |                 |
+//_:_// +--------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// 0
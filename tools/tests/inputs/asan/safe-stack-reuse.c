@@ -0,0 +1,31 @@
+#include <stdio.h>
+
+void leave_redzones() {
+    char buf[1024];
+    buf[0] = 'a';
+}
+
+int reuse_stack(int n) {
+    char vla[n]; // VLA is not surrounded by redzones and overlaps the returned frame
+    for (int i = 0; i < n; i++) {
+        vla[i] = 'b';
+    }
+    return vla[n - 1];
+}
+
+int main() {
+    leave_redzones();
+    printf("%d\n", reuse_stack(4096));
+}
+
+//_:_// expected stdout:
+//_:_// [+] compiled to IR (covout/safe-stack-reuse.c.ll)
+//_:_// [+] IR file instrumented (covout/instrumented_safe-stack-reuse.c.ll)
+//_:_// [+] Binary created (safe-stack-reuse)
+//_:_// [+] You can run LD_LIBRARY_PATH=../bin/debug ./covout/safe-stack-reuse 
+//_:_// +--------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | File                                 | % Funcs | Uncovered Funcs | % Branch | Uncovered Branches | % Lines | Uncovered lines |
+//_:_// +--------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | tests/inputs/asan/safe-stack-reuse.c | 100.00  |                 | 100.00   |                    | 100.00  |                 |
+//_:_// +--------------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// 98
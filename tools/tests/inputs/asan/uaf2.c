@@ -23,7 +23,7 @@ int main() {
 //_:_// +--------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
 
 //_:_// expected stderr:
-//_:_// [ASAN] invalid memory access detected at tests/inputs/asan/uaf2.c
+//_:_// [ASAN] invalid memory access detected at tests/inputs/asan/uaf2.c:10:12 in main
 //_:_//    5: main
 //_:_//              at ./tests/inputs/asan/uaf2.c:10:12
 //_:_//    6: __libc_start_call_main
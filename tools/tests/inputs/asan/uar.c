@@ -0,0 +1,30 @@
+// ASAN_OPTIONS: detect_stack_use_after_return=1
+
+int* get_local() {
+    int local = 1;
+    return &local;
+}
+
+int main() {
+    int* ptr = get_local();
+    *ptr = 2; // use after return
+}
+
+//_:_// expected stdout:
+//_:_// [+] compiled to IR (covout/uar.c.ll)
+//_:_// [+] IR file instrumented (covout/instrumented_uar.c.ll)
+//_:_// [+] Binary created (uar)
+//_:_// [+] You can run LD_LIBRARY_PATH=../bin/debug ./covout/uar 
+//_:_// +-------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | File                    | % Funcs | Uncovered Funcs | % Branch | Uncovered Branches | % Lines | Uncovered lines |
+//_:_// +-------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | tests/inputs/asan/uar.c | 100.00  |                 | NaN      |                    | 100.00  |                 |
+//_:_// +-------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+
+//_:_// expected stderr:
+//_:_// [ASAN] invalid memory access detected at tests/inputs/asan/uar.c:10:10 in main
+//_:_//    5: main
+//_:_//              at ./tests/inputs/asan/uar.c:10:10
+//_:_//    6: __libc_start_call_main
+//_:_//    7: __libc_start_main_alias_2
+//_:_//    8: _start
@@ -0,0 +1,29 @@
+// TOOL_FLAGS: --lifetime_markers
+
+int main() {
+    int* ptr;
+    {
+        int local = 1;
+        ptr = &local;
+    }
+    *ptr = 2; // use after scope
+}
+
+//_:_// expected stdout:
+//_:_// [+] compiled to IR (covout/uas.c.ll)
+//_:_// [+] IR file instrumented (covout/instrumented_uas.c.ll)
+//_:_// [+] Binary created (uas)
+//_:_// [+] You can run LD_LIBRARY_PATH=../bin/debug ./covout/uas 
+//_:_// +-------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | File                    | % Funcs | Uncovered Funcs | % Branch | Uncovered Branches | % Lines | Uncovered lines |
+//_:_// +-------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | tests/inputs/asan/uas.c | 100.00  |                 | NaN      |                    | 100.00  |                 |
+//_:_// +-------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+
+//_:_// expected stderr:
+//_:_// [ASAN] invalid memory access detected at tests/inputs/asan/uas.c:9:10 in main
+//_:_//    5: main
+//_:_//              at ./tests/inputs/asan/uas.c:9:10
+//_:_//    6: __libc_start_call_main
+//_:_//    7: __libc_start_main_alias_2
+//_:_//    8: _start
@@ -0,0 +1,24 @@
+#include <stdio.h>
+// TOOL_FLAGS: --msan
+
+int main() {
+    int parsed;
+    int uninit;
+    sscanf("7", "%d", &parsed); // written by libc, so initialized
+    snprintf(NULL, 0, "%d", parsed);
+    snprintf(NULL, 0, "%d", uninit); // use of an uninitialized value
+}
+
+//_:_// expected stdout:
+//_:_// [+] compiled to IR (covout/uninit-arg.c.ll)
+//_:_// [+] IR file instrumented (covout/instrumented_uninit-arg.c.ll)
+//_:_// [+] Binary created (uninit-arg)
+//_:_// [+] You can run LD_LIBRARY_PATH=../bin/debug ./covout/uninit-arg 
+//_:_// +--------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | File                           | % Funcs | Uncovered Funcs | % Branch | Uncovered Branches | % Lines | Uncovered lines |
+//_:_// +--------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+//_:_// | tests/inputs/msan/uninit-arg.c | 100.00  |                 | NaN      |                    | 100.00  |                 |
+//_:_// +--------------------------------+---------+-----------------+----------+--------------------+---------+-----------------+
+
+//_:_// expected stderr:
+//_:_// [MSAN] use of uninitialized value as an argument of a library call detected at tests/inputs/msan/uninit-arg.c:9:5 in main
@@ -6,6 +6,7 @@ fn test_integration() {
     let test_configs = [
         ("tests/run.sh", "tests/inputs/coverage"),
         ("tests/run.sh", "tests/inputs/asan"),
+        ("tests/run.sh", "tests/inputs/msan"),
         ("tests/run.sh", "tests/inputs/race"),
         ("tests/symbolic_run.sh", "tests/inputs/symbolic"),
     ];
@@ -4,6 +4,7 @@ tool=../bin/debug/tools
 common_libpath=../bin/debug
 coverage_libname=coverage_runtime
 asan_libname=asan_runtime
+msan_libname=msan_runtime
 fuzzer_libname=fuzzer_runtime
 symbolic_libname=symbolic_runtime
 race_libname=race_runtime
@@ -12,6 +13,8 @@ outdir=covout
 covout='cov.out'
 input=$1
 outbin=$(echo "$input" | sed 's:.*/::' | sed 's/\.c$//') # remove filepath and extension (e.g., "a/b/c.c" => "c")
+tool_flags=$(sed -n 's:^// TOOL_FLAGS\: *::p' "$input") # compile flags of the test (e.g., "// TOOL_FLAGS: --lifetime_markers")
+asan_options=$(sed -n 's:^// ASAN_OPTIONS\: *::p' "$input") # runtime options of the test (e.g., "// ASAN_OPTIONS: halt_on_error=0")
 
 if [[ "$input" == *.c ]]; then
     compiler="clang"
@@ -23,6 +26,6 @@ else
 fi
 
 # 1. compile
-$tool -c $compiler -o $outdir -b $outbin -q $common_libpath -w $coverage_libname -a $common_libpath -s $asan_libname -f $common_libpath -m $fuzzer_libname -v $common_libpath -g $symbolic_libname -k $common_libpath -j $race_libname -i $input
+$tool -c $compiler -o $outdir -b $outbin -q $common_libpath -w $coverage_libname -a $common_libpath -s $asan_libname -n $common_libpath -u $msan_libname -f $common_libpath -m $fuzzer_libname -v $common_libpath -g $symbolic_libname -k $common_libpath -j $race_libname $tool_flags -i $input
 # 2. run
-ASAN_UNIT_TEST_ENABLED=1 RACE_UNIT_TEST_ENABLED=1 COVERAGE_OUTPUT=$covout COLOR=0 LD_LIBRARY_PATH=$common_libpath ./$outdir/$outbin
+ASAN_OPTIONS=$asan_options ASAN_UNIT_TEST_ENABLED=1 MSAN_UNIT_TEST_ENABLED=1 RACE_UNIT_TEST_ENABLED=1 COVERAGE_OUTPUT=$covout COLOR=0 LD_LIBRARY_PATH=$common_libpath ./$outdir/$outbin